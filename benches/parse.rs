@@ -0,0 +1,192 @@
+//! Throughput of each stage of a run over generated exports of 10MB and
+//! 100MB of XML. The 1GB case takes minutes and several GB of disk, so it
+//! only runs with `BENCH_1GB=1`:
+//!
+//! ```sh
+//! cargo bench --bench parse
+//! BENCH_1GB=1 cargo bench --bench parse -- 1GB
+//! ```
+//!
+//! Fixtures are generated once into `target/bench-fixtures` and reused.
+
+use apple_health_export_parser_rs::generate::{GeneratorConfig, generate_export};
+use apple_health_export_parser_rs::input::ExportInput;
+use apple_health_export_parser_rs::progress::{Progress, Unit};
+use apple_health_export_parser_rs::type_filter::{FilterConfig, TypeFilter};
+use apple_health_export_parser_rs::{
+    HealthRecord, cda, get_file_hash, parse_record_refs, parse_records, read_export_xml, write_csv,
+    write_csv_per_type,
+};
+use criterion::{
+    BenchmarkId, Criterion, SamplingMode, Throughput, criterion_group, criterion_main,
+};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const SEED: u64 = 1;
+
+struct Fixture {
+    label: &'static str,
+    zip: PathBuf,
+    input: ExportInput,
+}
+
+fn fixtures() -> Vec<Fixture> {
+    let mut sizes = vec![("10MB", 10u64 << 20), ("100MB", 100 << 20)];
+    if env::var_os("BENCH_1GB").is_some() {
+        sizes.push(("1GB", 1 << 30));
+    }
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/bench-fixtures");
+    fs::create_dir_all(&dir).unwrap();
+    sizes
+        .into_iter()
+        .map(|(label, size)| {
+            let zip = dir.join(format!("export-{}-seed{}.zip", label, SEED));
+            if !zip.exists() {
+                eprintln!("Generating {} fixture at {}", label, zip.display());
+                let config = GeneratorConfig {
+                    days: 1,
+                    target_size: Some(size),
+                    routes: false,
+                    seed: SEED,
+                    ..GeneratorConfig::default()
+                };
+                let partial = zip.with_extension("zip.partial");
+                generate_export(&config, &partial, &quiet()).unwrap();
+                fs::rename(&partial, &zip).unwrap();
+            }
+            let input = ExportInput::Zip {
+                path: zip.clone(),
+                entry: "apple_health_export/export.xml".to_string(),
+            };
+            Fixture { label, zip, input }
+        })
+        .collect()
+}
+
+fn quiet() -> Progress {
+    Progress::new(true, false)
+}
+
+fn all_types() -> TypeFilter {
+    let config = FilterConfig {
+        include: vec!["*".to_string()],
+        exclude: Vec::new(),
+    };
+    TypeFilter::from_config(&config).unwrap()
+}
+
+/// Criterion's defaults take far too long on files this size.
+fn configure(group: &mut criterion::BenchmarkGroup<'_, criterion::measurement::WallTime>) {
+    group
+        .sample_size(10)
+        .sampling_mode(SamplingMode::Flat)
+        .measurement_time(Duration::from_secs(20));
+}
+
+fn bench_input(c: &mut Criterion, fixtures: &[Fixture]) {
+    let mut group = c.benchmark_group("hashing");
+    configure(&mut group);
+    for fixture in fixtures {
+        let len = fs::metadata(&fixture.zip).unwrap().len();
+        group.throughput(Throughput::Bytes(len));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(fixture.label),
+            &fixture.zip,
+            |b, zip| b.iter(|| get_file_hash(zip, &quiet()).unwrap()),
+        );
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("decompression");
+    configure(&mut group);
+    for fixture in fixtures {
+        let len = read_export_xml(&fixture.input, &quiet()).unwrap().len();
+        group.throughput(Throughput::Bytes(len as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(fixture.label),
+            &fixture.input,
+            |b, input| b.iter(|| read_export_xml(input, &quiet()).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+/// Throughput is in records scanned, whether or not they fall inside the
+/// 12-month window and end up parsed.
+fn bench_parse(c: &mut Criterion, fixtures: &[Fixture]) -> Vec<(&'static str, Vec<HealthRecord>)> {
+    let type_filter = all_types();
+    let progress = quiet();
+    let mut parsed = Vec::new();
+
+    let mut group = c.benchmark_group("parse_records");
+    configure(&mut group);
+    for fixture in fixtures {
+        let mapped = read_export_xml(&fixture.input, &progress).unwrap();
+        let xml = std::str::from_utf8(&mapped).unwrap();
+        let task = progress.task("parsing", None, Unit::Records);
+        let records = parse_records(xml, &type_filter, &task, false).unwrap();
+
+        group.throughput(Throughput::Elements(records.diagnostics.scanned));
+        group.bench_with_input(BenchmarkId::new("owned", fixture.label), xml, |b, xml| {
+            b.iter(|| parse_records(xml, &type_filter, &task, false).unwrap())
+        });
+        group.bench_with_input(
+            BenchmarkId::new("borrowed", fixture.label),
+            xml,
+            |b, xml| b.iter(|| parse_record_refs(xml, &type_filter, &task, false).unwrap()),
+        );
+        parsed.push((fixture.label, records.records));
+    }
+    group.finish();
+    parsed
+}
+
+fn bench_writers(c: &mut Criterion, parsed: &[(&'static str, Vec<HealthRecord>)]) {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut group = c.benchmark_group("writers");
+    configure(&mut group);
+    for (label, records) in parsed {
+        group.throughput(Throughput::Elements(records.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("json", label), records, |b, records| {
+            let path = dir.path().join("output.json");
+            b.iter(|| {
+                // As `main` writes output.json.
+                let json = serde_json::to_string_pretty(records).unwrap();
+                fs::write(&path, json).unwrap();
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("csv", label), records, |b, records| {
+            let path = dir.path().join("output.csv");
+            b.iter(|| write_csv(records, &path).unwrap())
+        });
+        group.bench_with_input(
+            BenchmarkId::new("csv_per_type", label),
+            records,
+            |b, records| {
+                let path = dir.path().join("csv");
+                b.iter(|| write_csv_per_type(records, &path).unwrap())
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("cda", label), records, |b, records| {
+            let path = dir.path().join("output_cda.xml");
+            b.iter(|| cda::write_cda(records, &path).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn benches(c: &mut Criterion) {
+    let fixtures = fixtures();
+    bench_input(c, &fixtures);
+    let parsed = bench_parse(c, &fixtures);
+    bench_writers(c, &parsed);
+}
+
+criterion_group!(parse, benches);
+criterion_main!(parse);
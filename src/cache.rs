@@ -0,0 +1,251 @@
+use crate::error::ParseError;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tempfile::NamedTempFile;
+
+const CACHE_MAGIC: &[u8; 4] = b"AHEP";
+
+/// Bump whenever the parsed output changes shape or content for the same
+/// input, so stale entries are never deserialized into the new layout.
+pub const CACHE_FORMAT_VERSION: u32 = 9;
+
+const CACHE_DIR_NAME: &str = "apple_health_export_parser_rs";
+
+/// The platform cache directory (e.g. `~/.cache` or `~/Library/Caches`),
+/// falling back to the temp dir on systems without one.
+pub fn get_cache_dir() -> PathBuf {
+    let mut cache_dir = dirs::cache_dir().unwrap_or_else(env::temp_dir);
+    cache_dir.push(CACHE_DIR_NAME);
+    cache_dir
+}
+
+/// Where versions before the platform cache dir wrote their raw
+/// `<hash>.xml` entries.
+pub fn legacy_cache_dir() -> PathBuf {
+    env::temp_dir().join(CACHE_DIR_NAME)
+}
+
+/// The legacy location to clean up along with the default `cache_dir`, or
+/// `None` where the default already is the temp dir.
+pub fn default_legacy_dir(cache_dir: &Path) -> Option<PathBuf> {
+    let legacy = legacy_cache_dir();
+    (legacy != cache_dir).then_some(legacy)
+}
+
+/// Derives the cache key from the export hash and everything that affects
+/// the parsed result, including the crate and cache format versions.
+pub fn cache_key(export_hash: &str, parser_config: &[String]) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+    hasher.update(&CACHE_FORMAT_VERSION.to_le_bytes());
+    hasher.update(export_hash.as_bytes());
+    for part in parser_config {
+        hasher.update(&(part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+fn cache_path(cache_dir: &Path, key: &str) -> PathBuf {
+    cache_dir.join(format!("{}.bin", key))
+}
+
+pub fn load<T: DeserializeOwned>(cache_dir: &Path, key: &str) -> Option<T> {
+    let file = File::open(cache_path(cache_dir, key)).ok()?;
+    // Touch the entry so pruning by size evicts the least recently used ones.
+    let _ = file.set_modified(SystemTime::now());
+    let mut reader = BufReader::new(file);
+
+    let mut header = [0u8; 8];
+    reader.read_exact(&mut header).ok()?;
+    if &header[..4] != CACHE_MAGIC || header[4..] != CACHE_FORMAT_VERSION.to_le_bytes() {
+        return None;
+    }
+
+    bincode::deserialize_from(reader).ok()
+}
+
+pub fn save<T: Serialize>(cache_dir: &Path, key: &str, value: &T) -> Result<(), ParseError> {
+    let cache_error = |e: &dyn std::fmt::Display| {
+        ParseError::Cache(format!("writing to {}: {}", cache_dir.display(), e))
+    };
+    fs::create_dir_all(cache_dir).map_err(|e| cache_error(&e))?;
+
+    // Written to a temp file and renamed so an interrupted run never leaves a
+    // truncated entry behind.
+    let mut tmp = NamedTempFile::new_in(cache_dir).map_err(|e| cache_error(&e))?;
+    {
+        let mut writer = BufWriter::new(tmp.as_file_mut());
+        writer
+            .write_all(CACHE_MAGIC)
+            .and_then(|()| writer.write_all(&CACHE_FORMAT_VERSION.to_le_bytes()))
+            .map_err(|e| cache_error(&e))?;
+        bincode::serialize_into(&mut writer, value).map_err(|e| cache_error(&e))?;
+        writer.flush().map_err(|e| cache_error(&e))?;
+    }
+    tmp.persist(cache_path(cache_dir, key))
+        .map_err(|e| cache_error(&e))?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// Lists the `<key>.bin` entries of `cache_dir` and, if given, the raw
+/// `<hash>.xml` entries older versions of the tool left in `legacy_dir`,
+/// oldest first. Other files are never taken for entries.
+pub fn list_entries(cache_dir: &Path, legacy_dir: Option<&Path>) -> io::Result<Vec<CacheEntry>> {
+    let mut entries = Vec::new();
+    list_dir(cache_dir, &["bin"], &mut entries)?;
+    if let Some(legacy_dir) = legacy_dir {
+        list_dir(legacy_dir, &["bin", "xml"], &mut entries)?;
+    }
+    entries.sort_by_key(|entry| entry.modified);
+    Ok(entries)
+}
+
+fn list_dir(dir: &Path, extensions: &[&str], entries: &mut Vec<CacheEntry>) -> io::Result<()> {
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for dir_entry in read_dir {
+        let path = dir_entry?.path();
+        if !is_entry_name(&path, extensions) {
+            continue;
+        }
+        let metadata = fs::metadata(&path)?;
+        if !metadata.is_file() {
+            continue;
+        }
+        entries.push(CacheEntry {
+            path,
+            size: metadata.len(),
+            modified: metadata.modified()?,
+        });
+    }
+    Ok(())
+}
+
+/// Whether `path` is named like an entry: a blake3 hex digest with one of
+/// `extensions`.
+fn is_entry_name(path: &Path, extensions: &[&str]) -> bool {
+    let is_key = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| {
+            stem.len() == 64 && stem.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        });
+    let has_extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.contains(&ext));
+    is_key && has_extension
+}
+
+/// Removes every entry `list_entries` finds, returning how many were removed
+/// and the bytes freed.
+pub fn clear(cache_dir: &Path, legacy_dir: Option<&Path>) -> io::Result<(usize, u64)> {
+    remove_entries(list_entries(cache_dir, legacy_dir)?)
+}
+
+/// Removes the entries `list_entries` finds that are older than `max_age`,
+/// then evicts the least recently used until they fit in `max_size` bytes.
+pub fn prune(
+    cache_dir: &Path,
+    legacy_dir: Option<&Path>,
+    max_size: Option<u64>,
+    max_age: Option<Duration>,
+) -> io::Result<(usize, u64)> {
+    let now = SystemTime::now();
+    let mut expired = Vec::new();
+    let mut kept = Vec::new();
+
+    for entry in list_entries(cache_dir, legacy_dir)? {
+        let age = now.duration_since(entry.modified).unwrap_or_default();
+        if max_age.is_some_and(|max_age| age > max_age) {
+            expired.push(entry);
+        } else {
+            kept.push(entry);
+        }
+    }
+
+    if let Some(max_size) = max_size {
+        let mut total: u64 = kept.iter().map(|entry| entry.size).sum();
+        let mut evict = 0;
+        while total > max_size && evict < kept.len() {
+            total -= kept[evict].size;
+            evict += 1;
+        }
+        expired.extend(kept.drain(..evict));
+    }
+
+    remove_entries(expired)
+}
+
+fn remove_entries(entries: Vec<CacheEntry>) -> io::Result<(usize, u64)> {
+    let mut freed = 0;
+    for entry in &entries {
+        fs::remove_file(&entry.path)?;
+        freed += entry.size;
+    }
+    Ok((entries.len(), freed))
+}
+
+/// Parses sizes such as `500M`, `2GiB` or `1048576` (binary multiples).
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(input.len());
+    let (number, suffix) = input.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", input))?;
+
+    let multiplier: u64 = match suffix.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        other => return Err(format!("unknown size unit '{}'", other)),
+    };
+
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Parses ages such as `30d`, `12h` or `2w`.
+pub fn parse_age(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, suffix) = input.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid age '{}'", input))?;
+
+    let seconds = match suffix.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        "" => return Err(format!("age '{}' needs a unit (s, m, h, d or w)", input)),
+        other => return Err(format!("unknown age unit '{}'", other)),
+    };
+
+    Ok(Duration::from_secs(number * seconds))
+}
@@ -0,0 +1,413 @@
+use crate::chunker;
+use crate::diagnostics::{Diagnostics, SkipReason};
+use crate::error::{ElementFault, ParseError};
+use crate::health_type::RecordType;
+use crate::input::Document;
+use crate::progress::Task;
+use crate::recovery;
+use crate::type_filter::TypeFilter;
+use crate::{HealthRecord, METADATA_KEYS_TO_INCLUDE, ParsedRecords, attr_str};
+use chrono::Utc;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use smallstr::SmallString;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// LOINC codes for the HealthKit types that have a direct equivalent; other
+/// types are written with a `nullFlavor` code and their identifier as text.
+fn loinc_code(record_type: &RecordType) -> Option<(&'static str, &'static str)> {
+    let code = match record_type {
+        RecordType::HeartRate => ("8867-4", "Heart rate"),
+        RecordType::RestingHeartRate => ("40443-4", "Heart rate --resting"),
+        RecordType::RespiratoryRate => ("9279-1", "Respiratory rate"),
+        RecordType::BodyTemperature => ("8310-5", "Body temperature"),
+        RecordType::OxygenSaturation => ("59408-5", "Oxygen saturation"),
+        RecordType::BloodPressureSystolic => ("8480-6", "Systolic blood pressure"),
+        RecordType::BloodPressureDiastolic => ("8462-4", "Diastolic blood pressure"),
+        RecordType::BodyMass => ("29463-7", "Body weight"),
+        RecordType::Height => ("8302-2", "Body height"),
+        RecordType::BodyMassIndex => ("39156-5", "Body mass index"),
+        RecordType::StepCount => ("55423-8", "Number of steps"),
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// HealthKit unit strings that differ from their UCUM spelling.
+fn ucum_unit(unit: &str) -> &str {
+    match unit {
+        "count/min" => "/min",
+        "count" => "{count}",
+        "degC" => "Cel",
+        "degF" => "[degF]",
+        "lb" => "[lb_av]",
+        "mmHg" => "mm[Hg]",
+        other => other,
+    }
+}
+
+/// `2024-03-01 07:15:00 +0100` to the CDA `TS` form `20240301071500+0100`.
+fn to_cda_time(date: &str) -> Option<String> {
+    let (date_time, offset) = date.rsplit_once(' ')?;
+    let digits: String = date_time.chars().filter(char::is_ascii_digit).collect();
+    (digits.len() == 14).then(|| format!("{}{}", digits, offset))
+}
+
+/// The inverse of `to_cda_time`, so CDA records sort and filter like ones
+/// parsed from `export.xml`.
+fn from_cda_time(ts: &str) -> Option<String> {
+    let digits = ts.get(..14)?;
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let offset = ts[14..].trim();
+    Some(format!(
+        "{}-{}-{} {}:{}:{} {}",
+        &digits[0..4],
+        &digits[4..6],
+        &digits[6..8],
+        &digits[8..10],
+        &digits[10..12],
+        &digits[12..14],
+        if offset.is_empty() { "+0000" } else { offset }
+    ))
+}
+
+/// Parses the observations in `export_cda.xml` into `HealthRecord`s, applying
+/// the same type filter and date window as `parse_records`.
+pub fn parse_cda(
+    xml: &str,
+    type_filter: &TypeFilter,
+    task: &Task,
+    lenient: bool,
+) -> Result<ParsedRecords, ParseError> {
+    // The body only skips the prolog, whose DTD may mention the element too.
+    let body = recovery::root_start(xml, Document::Cda.root_element()).unwrap_or(0);
+    let parts = rayon::current_num_threads() * 8;
+    let batches = chunker::par_elements(xml, body, "observation", parts, |elements| {
+        let mut batch = ParsedRecords::default();
+        for (start, element) in elements {
+            batch.diagnostics.scanned += 1;
+            match parse_observation(element, start, type_filter, &mut batch.diagnostics) {
+                Ok(Some(record)) => batch.records.push(record),
+                Ok(None) => {}
+                Err(fault) => batch.fail(xml, "observation", start, fault),
+            }
+        }
+        task.inc(batch.diagnostics.scanned);
+        batch
+    });
+
+    ParsedRecords::concat(xml, batches, lenient)
+}
+
+fn parse_observation(
+    element: &str,
+    start: usize,
+    type_filter: &TypeFilter,
+    diagnostics: &mut Diagnostics,
+) -> Result<Option<HealthRecord>, ElementFault> {
+    let mut reader = Reader::from_str(element);
+    reader.config_mut().trim_text(true);
+
+    let mut buf = Vec::with_capacity(2048);
+    // Names of the open elements below (and including) the observation.
+    let mut path: Vec<Vec<u8>> = Vec::new();
+
+    let mut text_type = None;
+    let mut text_value = None;
+    let mut text_unit = None;
+    let mut text_source_name = None;
+    let mut display_name = None;
+    let mut value = None;
+    let mut unit = None;
+    let mut start_date = None;
+    let mut end_date = None;
+    let mut metadata_key: Option<String> = None;
+    let mut metadata: HashMap<SmallString<[u8; 16]>, SmallString<[u8; 32]>> = HashMap::new();
+
+    loop {
+        // Where the next tag starts, to locate errors in its attributes.
+        let position = reader.buffer_position();
+        let event = reader
+            .read_event_into(&mut buf)
+            .map_err(|e| ElementFault::xml(reader.error_position(), e))?;
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let name = e.name().as_ref().to_vec();
+                let parent = path.last().map(Vec::as_slice);
+                let grandparent = path.len().checked_sub(2).map(|i| path[i].as_slice());
+
+                for attr in recovery::attributes(e) {
+                    let attr = attr.map_err(|e| ElementFault::xml(position, e))?;
+                    let v_str = attr_str(&attr.value, position)?;
+                    match (name.as_slice(), attr.key.as_ref()) {
+                        (b"code", b"displayName") if parent == Some(b"observation") => {
+                            display_name = Some(v_str.to_string());
+                        }
+                        (b"value", b"value") if parent == Some(b"observation") => {
+                            value = Some(v_str.to_string());
+                        }
+                        (b"value", b"unit") if parent == Some(b"observation") => {
+                            unit = Some(v_str.to_string());
+                        }
+                        (b"low", b"value")
+                            if parent == Some(b"effectiveTime")
+                                && grandparent == Some(b"observation") =>
+                        {
+                            start_date = from_cda_time(&v_str);
+                        }
+                        (b"high", b"value")
+                            if parent == Some(b"effectiveTime")
+                                && grandparent == Some(b"observation") =>
+                        {
+                            end_date = from_cda_time(&v_str);
+                        }
+                        _ => {}
+                    }
+                }
+
+                if matches!(event, Event::Start(_)) {
+                    path.push(name);
+                } else if path.is_empty() {
+                    // A self-closing observation has nothing more to read.
+                    break;
+                }
+            }
+            Event::Text(ref t) => {
+                let text = t.unescape().map_err(|e| ElementFault::xml(position, e))?;
+                let text = text.into_owned();
+                let field = path.last().map(Vec::as_slice);
+                let parent = path.len().checked_sub(2).map(|i| path[i].as_slice());
+                match (parent, field) {
+                    (Some(b"text"), Some(b"type")) => text_type = Some(text),
+                    (Some(b"text"), Some(b"value")) => text_value = Some(text),
+                    (Some(b"text"), Some(b"unit")) => text_unit = Some(text),
+                    (Some(b"text"), Some(b"sourceName")) => text_source_name = Some(text),
+                    (Some(b"observation"), Some(b"value")) => value = Some(text),
+                    (Some(b"metadataEntry"), Some(b"key")) => metadata_key = Some(text),
+                    (Some(b"metadataEntry"), Some(b"value")) => {
+                        if let Some(key) = metadata_key.take()
+                            && METADATA_KEYS_TO_INCLUDE.contains(&key.as_str())
+                        {
+                            metadata.insert(SmallString::from(key), SmallString::from(text));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(ref e) => {
+                path.pop();
+                if e.name().as_ref() == b"observation" {
+                    break;
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+
+        buf.clear();
+    }
+
+    let Some(record_type) = text_type.or(display_name) else {
+        diagnostics.skip(SkipReason::MissingType, None, start, element, String::new);
+        return Ok(None);
+    };
+    if !type_filter.allows(&record_type) {
+        diagnostics.skip(
+            SkipReason::FilteredByType,
+            Some(&record_type),
+            start,
+            element,
+            String::new,
+        );
+        return Ok(None);
+    }
+    if !start_date
+        .as_deref()
+        .is_some_and(|date| type_filter.allows_date(date))
+    {
+        diagnostics.skip(
+            SkipReason::FilteredByDate,
+            Some(&record_type),
+            start,
+            element,
+            || match &start_date {
+                Some(date) => format!("startDate {} is before the cutoff", date),
+                None => "no effectiveTime low value".to_string(),
+            },
+        );
+        return Ok(None);
+    }
+    diagnostics.parsed += 1;
+
+    Ok(Some(HealthRecord {
+        record_type: Some(RecordType::from_identifier(&record_type)),
+        unit: text_unit.or(unit).map(SmallString::from),
+        value: text_value.or(value).map(SmallString::from),
+        start_date: start_date.map(SmallString::from),
+        end_date: end_date.map(SmallString::from),
+        metadata,
+        source_name: text_source_name.map(SmallString::from),
+        export_id: None,
+    }))
+}
+
+/// Writes `records` as an HL7 CDA R2 document in the same shape Apple uses
+/// for `export_cda.xml`: one vital-signs organizer per observation, with the
+/// HealthKit identifier, value and unit repeated in the narrative `text`.
+pub fn write_cda(records: &[HealthRecord], path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut w = BufWriter::new(File::create(path)?);
+    let now = Utc::now().format("%Y%m%d%H%M%S+0000");
+
+    write!(
+        w,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<ClinicalDocument xmlns="urn:hl7-org:v3" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" classCode="DOCCLIN" moodCode="EVN">
+<realmCode code="US"/>
+<typeId root="2.16.840.1.113883.1.3" extension="POCD_HD000040"/>
+<templateId root="2.16.840.1.113883.10.20.22.1.1"/>
+<id extension="Health Export" root="1.1.1.1.1.1.1.1.1"/>
+<code code="34109-9" codeSystem="2.16.840.1.113883.6.1" codeSystemName="LOINC" displayName="Note"/>
+<title>Health Data Export</title>
+<effectiveTime value="{now}"/>
+<confidentialityCode code="N" codeSystem="2.16.840.1.113883.5.25"/>
+<languageCode code="en-US"/>
+<recordTarget>
+<patientRole>
+<id nullFlavor="NI"/>
+<patient>
+<name nullFlavor="NI"/>
+<administrativeGenderCode nullFlavor="NI"/>
+<birthTime nullFlavor="NI"/>
+</patient>
+</patientRole>
+</recordTarget>
+<author>
+<time value="{now}"/>
+<assignedAuthor>
+<id nullFlavor="NI"/>
+<assignedAuthoringDevice>
+<softwareName>{software}</softwareName>
+</assignedAuthoringDevice>
+</assignedAuthor>
+</author>
+<custodian>
+<assignedCustodian>
+<representedCustodianOrganization>
+<id nullFlavor="NI"/>
+</representedCustodianOrganization>
+</assignedCustodian>
+</custodian>
+<component>
+<structuredBody>
+<component>
+<section>
+<templateId root="2.16.840.1.113883.10.20.22.2.4.1"/>
+<code code="8716-3" codeSystem="2.16.840.1.113883.6.1" codeSystemName="LOINC" displayName="Vital Signs"/>
+<title>Vital Signs</title>
+<text/>
+"#,
+        now = now,
+        software = env!("CARGO_PKG_NAME"),
+    )?;
+
+    for rec in records {
+        let Some(record_type) = &rec.record_type else {
+            continue;
+        };
+        let value = rec.value.as_deref().unwrap_or("");
+        let unit = rec.unit.as_deref().unwrap_or("");
+        let source_name = rec.source_name.as_deref().unwrap_or("");
+        let low = rec.start_date.as_deref().and_then(to_cda_time);
+        let high = rec
+            .end_date
+            .as_deref()
+            .and_then(to_cda_time)
+            .or(low.clone());
+        let effective_time = match (&low, &high) {
+            (Some(low), Some(high)) => format!(
+                "<effectiveTime><low value=\"{}\"/><high value=\"{}\"/></effectiveTime>",
+                low, high
+            ),
+            _ => "<effectiveTime nullFlavor=\"NI\"/>".to_string(),
+        };
+        let code = match loinc_code(record_type) {
+            Some((code, display_name)) => format!(
+                "<code code=\"{}\" codeSystem=\"2.16.840.1.113883.6.1\" codeSystemName=\"LOINC\" displayName=\"{}\"/>",
+                code, display_name
+            ),
+            None => format!(
+                "<code nullFlavor=\"OTH\"><originalText>{}</originalText></code>",
+                escape(record_type.identifier())
+            ),
+        };
+        let coded_value = if value.parse::<f64>().is_ok() {
+            format!(
+                "<value xsi:type=\"PQ\" value=\"{}\" unit=\"{}\"/>",
+                escape(value),
+                escape(ucum_unit(unit))
+            )
+        } else {
+            format!("<value xsi:type=\"ST\">{}</value>", escape(value))
+        };
+
+        let mut metadata = String::new();
+        for (key, value) in &rec.metadata {
+            metadata.push_str(&format!(
+                "<metadataEntry><key>{}</key><value>{}</value></metadataEntry>\n",
+                escape(key.as_str()),
+                escape(value.as_str())
+            ));
+        }
+
+        write!(
+            w,
+            r#"<entry typeCode="DRIV">
+<organizer classCode="CLUSTER" moodCode="EVN">
+<templateId root="2.16.840.1.113883.10.20.22.4.26"/>
+<id nullFlavor="NI"/>
+<code code="46680005" codeSystem="2.16.840.1.113883.6.96" codeSystemName="SNOMED CT" displayName="Vital signs"/>
+<statusCode code="completed"/>
+{effective_time}
+<component>
+<observation classCode="OBS" moodCode="EVN">
+<templateId root="2.16.840.1.113883.10.20.22.4.27"/>
+<id nullFlavor="NI"/>
+{code}
+<text>
+<sourceName>{source_name}</sourceName>
+<value>{value}</value>
+<type>{record_type}</type>
+<unit>{unit}</unit>
+{metadata}</text>
+<statusCode code="completed"/>
+{effective_time}
+{coded_value}
+</observation>
+</component>
+</organizer>
+</entry>
+"#,
+            effective_time = effective_time,
+            code = code,
+            source_name = escape(source_name),
+            value = escape(value),
+            record_type = escape(record_type.identifier()),
+            unit = escape(unit),
+            metadata = metadata,
+            coded_value = coded_value,
+        )?;
+    }
+
+    write!(
+        w,
+        "</section>\n</component>\n</structuredBody>\n</component>\n</ClinicalDocument>\n"
+    )?;
+    w.flush()?;
+    Ok(())
+}
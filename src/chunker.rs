@@ -0,0 +1,369 @@
+//! Finds the elements the parsers want without parsing the whole document,
+//! so large ranges of it can go to rayon workers.
+//!
+//! The scan is lexical: it only tells tags apart from text, and knows enough
+//! about quoted attribute values, comments, CDATA sections and processing
+//! instructions that a literal `<Record ` inside any of them is never taken
+//! for a tag. Ranges are split speculatively at the first `<name` after an
+//! even share of the bytes; a split that turns out to sit inside other
+//! markup is caught when the range before it scans past it, and the range
+//! is scanned again from there.
+
+use memchr::{memchr, memchr3, memmem};
+use rayon::prelude::*;
+
+/// The outermost `name` elements starting in one range of a document, in
+/// document order, each with its byte offset. An element is the exact slice
+/// from its `<` to the end of its closing tag, wherever it is nested: records
+/// inside a `<Correlation>` are found like any other.
+///
+/// A `name` element never contains another one, so a second start tag ends
+/// the first; that is also where an element missing its closing tag ends.
+pub struct Elements<'a> {
+    xml: &'a str,
+    name: &'a str,
+    pos: usize,
+    until: usize,
+}
+
+impl<'a> Iterator for Elements<'a> {
+    type Item = (usize, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = self.xml.as_bytes();
+        while self.pos < self.until {
+            let Some(start) = memchr(b'<', &bytes[self.pos..]).map(|i| self.pos + i) else {
+                self.pos = bytes.len();
+                break;
+            };
+            if start >= self.until {
+                self.pos = start;
+                break;
+            }
+            let (tag, end) = tag_at(bytes, start);
+            self.pos = end;
+            if !is_named(bytes, start, self.name) {
+                continue;
+            }
+            match tag {
+                Tag::Empty => return Some((start, &self.xml[start..end])),
+                Tag::Start => {
+                    self.pos = self.element_end(end);
+                    return Some((start, &self.xml[start..self.pos]));
+                }
+                Tag::End | Tag::Other => {}
+            }
+        }
+        None
+    }
+}
+
+impl Elements<'_> {
+    /// Where the element whose start tag ends at `from` ends: after its
+    /// closing tag, or before the next `name` start tag if that comes first.
+    fn element_end(&self, mut from: usize) -> usize {
+        let bytes = self.xml.as_bytes();
+        let mut depth = 1usize;
+        while let Some(start) = memchr(b'<', &bytes[from..]).map(|i| from + i) {
+            let (tag, end) = tag_at(bytes, start);
+            match tag {
+                Tag::Start | Tag::Empty if is_named(bytes, start, self.name) => return start,
+                Tag::Start => depth += 1,
+                Tag::End => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return end;
+                    }
+                }
+                Tag::Empty | Tag::Other => {}
+            }
+            from = end;
+        }
+        bytes.len()
+    }
+}
+
+/// Runs `parse` over the outermost `name` elements of `xml[from..]` in about
+/// `parts` ranges at once, returning one batch per range in document order.
+/// `from` must not be inside markup; the root start tag is the usual choice.
+/// `parse` may stop early, in which case the rest of its range is skipped.
+/// A document without a single `<name` is not scanned at all.
+pub fn par_elements<'a, B: Send>(
+    xml: &'a str,
+    from: usize,
+    name: &'a str,
+    parts: usize,
+    parse: impl Fn(&mut Elements<'a>) -> B + Sync,
+) -> Vec<B> {
+    let run = |start: usize, until: usize| {
+        let mut elements = Elements {
+            xml,
+            name,
+            pos: start,
+            until,
+        };
+        let batch = parse(&mut elements);
+        elements.by_ref().for_each(drop);
+        (batch, elements.pos)
+    };
+
+    if memmem::find(&xml.as_bytes()[from..], format!("<{}", name).as_bytes()).is_none() {
+        return Vec::new();
+    }
+    let mut starts = candidate_starts(xml, from, name, parts);
+    let until = |starts: &[usize], i: usize| starts.get(i + 1).copied().unwrap_or(xml.len());
+    let mut batches: Vec<(B, usize)> = (0..starts.len())
+        .into_par_iter()
+        .map(|i| run(starts[i], until(&starts, i)))
+        .collect();
+
+    // Each range stops on the first tag boundary at or past the next range's
+    // start, so stopping anywhere else means that start was not a real tag.
+    for i in 1..starts.len() {
+        let reached = batches[i - 1].1;
+        if reached != starts[i] {
+            starts[i] = reached;
+            batches[i] = run(reached, until(&starts, i));
+        }
+    }
+    batches.into_iter().map(|(batch, _)| batch).collect()
+}
+
+/// The children of one element, as found by `par_children`.
+pub struct Children<'a> {
+    /// Byte offset and name of every child `keep` accepted, in document order.
+    pub elements: Vec<(usize, &'a str)>,
+    /// Whether no end tag closes more than the parent, counting only starts
+    /// and ends; names are not compared.
+    pub balanced: bool,
+    /// The depth at the end of `xml`, relative to the parent's children:
+    /// `-1` once the parent is closed, `0` when it is left open after its
+    /// last complete child, as in a truncated document.
+    pub depth: isize,
+}
+
+/// What one range of `par_children` found, relative to the depth it
+/// started at.
+struct ChildRange<'a> {
+    /// Where the scan stopped, as in `Elements`.
+    reached: usize,
+    depth: isize,
+    min_depth: isize,
+    /// Start tags at the shallowest depth reached so far, with that depth;
+    /// those at the parent's child depth are the children.
+    candidates: Vec<(isize, usize, &'a str)>,
+}
+
+/// Finds the children of the element whose start tag is at `parent`, in
+/// about `parts` ranges at once. Each range counts depth from wherever it
+/// starts, and keeps the tags at the shallowest depth it has seen; once the
+/// ranges before it are added up, that relative depth tells which of those
+/// are children. Only children `keep` accepts by name are returned.
+pub fn par_children<'a>(
+    xml: &'a str,
+    parent: usize,
+    parts: usize,
+    keep: impl Fn(&str) -> bool + Sync,
+) -> Children<'a> {
+    let bytes = xml.as_bytes();
+    // A document truncated before the parent has no children.
+    if parent >= bytes.len() {
+        return Children {
+            elements: Vec::new(),
+            balanced: true,
+            depth: 0,
+        };
+    }
+    let from = tag_at(bytes, parent).1;
+    let step = ((bytes.len() - from) / parts.max(1)).max(1);
+    let mut starts = vec![from];
+    let mut search = from + step;
+    while let Some(i) = bytes.get(search..).and_then(|rest| memchr(b'<', rest)) {
+        starts.push(search + i);
+        search += i + step;
+    }
+
+    let run = |start: usize, until: usize| scan_children(xml, start, until, &keep);
+    let until = |starts: &[usize], i: usize| starts.get(i + 1).copied().unwrap_or(xml.len());
+    let mut ranges: Vec<ChildRange> = (0..starts.len())
+        .into_par_iter()
+        .map(|i| run(starts[i], until(&starts, i)))
+        .collect();
+    // As in `par_elements`, a start inside other markup is scanned again
+    // from where the range before it really ended.
+    for i in 1..starts.len() {
+        let reached = ranges[i - 1].reached;
+        if reached != starts[i] {
+            starts[i] = reached;
+            ranges[i] = run(reached, until(&starts, i));
+        }
+    }
+
+    let mut depth = 0;
+    let mut balanced = true;
+    let mut elements = Vec::new();
+    for range in ranges {
+        balanced &= depth + range.min_depth >= -1;
+        let children = range
+            .candidates
+            .into_iter()
+            .filter(|&(candidate, _, _)| depth + candidate == 0);
+        elements.extend(children.map(|(_, offset, name)| (offset, name)));
+        depth += range.depth;
+    }
+    Children {
+        elements,
+        balanced,
+        depth,
+    }
+}
+
+fn scan_children<'a>(
+    xml: &'a str,
+    mut pos: usize,
+    until: usize,
+    keep: &impl Fn(&str) -> bool,
+) -> ChildRange<'a> {
+    let bytes = xml.as_bytes();
+    let (mut depth, mut min_depth) = (0, 0);
+    let mut candidates = Vec::new();
+    while pos < until {
+        let Some(start) = memchr(b'<', &bytes[pos..]).map(|i| pos + i) else {
+            pos = bytes.len();
+            break;
+        };
+        if start >= until {
+            pos = start;
+            break;
+        }
+        let (tag, end) = tag_at(bytes, start);
+        pos = end;
+        match tag {
+            Tag::Start | Tag::Empty => {
+                if depth == min_depth {
+                    let name = name_at(xml, start);
+                    if keep(name) {
+                        candidates.push((depth, start, name));
+                    }
+                }
+                if matches!(tag, Tag::Start) {
+                    depth += 1;
+                }
+            }
+            Tag::End => {
+                depth -= 1;
+                min_depth = min_depth.min(depth);
+            }
+            Tag::Other => {}
+        }
+    }
+    ChildRange {
+        reached: pos,
+        depth,
+        min_depth,
+        candidates,
+    }
+}
+
+/// The name of the tag starting with the `<` at `start`.
+fn name_at(xml: &str, start: usize) -> &str {
+    let name = &xml[start + 1..];
+    let end = name
+        .find(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/')
+        .unwrap_or(name.len());
+    &name[..end]
+}
+
+/// `from` followed by the first `<name` after every `1/parts` of the rest of
+/// `xml`. Only the text is checked, so a start may still be inside a comment
+/// or an attribute value.
+fn candidate_starts(xml: &str, from: usize, name: &str, parts: usize) -> Vec<usize> {
+    let bytes = xml.as_bytes();
+    let tag = format!("<{}", name);
+    let finder = memmem::Finder::new(tag.as_bytes());
+    let step = ((bytes.len() - from) / parts.max(1)).max(1);
+
+    let mut starts = Vec::with_capacity(parts + 1);
+    starts.push(from);
+    let mut search = from + step;
+    while let Some(found) = bytes.get(search..).and_then(|rest| finder.find(rest)) {
+        let start = search + found;
+        if is_named(bytes, start, name) {
+            starts.push(start);
+            search = start + step;
+        } else {
+            search = start + tag.len();
+        }
+    }
+    starts
+}
+
+enum Tag {
+    Start,
+    End,
+    Empty,
+    /// Comments, CDATA sections, processing instructions and declarations.
+    Other,
+}
+
+/// The kind of markup starting with the `<` at `start`, and where it ends.
+/// Markup that is never closed runs to the end of the document.
+fn tag_at(bytes: &[u8], start: usize) -> (Tag, usize) {
+    let rest = &bytes[start..];
+    let after = |open: usize, close: &[u8]| {
+        memmem::find(&rest[open..], close).map_or(bytes.len(), |i| start + open + i + close.len())
+    };
+    if rest.starts_with(b"<!--") {
+        (Tag::Other, after(4, b"-->"))
+    } else if rest.starts_with(b"<![CDATA[") {
+        (Tag::Other, after(9, b"]]>"))
+    } else if rest.starts_with(b"<?") {
+        (Tag::Other, after(2, b"?>"))
+    } else if rest.starts_with(b"<!") {
+        (Tag::Other, tag_end(bytes, start + 2))
+    } else if rest.starts_with(b"</") {
+        (Tag::End, tag_end(bytes, start + 2))
+    } else {
+        let end = tag_end(bytes, start + 1);
+        let empty = end >= start + 3 && bytes[end - 1] == b'>' && bytes[end - 2] == b'/';
+        (if empty { Tag::Empty } else { Tag::Start }, end)
+    }
+}
+
+/// Just past the `>` closing a tag, skipping any `>` in quoted values.
+fn tag_end(bytes: &[u8], mut from: usize) -> usize {
+    // Nearly every tag has only double-quoted values without a `>` in them,
+    // which counting quotes up to the first `>` confirms far faster than
+    // hopping from quote to quote.
+    if let Some(i) = memchr(b'>', &bytes[from..]) {
+        let (quotes, apostrophes) = bytes[from..from + i]
+            .iter()
+            .fold((0u32, 0u32), |(q, a), &b| {
+                (q + u32::from(b == b'"'), a + u32::from(b == b'\''))
+            });
+        if quotes % 2 == 0 && apostrophes == 0 {
+            return from + i + 1;
+        }
+    }
+    while let Some(i) = memchr3(b'>', b'"', b'\'', &bytes[from..]) {
+        let found = from + i;
+        let quote = bytes[found];
+        if quote == b'>' {
+            return found + 1;
+        }
+        match memchr(quote, &bytes[found + 1..]) {
+            Some(j) => from = found + 1 + j + 1,
+            None => break,
+        }
+    }
+    bytes.len()
+}
+
+/// Whether the tag at `start` is named exactly `name`.
+fn is_named(bytes: &[u8], start: usize, name: &str) -> bool {
+    let name_end = start + 1 + name.len();
+    bytes.get(start + 1..name_end) == Some(name.as_bytes())
+        && bytes
+            .get(name_end)
+            .is_none_or(|&b| b.is_ascii_whitespace() || b == b'>' || b == b'/')
+}
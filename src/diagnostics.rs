@@ -0,0 +1,225 @@
+use crate::error::{self, ErrorKind};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// How many example elements are kept for each skip reason.
+const MAX_SAMPLES: usize = 5;
+
+/// Longest excerpt of an element kept in a sample.
+const MAX_EXCERPT_LEN: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    FilteredByType,
+    FilteredByDate,
+    /// Parsed fine but carries no record type to filter on.
+    MissingType,
+    MalformedXml,
+    BadEncoding,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SkipReason::FilteredByType => "filtered by type",
+            SkipReason::FilteredByDate => "filtered by date",
+            SkipReason::MissingType => "missing a type",
+            SkipReason::MalformedXml => "malformed XML",
+            SkipReason::BadEncoding => "bad encoding",
+        })
+    }
+}
+
+impl From<ErrorKind> for SkipReason {
+    fn from(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::MalformedXml => SkipReason::MalformedXml,
+            ErrorKind::BadEncoding => SkipReason::BadEncoding,
+        }
+    }
+}
+
+/// A problem with the document as a whole rather than with one element, so
+/// it is not counted among the scanned elements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentIssue {
+    /// Bytes that are not UTF-8, read as U+FFFD; the element around them is
+    /// kept.
+    BadEncoding,
+    /// XML outside the parsed elements, which ends the unknown element walk.
+    MalformedXml,
+    /// The tail of a document cut short, after its last complete element.
+    Truncated,
+}
+
+impl fmt::Display for DocumentIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DocumentIssue::BadEncoding => "bad encoding",
+            DocumentIssue::MalformedXml => "malformed XML",
+            DocumentIssue::Truncated => "truncated",
+        })
+    }
+}
+
+impl From<ErrorKind> for DocumentIssue {
+    fn from(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::MalformedXml => DocumentIssue::MalformedXml,
+            ErrorKind::BadEncoding => DocumentIssue::BadEncoding,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkipSample {
+    pub offset: u64,
+    /// Filled in once parsing is done, so lines are only counted for the
+    /// handful of samples that are kept.
+    pub line: u64,
+    pub record_type: Option<String>,
+    pub excerpt: String,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SkipSummary {
+    pub count: u64,
+    pub by_type: BTreeMap<String, u64>,
+    pub samples: Vec<SkipSample>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentSample {
+    pub offset: u64,
+    pub line: u64,
+    pub excerpt: String,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DocumentSummary {
+    pub count: u64,
+    pub samples: Vec<DocumentSample>,
+}
+
+/// Accounts for every element the record parsers saw: how many became
+/// records, and how many were skipped and why, so `scanned` is always
+/// `parsed` plus the skipped counts. Problems with the document itself are
+/// kept apart in `document`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Diagnostics {
+    pub scanned: u64,
+    pub parsed: u64,
+    pub skipped: BTreeMap<SkipReason, SkipSummary>,
+    pub document: BTreeMap<DocumentIssue, DocumentSummary>,
+}
+
+impl Diagnostics {
+    /// Records a skipped element starting at `offset`; `element` is its text
+    /// from there on, used for the sample excerpt.
+    pub(crate) fn skip(
+        &mut self,
+        reason: SkipReason,
+        record_type: Option<&str>,
+        offset: usize,
+        element: &str,
+        detail: impl FnOnce() -> String,
+    ) {
+        let summary = self.skipped.entry(reason).or_default();
+        summary.count += 1;
+        if let Some(record_type) = record_type {
+            match summary.by_type.get_mut(record_type) {
+                Some(count) => *count += 1,
+                None => {
+                    summary.by_type.insert(record_type.to_string(), 1);
+                }
+            }
+        }
+        if summary.samples.len() < MAX_SAMPLES {
+            summary.samples.push(SkipSample {
+                offset: offset as u64,
+                line: 0,
+                record_type: record_type.map(str::to_string),
+                excerpt: excerpt(element),
+                detail: detail(),
+            });
+        }
+    }
+
+    /// Records a problem with the document at `offset`; `rest` is the text
+    /// the sample excerpt is taken from.
+    pub(crate) fn document_issue(
+        &mut self,
+        issue: DocumentIssue,
+        offset: usize,
+        rest: &str,
+        detail: impl FnOnce() -> String,
+    ) {
+        let summary = self.document.entry(issue).or_default();
+        summary.count += 1;
+        if summary.samples.len() < MAX_SAMPLES {
+            summary.samples.push(DocumentSample {
+                offset: offset as u64,
+                line: 0,
+                excerpt: excerpt(rest),
+                detail: detail(),
+            });
+        }
+    }
+
+    pub fn skipped_count(&self) -> u64 {
+        self.skipped.values().map(|summary| summary.count).sum()
+    }
+
+    /// Adds `other`, which must come later in the document (or from a later
+    /// export), keeping the earliest samples.
+    pub fn merge(&mut self, other: Diagnostics) {
+        self.scanned += other.scanned;
+        self.parsed += other.parsed;
+        for (reason, theirs) in other.skipped {
+            let ours = self.skipped.entry(reason).or_default();
+            ours.count += theirs.count;
+            for (record_type, count) in theirs.by_type {
+                *ours.by_type.entry(record_type).or_insert(0) += count;
+            }
+            let room = MAX_SAMPLES.saturating_sub(ours.samples.len());
+            ours.samples.extend(theirs.samples.into_iter().take(room));
+        }
+        for (issue, theirs) in other.document {
+            let ours = self.document.entry(issue).or_default();
+            ours.count += theirs.count;
+            let room = MAX_SAMPLES.saturating_sub(ours.samples.len());
+            ours.samples.extend(theirs.samples.into_iter().take(room));
+        }
+    }
+
+    pub(crate) fn resolve_lines(&mut self, xml: &str) {
+        let skipped = self
+            .skipped
+            .values_mut()
+            .flat_map(|summary| &mut summary.samples)
+            .map(|sample| (sample.offset, &mut sample.line));
+        let document = self
+            .document
+            .values_mut()
+            .flat_map(|summary| &mut summary.samples)
+            .map(|sample| (sample.offset, &mut sample.line));
+        error::resolve_lines(xml, skipped.chain(document));
+    }
+}
+
+fn excerpt(element: &str) -> String {
+    let end = element
+        .find('\n')
+        .unwrap_or(element.len())
+        .min(MAX_EXCERPT_LEN);
+    let mut end = end.min(element.len());
+    while !element.is_char_boundary(end) {
+        end -= 1;
+    }
+    element[..end].trim_end().to_string()
+}
@@ -0,0 +1,132 @@
+use crate::HealthRecord;
+use crate::merge::record_key;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use tempfile::NamedTempFile;
+
+const STATE_MAGIC: &[u8; 4] = b"AHES";
+
+/// Bump whenever the way record keys are derived changes.
+const STATE_FORMAT_VERSION: u32 = 1;
+
+/// A truncated blake3 hash of a record's identity, small enough that the
+/// keys of many years of records fit comfortably in a state file.
+pub type RecordKey = [u8; 16];
+
+pub fn record_hash(record: &HealthRecord) -> RecordKey {
+    let mut hasher = blake3::Hasher::new();
+    for part in record_key(record) {
+        match part {
+            Some(part) => {
+                hasher.update(&[1]);
+                hasher.update(&(part.len() as u64).to_le_bytes());
+                hasher.update(part.as_bytes());
+            }
+            None => {
+                hasher.update(&[0]);
+            }
+        }
+    }
+    let mut key = [0u8; 16];
+    key.copy_from_slice(&hasher.finalize().as_bytes()[..16]);
+    key
+}
+
+pub fn record_hashes(records: &[HealthRecord]) -> HashSet<RecordKey> {
+    records.iter().map(record_hash).collect()
+}
+
+/// Whether `path` is a state file written by `save_state`, as opposed to an
+/// export or a previous `output.json`.
+pub fn is_state_file(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|()| magic == *STATE_MAGIC)
+}
+
+/// Whether `path` is a previous `output.json` rather than an export.
+pub fn is_output_json(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+pub fn load_output_json(path: &Path) -> Result<Vec<HealthRecord>, Box<dyn std::error::Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(reader)?)
+}
+
+pub fn load_state(path: &Path) -> Result<HashSet<RecordKey>, Box<dyn std::error::Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut header = [0u8; 8];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != STATE_MAGIC {
+        return Err(format!("{} is not a state file", path.display()).into());
+    }
+    if header[4..] != STATE_FORMAT_VERSION.to_le_bytes() {
+        return Err(format!(
+            "{} was written by an incompatible version; diff against the previous export instead",
+            path.display()
+        )
+        .into());
+    }
+
+    let keys: Vec<RecordKey> = bincode::deserialize_from(reader)?;
+    Ok(keys.into_iter().collect())
+}
+
+pub fn save_state(
+    path: &Path,
+    keys: &HashSet<RecordKey>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(dir)?;
+
+    // Sorted so the same set of records always produces the same file.
+    let mut keys: Vec<&RecordKey> = keys.iter().collect();
+    keys.sort_unstable();
+
+    let mut tmp = NamedTempFile::new_in(dir)?;
+    {
+        let mut writer = BufWriter::new(tmp.as_file_mut());
+        writer.write_all(STATE_MAGIC)?;
+        writer.write_all(&STATE_FORMAT_VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut writer, &keys)?;
+        writer.flush()?;
+    }
+    tmp.persist(path)?;
+
+    Ok(())
+}
+
+/// Keeps the records of the new export that the old one did not contain,
+/// in their original order.
+pub fn added_records(
+    previous: &HashSet<RecordKey>,
+    records: Vec<HealthRecord>,
+) -> Vec<HealthRecord> {
+    records
+        .into_iter()
+        .filter(|record| !previous.contains(&record_hash(record)))
+        .collect()
+}
+
+pub fn counts_by_type(records: &[HealthRecord]) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for record in records {
+        let record_type = record
+            .record_type
+            .as_ref()
+            .map_or(String::new(), ToString::to_string);
+        *counts.entry(record_type).or_insert(0) += 1;
+    }
+    counts
+}
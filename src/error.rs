@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use zip::result::ZipError;
+
+/// Everything that can go wrong while locating, reading or parsing an export.
+#[derive(Debug)]
+pub enum ParseError {
+    Io(io::Error),
+    Zip(ZipError),
+    /// Malformed XML; `offset` is the byte offset into the document.
+    Xml {
+        offset: u64,
+        line: u64,
+        message: String,
+    },
+    /// Bytes that are not valid UTF-8.
+    Encoding {
+        offset: u64,
+        line: u64,
+    },
+    /// The input holds no export document of the requested rendition.
+    MissingExport {
+        path: PathBuf,
+        document: String,
+    },
+    Cache(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Io(e) => write!(f, "I/O error: {}", e),
+            ParseError::Zip(e) => write!(f, "zip error: {}", e),
+            ParseError::Xml {
+                offset,
+                line,
+                message,
+            } => write!(
+                f,
+                "malformed XML at line {} (byte {}): {}",
+                line, offset, message
+            ),
+            ParseError::Encoding { offset, line } => {
+                write!(f, "invalid UTF-8 at line {} (byte {})", line, offset)
+            }
+            ParseError::MissingExport { path, document } => {
+                write!(f, "could not find {} in {}", document, path.display())
+            }
+            ParseError::Cache(message) => write!(f, "cache error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Io(e) => Some(e),
+            ParseError::Zip(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> Self {
+        ParseError::Io(e)
+    }
+}
+
+impl From<ZipError> for ParseError {
+    fn from(e: ZipError) -> Self {
+        ParseError::Zip(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    MalformedXml,
+    BadEncoding,
+}
+
+/// A single element that could not be parsed, kept in lenient mode so the
+/// rest of the export can still be read. Unlike `ParseError` it can be
+/// cached and written to JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElementError {
+    pub element: String,
+    pub kind: ErrorKind,
+    pub offset: u64,
+    pub line: u64,
+    pub message: String,
+}
+
+impl ElementError {
+    /// Builds an error for the element starting at `offset`. Its line is
+    /// left at 0 for `resolve_lines` to fill in, off the hot path.
+    pub(crate) fn new(
+        element: &str,
+        kind: ErrorKind,
+        offset: usize,
+        message: impl Into<String>,
+    ) -> Self {
+        ElementError {
+            element: element.to_string(),
+            kind,
+            offset: offset as u64,
+            line: 0,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<ElementError> for ParseError {
+    fn from(e: ElementError) -> Self {
+        match e.kind {
+            ErrorKind::MalformedXml => ParseError::Xml {
+                offset: e.offset,
+                line: e.line,
+                message: format!("in <{}>: {}", e.element, e.message),
+            },
+            ErrorKind::BadEncoding => ParseError::Encoding {
+                offset: e.offset,
+                line: e.line,
+            },
+        }
+    }
+}
+
+/// The 1-based line of byte `offset` in `xml`.
+pub(crate) fn line_at(xml: &str, offset: usize) -> u64 {
+    let end = offset.min(xml.len());
+    memchr::memchr_iter(b'\n', &xml.as_bytes()[..end]).count() as u64 + 1
+}
+
+/// Sets each `(offset, line)` pair's line to that of its byte offset in
+/// `xml`, counting newlines in one pass however many there are.
+pub(crate) fn resolve_lines<'a>(
+    xml: &str,
+    positions: impl IntoIterator<Item = (u64, &'a mut u64)>,
+) {
+    let mut positions: Vec<_> = positions.into_iter().collect();
+    positions.sort_by_key(|(offset, _)| *offset);
+    let (mut counted, mut line) = (0, 1);
+    for (offset, target) in positions {
+        let offset = (offset as usize).min(xml.len());
+        line += memchr::memchr_iter(b'\n', &xml.as_bytes()[counted..offset]).count() as u64;
+        counted = offset;
+        *target = line;
+    }
+}
+
+/// An error inside one element, positioned relative to the start of the
+/// element; the caller turns it into an `ElementError` for the document.
+#[derive(Debug)]
+pub(crate) struct ElementFault {
+    pub kind: ErrorKind,
+    pub position: usize,
+    pub message: String,
+}
+
+impl ElementFault {
+    pub(crate) fn xml(position: u64, error: impl fmt::Display) -> Self {
+        ElementFault {
+            kind: ErrorKind::MalformedXml,
+            position: position as usize,
+            message: error.to_string(),
+        }
+    }
+
+    pub(crate) fn encoding(position: u64, error: impl fmt::Display) -> Self {
+        ElementFault {
+            kind: ErrorKind::BadEncoding,
+            position: position as usize,
+            message: error.to_string(),
+        }
+    }
+}
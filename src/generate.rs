@@ -0,0 +1,555 @@
+//! Writes realistic but entirely fake exports, for benchmarking the parser
+//! and for demos where a real (and personal) export cannot be shared.
+
+use crate::progress::{Progress, Task, Unit};
+use chrono::{Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde::Serialize;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+/// Days generated to estimate the size of one day for `target_size`.
+const SAMPLE_DAYS: u32 = 28;
+
+/// Where routes start, near which every GPX track wanders.
+const HOME: (f64, f64) = (51.5072, -0.1276);
+
+const WATCH: &str = "&lt;&lt;HKDevice: 0x3016e4a50&gt;, name:Apple Watch, manufacturer:Apple Inc., model:Watch, hardware:Watch7,3, software:11.0&gt;";
+const PHONE: &str = "&lt;&lt;HKDevice: 0x3016e5cc0&gt;, name:iPhone, manufacturer:Apple Inc., model:iPhone, hardware:iPhone16,1, software:18.0&gt;";
+
+const PROLOG: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE HealthData [
+<!-- HealthKit Export Version: 14 -->
+<!ELEMENT HealthData (ExportDate,Me,(Record|Correlation|Workout|ActivitySummary|ClinicalRecord|Audiogram|VisionPrescription)*)>
+<!ATTLIST HealthData
+  locale CDATA #REQUIRED
+>
+<!ELEMENT ExportDate EMPTY>
+<!ATTLIST ExportDate
+  value CDATA #REQUIRED
+>
+<!ELEMENT Record ((MetadataEntry|HeartRateVariabilityMetadataList)*)>
+<!ATTLIST Record
+  type          CDATA #REQUIRED
+  unit          CDATA #IMPLIED
+  value         CDATA #IMPLIED
+  sourceName    CDATA #REQUIRED
+  sourceVersion CDATA #IMPLIED
+  device        CDATA #IMPLIED
+  creationDate  CDATA #IMPLIED
+  startDate     CDATA #REQUIRED
+  endDate       CDATA #REQUIRED
+>
+<!ELEMENT Workout ((MetadataEntry|WorkoutEvent|WorkoutStatistics|WorkoutRoute)*)>
+<!ATTLIST Workout
+  workoutActivityType   CDATA #REQUIRED
+  duration              CDATA #IMPLIED
+  durationUnit          CDATA #IMPLIED
+  sourceName            CDATA #REQUIRED
+  sourceVersion         CDATA #IMPLIED
+  device                CDATA #IMPLIED
+  creationDate          CDATA #IMPLIED
+  startDate             CDATA #REQUIRED
+  endDate               CDATA #REQUIRED
+>
+]>
+"#;
+
+/// Workouts to pick from: activity, whether it has a route, and the speed
+/// in metres per second for those that do.
+const WORKOUTS: &[(&str, bool, f64)] = &[
+    ("HKWorkoutActivityTypeRunning", true, 2.9),
+    ("HKWorkoutActivityTypeWalking", true, 1.4),
+    ("HKWorkoutActivityTypeCycling", true, 6.5),
+    (
+        "HKWorkoutActivityTypeTraditionalStrengthTraining",
+        false,
+        0.0,
+    ),
+    ("HKWorkoutActivityTypeYoga", false, 0.0),
+    ("HKWorkoutActivityTypeSwimming", false, 0.0),
+];
+
+/// What to generate. The same config and seed always give the same export.
+#[derive(Debug, Clone)]
+pub struct GeneratorConfig {
+    /// The last day covered.
+    pub end: NaiveDate,
+    /// How many days, up to and including `end`, to cover.
+    pub days: u32,
+    /// Extends the span back in time until `export.xml` is roughly this many
+    /// bytes.
+    pub target_size: Option<u64>,
+    /// Write GPX routes for outdoor workouts.
+    pub routes: bool,
+    pub seed: u64,
+    /// The offset all dates are written with.
+    pub utc_offset: FixedOffset,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        GeneratorConfig {
+            end: Utc::now().date_naive(),
+            days: 30,
+            target_size: None,
+            routes: true,
+            seed: 0,
+            utc_offset: FixedOffset::east_opt(0).expect("zero offset"),
+        }
+    }
+}
+
+/// What a generated export contains.
+#[derive(Debug, Clone, Serialize)]
+pub struct GeneratedExport {
+    pub first_day: NaiveDate,
+    pub last_day: NaiveDate,
+    pub records: u64,
+    pub workouts: u64,
+    pub routes: u64,
+    /// Size of `export.xml`, before compression.
+    pub xml_bytes: u64,
+}
+
+/// A route to write once `export.xml` is finished; the GPX points are drawn
+/// from their own seed so they need not be kept in memory.
+struct RouteSpec {
+    file_name: String,
+    /// The local start time, which names the route.
+    start: NaiveDateTime,
+    /// The start in UTC, as GPX times are written.
+    start_utc: NaiveDateTime,
+    seconds: i64,
+    speed: f64,
+    seed: u64,
+}
+
+struct CountingWriter<W> {
+    inner: W,
+    bytes: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct Generator<W> {
+    out: CountingWriter<W>,
+    rng: fastrand::Rng,
+    utc_offset: FixedOffset,
+    offset: String,
+    with_routes: bool,
+    /// Apple writes workouts after all records, so they are held back.
+    workouts: String,
+    routes: Vec<RouteSpec>,
+    records: u64,
+    workout_count: u64,
+    line: String,
+}
+
+impl<W: Write> Generator<W> {
+    fn new(config: &GeneratorConfig, out: W) -> Self {
+        Generator {
+            out: CountingWriter {
+                inner: out,
+                bytes: 0,
+            },
+            rng: fastrand::Rng::with_seed(config.seed),
+            utc_offset: config.utc_offset,
+            offset: config.utc_offset.to_string().replace(':', ""),
+            with_routes: config.routes,
+            workouts: String::new(),
+            routes: Vec::new(),
+            records: 0,
+            workout_count: 0,
+            line: String::with_capacity(1024),
+        }
+    }
+
+    fn date(&self, time: NaiveDateTime) -> String {
+        format!("{} {}", time.format("%Y-%m-%d %H:%M:%S"), self.offset)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn record(
+        &mut self,
+        record_type: &str,
+        unit: Option<&str>,
+        value: &str,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+        watch: bool,
+        metadata: &[(&str, &str)],
+    ) -> io::Result<()> {
+        let (start_date, end_date) = (self.date(start), self.date(end));
+        let (source, version, device) = if watch {
+            ("Apple Watch", "11.0", WATCH)
+        } else {
+            ("iPhone", "18.0", PHONE)
+        };
+        self.line.clear();
+        let _ = write!(
+            self.line,
+            r#" <Record type="{record_type}" sourceName="{source}" sourceVersion="{version}" device="{device}""#
+        );
+        if let Some(unit) = unit {
+            let _ = write!(self.line, r#" unit="{unit}""#);
+        }
+        let _ = write!(
+            self.line,
+            r#" creationDate="{end_date}" startDate="{start_date}" endDate="{end_date}" value="{value}""#
+        );
+        if metadata.is_empty() {
+            self.line.push_str("/>\n");
+        } else {
+            self.line.push_str(">\n");
+            for (key, value) in metadata {
+                let _ = writeln!(
+                    self.line,
+                    r#"  <MetadataEntry key="{key}" value="{value}"/>"#
+                );
+            }
+            self.line.push_str(" </Record>\n");
+        }
+        self.records += 1;
+        self.out.write_all(self.line.as_bytes())
+    }
+
+    fn header(&mut self, export_date: NaiveDateTime) -> io::Result<()> {
+        let header = format!(
+            "{PROLOG}<HealthData locale=\"en_GB\">\n <ExportDate value=\"{}\"/>\n <Me HKCharacteristicTypeIdentifierDateOfBirth=\"1990-01-01\" HKCharacteristicTypeIdentifierBiologicalSex=\"HKBiologicalSexNotSet\" HKCharacteristicTypeIdentifierBloodType=\"HKBloodTypeNotSet\" HKCharacteristicTypeIdentifierFitzpatrickSkinType=\"HKFitzpatrickSkinTypeNotSet\" HKCharacteristicTypeIdentifierCardioFitnessMedicationsUse=\"None\"/>\n",
+            self.date(export_date)
+        );
+        self.out.write_all(header.as_bytes())
+    }
+
+    fn footer(&mut self) -> io::Result<()> {
+        let workouts = std::mem::take(&mut self.workouts);
+        self.out.write_all(workouts.as_bytes())?;
+        self.out.write_all(b"</HealthData>\n")
+    }
+
+    fn day(&mut self, day: NaiveDate) -> io::Result<()> {
+        let midnight = day.and_time(NaiveTime::MIN);
+        let workout = self.plan_workout(midnight);
+
+        self.sleep(midnight)?;
+        self.heart_rate(midnight, workout.as_ref())?;
+        self.steps(midnight)?;
+        if let Some((start, end, index)) = workout {
+            self.workout(start, end, index);
+        }
+        Ok(())
+    }
+
+    /// Roughly every other day, an evening or early-morning workout.
+    fn plan_workout(
+        &mut self,
+        midnight: NaiveDateTime,
+    ) -> Option<(NaiveDateTime, NaiveDateTime, usize)> {
+        if self.rng.u32(0..100) >= 45 {
+            return None;
+        }
+        let hour = if self.rng.bool() { 7 } else { 18 };
+        let start = midnight
+            + Duration::hours(hour)
+            + Duration::minutes(self.rng.i64(0..60))
+            + Duration::seconds(self.rng.i64(0..60));
+        let end = start + Duration::minutes(self.rng.i64(20..75));
+        Some((start, end, self.rng.usize(..WORKOUTS.len())))
+    }
+
+    /// A night from about 23:00, as in-bed time split into sleep stages.
+    fn sleep(&mut self, midnight: NaiveDateTime) -> io::Result<()> {
+        const SLEEP: &str = "HKCategoryTypeIdentifierSleepAnalysis";
+        let bed = midnight + Duration::hours(22) + Duration::minutes(self.rng.i64(30..90));
+        let wake = bed + Duration::minutes(self.rng.i64(390..510));
+        self.record(
+            SLEEP,
+            None,
+            "HKCategoryValueSleepAnalysisInBed",
+            bed,
+            wake,
+            false,
+            &[],
+        )?;
+
+        let mut time = bed + Duration::minutes(self.rng.i64(5..25));
+        while time < wake {
+            let (stage, minutes) = match self.rng.u32(0..10) {
+                0..=4 => (
+                    "HKCategoryValueSleepAnalysisAsleepCore",
+                    self.rng.i64(20..50),
+                ),
+                5..=6 => (
+                    "HKCategoryValueSleepAnalysisAsleepDeep",
+                    self.rng.i64(10..35),
+                ),
+                7..=8 => (
+                    "HKCategoryValueSleepAnalysisAsleepREM",
+                    self.rng.i64(10..30),
+                ),
+                _ => ("HKCategoryValueSleepAnalysisAwake", self.rng.i64(1..6)),
+            };
+            let end = (time + Duration::minutes(minutes)).min(wake);
+            self.record(SLEEP, None, stage, time, end, true, &[])?;
+            time = end;
+        }
+        Ok(())
+    }
+
+    /// Heart rate at the Watch's background cadence of a few minutes, and
+    /// every five seconds during a workout.
+    fn heart_rate(
+        &mut self,
+        midnight: NaiveDateTime,
+        workout: Option<&(NaiveDateTime, NaiveDateTime, usize)>,
+    ) -> io::Result<()> {
+        const HEART_RATE: &str = "HKQuantityTypeIdentifierHeartRate";
+        let next_day = midnight + Duration::days(1);
+        let mut time = midnight + Duration::seconds(self.rng.i64(0..300));
+        let mut bpm = 60.0;
+
+        while time < next_day {
+            let in_workout = workout.is_some_and(|(start, end, _)| time >= *start && time < *end);
+            let hour = (time - midnight).num_hours();
+            let (target, step, context) = if in_workout {
+                (150.0, Duration::seconds(5), "2")
+            } else if !(7..23).contains(&hour) {
+                (55.0, Duration::seconds(self.rng.i64(240..420)), "1")
+            } else {
+                (72.0, Duration::seconds(self.rng.i64(240..420)), "1")
+            };
+            bpm += (target - bpm) * 0.3 + (self.rng.f64() - 0.5) * 8.0;
+            let value = format!("{}", bpm.round().clamp(40.0, 200.0));
+            self.record(
+                HEART_RATE,
+                Some("count/min"),
+                &value,
+                time,
+                time,
+                true,
+                &[("HKMetadataKeyHeartRateMotionContext", context)],
+            )?;
+
+            time += step;
+            if let Some((start, _, _)) = workout
+                && !in_workout
+                && time > *start
+                && time - step < *start
+            {
+                time = *start;
+            }
+        }
+        Ok(())
+    }
+
+    /// Step counts as the phone records them: short intervals through the
+    /// waking day, adding up to a daily total.
+    fn steps(&mut self, midnight: NaiveDateTime) -> io::Result<()> {
+        const STEP_COUNT: &str = "HKQuantityTypeIdentifierStepCount";
+        let mut time = midnight + Duration::hours(7) + Duration::minutes(self.rng.i64(0..60));
+        let bedtime = midnight + Duration::hours(22);
+        while time < bedtime {
+            let end = time + Duration::minutes(self.rng.i64(2..11));
+            let steps = self.rng.u32(10..900).to_string();
+            self.record(STEP_COUNT, Some("count"), &steps, time, end, false, &[])?;
+            time = end + Duration::minutes(self.rng.i64(5..40));
+        }
+        Ok(())
+    }
+
+    fn workout(&mut self, start: NaiveDateTime, end: NaiveDateTime, index: usize) {
+        let (activity, outdoor, speed) = WORKOUTS[index];
+        let seconds = (end - start).num_seconds();
+        let minutes = seconds as f64 / 60.0;
+        let (start_date, end_date) = (self.date(start), self.date(end));
+        let energy = minutes * self.rng.f64().mul_add(4.0, 6.0);
+
+        let out = &mut self.workouts;
+        let _ = writeln!(
+            out,
+            r#" <Workout workoutActivityType="{activity}" duration="{minutes:.4}" durationUnit="min" sourceName="Apple Watch" sourceVersion="11.0" device="{WATCH}" creationDate="{end_date}" startDate="{start_date}" endDate="{end_date}">
+  <MetadataEntry key="HKIndoorWorkout" value="{}"/>
+  <MetadataEntry key="HKTimeZone" value="Europe/London"/>
+  <WorkoutEvent type="HKWorkoutEventTypeSegment" date="{start_date}" duration="{minutes:.4}" durationUnit="min"/>
+  <WorkoutStatistics type="HKQuantityTypeIdentifierActiveEnergyBurned" startDate="{start_date}" endDate="{end_date}" sum="{energy:.3}" unit="kcal"/>
+  <WorkoutStatistics type="HKQuantityTypeIdentifierHeartRate" startDate="{start_date}" endDate="{end_date}" average="148" minimum="112" maximum="171" unit="count/min"/>"#,
+            u8::from(!outdoor)
+        );
+        if outdoor {
+            let distance = speed * seconds as f64 / 1000.0;
+            let _ = writeln!(
+                out,
+                r#"  <WorkoutStatistics type="HKQuantityTypeIdentifierDistanceWalkingRunning" startDate="{start_date}" endDate="{end_date}" sum="{distance:.3}" unit="km"/>"#
+            );
+        }
+        if outdoor && self.with_routes {
+            let file_name = format!("route_{}.gpx", start.format("%Y-%m-%d_%-I.%M%P"));
+            let _ = writeln!(
+                out,
+                r#"  <WorkoutRoute sourceName="Apple Watch" sourceVersion="11.0" creationDate="{end_date}" startDate="{start_date}" endDate="{end_date}">
+   <MetadataEntry key="HKMetadataKeySyncVersion" value="2"/>
+   <FileReference path="/workout-routes/{file_name}"/>
+  </WorkoutRoute>"#
+            );
+            self.routes.push(RouteSpec {
+                file_name,
+                start,
+                start_utc: start - Duration::seconds(self.utc_offset.local_minus_utc().into()),
+                seconds,
+                speed,
+                seed: self.rng.u64(..),
+            });
+        }
+        out.push_str(" </Workout>\n");
+        self.workout_count += 1;
+    }
+}
+
+/// How many days to generate: `config.days`, or more if that falls short of
+/// `config.target_size` at the size of a typical sampled day.
+fn span(config: &GeneratorConfig) -> io::Result<u32> {
+    let days = config.days.max(1);
+    let Some(target) = config.target_size else {
+        return Ok(days);
+    };
+
+    let mut sample = Generator::new(config, io::sink());
+    for offset in 0..SAMPLE_DAYS {
+        sample.day(config.end - Duration::days(offset.into()))?;
+    }
+    let bytes = sample.out.bytes + sample.workouts.len() as u64;
+    let per_day = (bytes / u64::from(SAMPLE_DAYS)).max(1);
+    let needed = u32::try_from(target.div_ceil(per_day)).unwrap_or(u32::MAX);
+    Ok(days.max(needed))
+}
+
+fn write_xml<W: Write>(
+    config: &GeneratorConfig,
+    out: W,
+    progress: Option<&Task>,
+) -> io::Result<(GeneratedExport, Vec<RouteSpec>)> {
+    let days = span(config)?;
+    let first_day = config.end - Duration::days(i64::from(days) - 1);
+
+    let mut generator = Generator::new(config, out);
+    generator.header(
+        (config.end + Duration::days(1))
+            .and_time(NaiveTime::from_hms_opt(9, 0, 0).expect("valid time")),
+    )?;
+    for day in first_day.iter_days().take(days as usize) {
+        let before = generator.out.bytes;
+        generator.day(day)?;
+        if let Some(task) = progress {
+            task.inc(generator.out.bytes - before);
+        }
+    }
+    generator.footer()?;
+    generator.out.flush()?;
+
+    let summary = GeneratedExport {
+        first_day,
+        last_day: config.end,
+        records: generator.records,
+        workouts: generator.workout_count,
+        routes: generator.routes.len() as u64,
+        xml_bytes: generator.out.bytes,
+    };
+    Ok((summary, generator.routes))
+}
+
+/// Writes just the `export.xml` document to `out`, without routes.
+pub fn generate_xml<W: Write>(config: &GeneratorConfig, out: W) -> io::Result<GeneratedExport> {
+    let config = GeneratorConfig {
+        routes: false,
+        ..config.clone()
+    };
+    write_xml(&config, out, None).map(|(summary, _)| summary)
+}
+
+/// Writes an `export.zip` laid out like the one the Health app shares:
+/// `apple_health_export/export.xml` plus a GPX file per outdoor workout in
+/// `apple_health_export/workout-routes/`.
+pub fn generate_export(
+    config: &GeneratorConfig,
+    path: &Path,
+    progress: &Progress,
+) -> Result<GeneratedExport, Box<dyn Error>> {
+    let mut zip = ZipWriter::new(BufWriter::new(File::create(path)?));
+    let options =
+        SimpleFileOptions::default().large_file(config.target_size.unwrap_or(0) > u32::MAX.into());
+
+    zip.start_file("apple_health_export/export.xml", options)?;
+    let task = progress.task("generating", config.target_size, Unit::Bytes);
+    let (summary, routes) = write_xml(config, &mut zip, Some(&task))?;
+    task.finish();
+
+    for route in &routes {
+        zip.start_file(
+            format!("apple_health_export/workout-routes/{}", route.file_name),
+            SimpleFileOptions::default(),
+        )?;
+        zip.write_all(gpx(route).as_bytes())?;
+    }
+    zip.finish()?.flush()?;
+    Ok(summary)
+}
+
+/// A GPX track with a point every second, wandering away from `HOME` at the
+/// workout's speed.
+fn gpx(route: &RouteSpec) -> String {
+    let mut rng = fastrand::Rng::with_seed(route.seed);
+    let mut gpx = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="Apple Health Export" xmlns="http://www.topografix.com/GPX/1/1" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://www.topografix.com/GPX/1/1 http://www.topografix.com/GPX/1/1/gpx.xsd">
+  <metadata>
+    <time>{}</time>
+  </metadata>
+  <trk>
+    <name>Route {}</name>
+    <trkseg>
+"#,
+        route.start_utc.format("%Y-%m-%dT%H:%M:%SZ"),
+        route.start.format("%Y-%m-%d %-I:%M%P")
+    );
+
+    let (mut lat, mut lon) = (
+        HOME.0 + (rng.f64() - 0.5) * 0.01,
+        HOME.1 + (rng.f64() - 0.5) * 0.01,
+    );
+    let mut course: f64 = rng.f64() * 360.0;
+    let mut elevation = 10.0 + rng.f64() * 20.0;
+    for second in 0..=route.seconds {
+        let time = route.start_utc + Duration::seconds(second);
+        let speed = route.speed * (0.85 + rng.f64() * 0.3);
+        course = (course + (rng.f64() - 0.5) * 12.0).rem_euclid(360.0);
+        elevation += (rng.f64() - 0.5) * 0.4;
+        let _ = writeln!(
+            gpx,
+            r#"      <trkpt lon="{lon:.6}" lat="{lat:.6}"><ele>{elevation:.6}</ele><time>{}</time><extensions><speed>{speed:.6}</speed><course>{course:.6}</course><hAcc>{:.6}</hAcc><vAcc>{:.6}</vAcc></extensions></trkpt>"#,
+            time.format("%Y-%m-%dT%H:%M:%SZ"),
+            1.0 + rng.f64() * 2.0,
+            1.0 + rng.f64(),
+        );
+        // Metres to degrees, close enough at these distances.
+        let radians = course.to_radians();
+        lat += speed * radians.cos() / 111_320.0;
+        lon += speed * radians.sin() / (111_320.0 * lat.to_radians().cos());
+    }
+    gpx.push_str("    </trkseg>\n  </trk>\n</gpx>\n");
+    gpx
+}
@@ -0,0 +1,533 @@
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeKind {
+    Quantity,
+    Category,
+    Correlation,
+}
+
+/// Broad areas of health data, matching the `@name` groups of the type filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TypeGroup {
+    Activity,
+    Body,
+    Heart,
+    Hearing,
+    Mindfulness,
+    Nutrition,
+    Reproductive,
+    Respiratory,
+    Sleep,
+    Symptoms,
+    Other,
+}
+
+/// How samples of a quantity type combine over an interval: cumulative types
+/// (steps, energy, nutrients) are summed, discrete ones (heart rate, body
+/// mass) are averaged or reduced to a minimum and maximum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation {
+    Cumulative,
+    Discrete,
+}
+
+#[derive(Debug)]
+pub struct HealthType {
+    pub identifier: &'static str,
+    pub name: &'static str,
+    pub kind: TypeKind,
+    pub group: TypeGroup,
+    /// The unit HealthKit stores the type in; category and correlation types
+    /// have none.
+    pub unit: Option<&'static str>,
+    /// `None` for category and correlation types, which are not aggregated.
+    pub aggregation: Option<Aggregation>,
+}
+
+macro_rules! identifier {
+    (Quantity, $variant:ident) => {
+        concat!("HKQuantityTypeIdentifier", stringify!($variant))
+    };
+    (Category, $variant:ident) => {
+        concat!("HKCategoryTypeIdentifier", stringify!($variant))
+    };
+    (Correlation, $variant:ident) => {
+        concat!("HKCorrelationTypeIdentifier", stringify!($variant))
+    };
+}
+
+/// Declares `RecordType` and `CATALOGUE` from one list, so every catalogued
+/// identifier has a variant and every variant a catalogue entry.
+macro_rules! health_types {
+    ($(
+        $kind:ident $variant:ident, $name:literal, $group:ident
+        $(, $unit:literal, $aggregation:ident)?;
+    )*) => {
+        /// A HealthKit sample type. Identifiers missing from the catalogue,
+        /// such as ones added in newer iOS releases, are kept as `Other`.
+        /// Types order by identifier.
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum RecordType {
+            $($variant,)*
+            Other(String),
+        }
+
+        impl RecordType {
+            /// The full `HK...TypeIdentifier` as it appears in export.xml.
+            pub fn identifier(&self) -> &str {
+                match self {
+                    $(RecordType::$variant => identifier!($kind, $variant),)*
+                    RecordType::Other(identifier) => identifier,
+                }
+            }
+
+            /// The identifier without its `HK...TypeIdentifier` prefix.
+            pub fn short_name(&self) -> &str {
+                match self {
+                    $(RecordType::$variant => stringify!($variant),)*
+                    RecordType::Other(identifier) => strip_prefix(identifier),
+                }
+            }
+
+            const KNOWN: &[RecordType] = &[$(RecordType::$variant,)*];
+        }
+
+        pub const CATALOGUE: &[HealthType] = &[$(
+            HealthType {
+                identifier: identifier!($kind, $variant),
+                name: $name,
+                kind: TypeKind::$kind,
+                group: TypeGroup::$group,
+                unit: health_types!(@option $($unit)?),
+                aggregation: health_types!(@option $(Aggregation::$aggregation)?),
+            },
+        )*];
+    };
+    (@option $value:expr) => {
+        Some($value)
+    };
+    (@option) => {
+        None
+    };
+}
+
+#[rustfmt::skip]
+health_types! {
+    // Activity
+    Quantity StepCount, "Steps", Activity, "count", Cumulative;
+    Quantity DistanceWalkingRunning, "Walking + Running Distance", Activity, "m", Cumulative;
+    Quantity DistanceCycling, "Cycling Distance", Activity, "m", Cumulative;
+    Quantity DistanceWheelchair, "Wheelchair Distance", Activity, "m", Cumulative;
+    Quantity DistanceSwimming, "Swimming Distance", Activity, "m", Cumulative;
+    Quantity DistanceDownhillSnowSports, "Downhill Snow Sports Distance", Activity, "m", Cumulative;
+    Quantity DistanceCrossCountrySkiing, "Cross-Country Skiing Distance", Activity, "m", Cumulative;
+    Quantity DistancePaddleSports, "Paddle Sports Distance", Activity, "m", Cumulative;
+    Quantity DistanceRowing, "Rowing Distance", Activity, "m", Cumulative;
+    Quantity DistanceSkatingSports, "Skating Sports Distance", Activity, "m", Cumulative;
+    Quantity FlightsClimbed, "Flights Climbed", Activity, "count", Cumulative;
+    Quantity PushCount, "Pushes", Activity, "count", Cumulative;
+    Quantity SwimmingStrokeCount, "Swimming Strokes", Activity, "count", Cumulative;
+    Quantity NikeFuel, "NikeFuel", Activity, "count", Cumulative;
+    Quantity ActiveEnergyBurned, "Active Energy", Activity, "kcal", Cumulative;
+    Quantity BasalEnergyBurned, "Resting Energy", Activity, "kcal", Cumulative;
+    Quantity AppleExerciseTime, "Exercise Minutes", Activity, "min", Cumulative;
+    Quantity AppleMoveTime, "Move Minutes", Activity, "min", Cumulative;
+    Quantity AppleStandTime, "Stand Minutes", Activity, "min", Cumulative;
+    Quantity PhysicalEffort, "Physical Effort", Activity, "kcal/hr·kg", Discrete;
+    Quantity VO2Max, "Cardio Fitness", Activity, "mL/min·kg", Discrete;
+    Quantity WalkingSpeed, "Walking Speed", Activity, "m/s", Discrete;
+    Quantity WalkingStepLength, "Walking Step Length", Activity, "m", Discrete;
+    Quantity WalkingAsymmetryPercentage, "Walking Asymmetry", Activity, "%", Discrete;
+    Quantity WalkingDoubleSupportPercentage, "Double Support Time", Activity, "%", Discrete;
+    Quantity AppleWalkingSteadiness, "Walking Steadiness", Activity, "%", Discrete;
+    Quantity StairAscentSpeed, "Stair Speed: Up", Activity, "m/s", Discrete;
+    Quantity StairDescentSpeed, "Stair Speed: Down", Activity, "m/s", Discrete;
+    Quantity SixMinuteWalkTestDistance, "Six-Minute Walk", Activity, "m", Discrete;
+    Quantity RunningSpeed, "Running Speed", Activity, "m/s", Discrete;
+    Quantity RunningPower, "Running Power", Activity, "W", Discrete;
+    Quantity RunningStrideLength, "Running Stride Length", Activity, "m", Discrete;
+    Quantity RunningVerticalOscillation, "Vertical Oscillation", Activity, "cm", Discrete;
+    Quantity RunningGroundContactTime, "Ground Contact Time", Activity, "ms", Discrete;
+    Quantity CyclingSpeed, "Cycling Speed", Activity, "m/s", Discrete;
+    Quantity CyclingPower, "Cycling Power", Activity, "W", Discrete;
+    Quantity CyclingCadence, "Cycling Cadence", Activity, "count/min", Discrete;
+    Quantity CyclingFunctionalThresholdPower, "Cycling Functional Threshold Power", Activity, "W", Discrete;
+    Quantity CrossCountrySkiingSpeed, "Cross-Country Skiing Speed", Activity, "m/s", Discrete;
+    Quantity PaddleSportsSpeed, "Paddle Sports Speed", Activity, "m/s", Discrete;
+    Quantity RowingSpeed, "Rowing Speed", Activity, "m/s", Discrete;
+    Quantity WorkoutEffortScore, "Workout Effort", Activity, "appleEffortScore", Discrete;
+    Quantity EstimatedWorkoutEffortScore, "Estimated Workout Effort", Activity, "appleEffortScore", Discrete;
+    Category AppleStandHour, "Stand Hours", Activity;
+    Category AppleWalkingSteadinessEvent, "Walking Steadiness Notification", Activity;
+    // Body
+    Quantity BodyMass, "Weight", Body, "kg", Discrete;
+    Quantity BodyMassIndex, "Body Mass Index", Body, "count", Discrete;
+    Quantity BodyFatPercentage, "Body Fat Percentage", Body, "%", Discrete;
+    Quantity LeanBodyMass, "Lean Body Mass", Body, "kg", Discrete;
+    Quantity Height, "Height", Body, "m", Discrete;
+    Quantity WaistCircumference, "Waist Circumference", Body, "m", Discrete;
+    Quantity BodyTemperature, "Body Temperature", Body, "degC", Discrete;
+    Quantity BloodPressureSystolic, "Blood Pressure (Systolic)", Body, "mmHg", Discrete;
+    Quantity BloodPressureDiastolic, "Blood Pressure (Diastolic)", Body, "mmHg", Discrete;
+    Correlation BloodPressure, "Blood Pressure", Body;
+    // Heart
+    Quantity HeartRate, "Heart Rate", Heart, "count/min", Discrete;
+    Quantity RestingHeartRate, "Resting Heart Rate", Heart, "count/min", Discrete;
+    Quantity WalkingHeartRateAverage, "Walking Heart Rate Average", Heart, "count/min", Discrete;
+    Quantity HeartRateVariabilitySDNN, "Heart Rate Variability", Heart, "ms", Discrete;
+    Quantity HeartRateRecoveryOneMinute, "Cardio Recovery", Heart, "count/min", Discrete;
+    Quantity AtrialFibrillationBurden, "AFib History", Heart, "%", Discrete;
+    Category HighHeartRateEvent, "High Heart Rate Notification", Heart;
+    Category LowHeartRateEvent, "Low Heart Rate Notification", Heart;
+    Category IrregularHeartRhythmEvent, "Irregular Rhythm Notification", Heart;
+    Category LowCardioFitnessEvent, "Low Cardio Fitness Notification", Heart;
+    // Hearing
+    Quantity EnvironmentalAudioExposure, "Environmental Sound Levels", Hearing, "dBASPL", Discrete;
+    Quantity HeadphoneAudioExposure, "Headphone Audio Levels", Hearing, "dBASPL", Discrete;
+    Quantity EnvironmentalSoundReduction, "Environmental Sound Reduction", Hearing, "dBASPL", Discrete;
+    Category AudioExposureEvent, "Loud Audio Event", Hearing;
+    Category EnvironmentalAudioExposureEvent, "Environmental Sound Notification", Hearing;
+    Category HeadphoneAudioExposureEvent, "Headphone Notification", Hearing;
+    // Mindfulness
+    Category MindfulSession, "Mindful Minutes", Mindfulness;
+    // Nutrition
+    Quantity DietaryEnergyConsumed, "Dietary Energy", Nutrition, "kcal", Cumulative;
+    Quantity DietaryCarbohydrates, "Carbohydrates", Nutrition, "g", Cumulative;
+    Quantity DietaryFiber, "Fiber", Nutrition, "g", Cumulative;
+    Quantity DietarySugar, "Dietary Sugar", Nutrition, "g", Cumulative;
+    Quantity DietaryFatTotal, "Total Fat", Nutrition, "g", Cumulative;
+    Quantity DietaryFatMonounsaturated, "Monounsaturated Fat", Nutrition, "g", Cumulative;
+    Quantity DietaryFatPolyunsaturated, "Polyunsaturated Fat", Nutrition, "g", Cumulative;
+    Quantity DietaryFatSaturated, "Saturated Fat", Nutrition, "g", Cumulative;
+    Quantity DietaryCholesterol, "Dietary Cholesterol", Nutrition, "mg", Cumulative;
+    Quantity DietaryProtein, "Protein", Nutrition, "g", Cumulative;
+    Quantity DietaryVitaminA, "Vitamin A", Nutrition, "mcg", Cumulative;
+    Quantity DietaryThiamin, "Thiamin", Nutrition, "mg", Cumulative;
+    Quantity DietaryRiboflavin, "Riboflavin", Nutrition, "mg", Cumulative;
+    Quantity DietaryNiacin, "Niacin", Nutrition, "mg", Cumulative;
+    Quantity DietaryPantothenicAcid, "Pantothenic Acid", Nutrition, "mg", Cumulative;
+    Quantity DietaryVitaminB6, "Vitamin B6", Nutrition, "mg", Cumulative;
+    Quantity DietaryBiotin, "Biotin", Nutrition, "mcg", Cumulative;
+    Quantity DietaryVitaminB12, "Vitamin B12", Nutrition, "mcg", Cumulative;
+    Quantity DietaryVitaminC, "Vitamin C", Nutrition, "mg", Cumulative;
+    Quantity DietaryVitaminD, "Vitamin D", Nutrition, "mcg", Cumulative;
+    Quantity DietaryVitaminE, "Vitamin E", Nutrition, "mg", Cumulative;
+    Quantity DietaryVitaminK, "Vitamin K", Nutrition, "mcg", Cumulative;
+    Quantity DietaryFolate, "Folate", Nutrition, "mcg", Cumulative;
+    Quantity DietaryCalcium, "Calcium", Nutrition, "mg", Cumulative;
+    Quantity DietaryChloride, "Chloride", Nutrition, "mg", Cumulative;
+    Quantity DietaryIron, "Iron", Nutrition, "mg", Cumulative;
+    Quantity DietaryMagnesium, "Magnesium", Nutrition, "mg", Cumulative;
+    Quantity DietaryPhosphorus, "Phosphorus", Nutrition, "mg", Cumulative;
+    Quantity DietaryPotassium, "Potassium", Nutrition, "mg", Cumulative;
+    Quantity DietarySodium, "Sodium", Nutrition, "mg", Cumulative;
+    Quantity DietaryZinc, "Zinc", Nutrition, "mg", Cumulative;
+    Quantity DietaryChromium, "Chromium", Nutrition, "mcg", Cumulative;
+    Quantity DietaryCopper, "Copper", Nutrition, "mg", Cumulative;
+    Quantity DietaryIodine, "Iodine", Nutrition, "mcg", Cumulative;
+    Quantity DietaryManganese, "Manganese", Nutrition, "mg", Cumulative;
+    Quantity DietaryMolybdenum, "Molybdenum", Nutrition, "mcg", Cumulative;
+    Quantity DietarySelenium, "Selenium", Nutrition, "mcg", Cumulative;
+    Quantity DietaryWater, "Water", Nutrition, "mL", Cumulative;
+    Quantity DietaryCaffeine, "Caffeine", Nutrition, "mg", Cumulative;
+    Quantity NumberOfAlcoholicBeverages, "Alcoholic Beverages", Nutrition, "count", Cumulative;
+    Correlation Food, "Food", Nutrition;
+    // Reproductive health
+    Quantity BasalBodyTemperature, "Basal Body Temperature", Reproductive, "degC", Discrete;
+    Category MenstrualFlow, "Menstruation", Reproductive;
+    Category IntermenstrualBleeding, "Spotting", Reproductive;
+    Category InfrequentMenstrualCycles, "Infrequent Periods", Reproductive;
+    Category IrregularMenstrualCycles, "Irregular Cycles", Reproductive;
+    Category PersistentIntermenstrualBleeding, "Persistent Spotting", Reproductive;
+    Category ProlongedMenstrualPeriods, "Prolonged Periods", Reproductive;
+    Category CervicalMucusQuality, "Cervical Mucus Quality", Reproductive;
+    Category OvulationTestResult, "Ovulation Test Result", Reproductive;
+    Category PregnancyTestResult, "Pregnancy Test Result", Reproductive;
+    Category ProgesteroneTestResult, "Progesterone Test Result", Reproductive;
+    Category SexualActivity, "Sexual Activity", Reproductive;
+    Category Contraceptive, "Contraceptives", Reproductive;
+    Category Pregnancy, "Pregnancy", Reproductive;
+    Category Lactation, "Lactation", Reproductive;
+    Category BleedingAfterPregnancy, "Bleeding After Pregnancy", Reproductive;
+    Category BleedingDuringPregnancy, "Bleeding During Pregnancy", Reproductive;
+    // Respiratory
+    Quantity OxygenSaturation, "Blood Oxygen", Respiratory, "%", Discrete;
+    Quantity RespiratoryRate, "Respiratory Rate", Respiratory, "count/min", Discrete;
+    Quantity ForcedVitalCapacity, "Forced Vital Capacity", Respiratory, "L", Discrete;
+    Quantity ForcedExpiratoryVolume1, "Forced Expiratory Volume, 1 sec", Respiratory, "L", Discrete;
+    Quantity PeakExpiratoryFlowRate, "Peak Expiratory Flow Rate", Respiratory, "L/min", Discrete;
+    Quantity InhalerUsage, "Inhaler Usage", Respiratory, "count", Cumulative;
+    // Sleep
+    Quantity AppleSleepingWristTemperature, "Wrist Temperature", Sleep, "degC", Discrete;
+    Quantity AppleSleepingBreathingDisturbances, "Breathing Disturbances", Sleep, "count", Discrete;
+    Category SleepAnalysis, "Sleep", Sleep;
+    Category SleepApneaEvent, "Sleep Apnea Notification", Sleep;
+    // Symptoms
+    Category AbdominalCramps, "Abdominal Cramps", Symptoms;
+    Category Acne, "Acne", Symptoms;
+    Category AppetiteChanges, "Appetite Changes", Symptoms;
+    Category BladderIncontinence, "Bladder Incontinence", Symptoms;
+    Category Bloating, "Bloating", Symptoms;
+    Category BreastPain, "Breast Pain", Symptoms;
+    Category ChestTightnessOrPain, "Chest Tightness or Pain", Symptoms;
+    Category Chills, "Chills", Symptoms;
+    Category Constipation, "Constipation", Symptoms;
+    Category Coughing, "Coughing", Symptoms;
+    Category Diarrhea, "Diarrhea", Symptoms;
+    Category Dizziness, "Dizziness", Symptoms;
+    Category DrySkin, "Dry Skin", Symptoms;
+    Category Fainting, "Fainting", Symptoms;
+    Category Fatigue, "Fatigue", Symptoms;
+    Category Fever, "Fever", Symptoms;
+    Category GeneralizedBodyAche, "Body and Muscle Ache", Symptoms;
+    Category HairLoss, "Hair Loss", Symptoms;
+    Category Headache, "Headache", Symptoms;
+    Category Heartburn, "Heartburn", Symptoms;
+    Category HotFlashes, "Hot Flashes", Symptoms;
+    Category LossOfSmell, "Loss of Smell", Symptoms;
+    Category LossOfTaste, "Loss of Taste", Symptoms;
+    Category LowerBackPain, "Lower Back Pain", Symptoms;
+    Category MemoryLapse, "Memory Lapse", Symptoms;
+    Category MoodChanges, "Mood Changes", Symptoms;
+    Category Nausea, "Nausea", Symptoms;
+    Category NightSweats, "Night Sweats", Symptoms;
+    Category PelvicPain, "Pelvic Pain", Symptoms;
+    Category RapidPoundingOrFlutteringHeartbeat, "Rapid, Pounding, or Fluttering Heartbeat", Symptoms;
+    Category RunnyNose, "Runny Nose", Symptoms;
+    Category ShortnessOfBreath, "Shortness of Breath", Symptoms;
+    Category SinusCongestion, "Sinus Congestion", Symptoms;
+    Category SkippedHeartbeat, "Skipped Heartbeat", Symptoms;
+    Category SleepChanges, "Sleep Changes", Symptoms;
+    Category SoreThroat, "Sore Throat", Symptoms;
+    Category VaginalDryness, "Vaginal Dryness", Symptoms;
+    Category Vomiting, "Vomiting", Symptoms;
+    Category Wheezing, "Wheezing", Symptoms;
+    // Other
+    Quantity BloodGlucose, "Blood Glucose", Other, "mg/dL", Discrete;
+    Quantity BloodAlcoholContent, "Blood Alcohol Content", Other, "%", Discrete;
+    Quantity PeripheralPerfusionIndex, "Peripheral Perfusion Index", Other, "%", Discrete;
+    Quantity ElectrodermalActivity, "Electrodermal Activity", Other, "mcS", Discrete;
+    Quantity InsulinDelivery, "Insulin Delivery", Other, "IU", Cumulative;
+    Quantity NumberOfTimesFallen, "Number of Times Fallen", Other, "count", Cumulative;
+    Quantity UVExposure, "UV Index", Other, "count", Discrete;
+    Quantity TimeInDaylight, "Time in Daylight", Other, "min", Cumulative;
+    Quantity UnderwaterDepth, "Underwater Depth", Other, "m", Discrete;
+    Quantity WaterTemperature, "Water Temperature", Other, "degC", Discrete;
+    Category ToothbrushingEvent, "Toothbrushing", Other;
+    Category HandwashingEvent, "Handwashing", Other;
+}
+
+/// Looks up a type by its full `HK...TypeIdentifier`.
+pub fn lookup(identifier: &str) -> Option<&'static HealthType> {
+    static INDEX: OnceLock<HashMap<&'static str, &'static HealthType>> = OnceLock::new();
+    INDEX
+        .get_or_init(|| CATALOGUE.iter().map(|t| (t.identifier, t)).collect())
+        .get(identifier)
+        .copied()
+}
+
+pub fn in_group(group: TypeGroup) -> impl Iterator<Item = &'static HealthType> {
+    CATALOGUE.iter().filter(move |t| t.group == group)
+}
+
+/// A file-system friendly name for per-type output, e.g. `heart_rate` for
+/// `HKQuantityTypeIdentifierHeartRate`. Types missing from the catalogue
+/// fall back to their identifier without the `HK...TypeIdentifier` prefix.
+pub fn file_stem(record_type: &RecordType) -> String {
+    match record_type.health_type() {
+        Some(health_type) => snake_case(health_type.name),
+        None => snake_case(record_type.short_name()),
+    }
+}
+
+fn strip_prefix(identifier: &str) -> &str {
+    [
+        "HKQuantityTypeIdentifier",
+        "HKCategoryTypeIdentifier",
+        "HKCorrelationTypeIdentifier",
+        "HKDataType",
+    ]
+    .iter()
+    .find_map(|prefix| identifier.strip_prefix(prefix))
+    .unwrap_or(identifier)
+}
+
+/// Lower-cases words (split on punctuation, spaces and camel-case humps)
+/// and joins them with underscores.
+fn snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 8);
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            if c.is_ascii_uppercase() && previous_lower && !out.ends_with('_') {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+            previous_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        } else {
+            if !out.is_empty() && !out.ends_with('_') {
+                out.push('_');
+            }
+            previous_lower = false;
+        }
+    }
+    while out.ends_with('_') {
+        out.pop();
+    }
+    if out.is_empty() {
+        out.push_str("unknown");
+    }
+    out
+}
+
+impl TypeGroup {
+    pub const ALL: &[TypeGroup] = &[
+        TypeGroup::Activity,
+        TypeGroup::Body,
+        TypeGroup::Heart,
+        TypeGroup::Hearing,
+        TypeGroup::Mindfulness,
+        TypeGroup::Nutrition,
+        TypeGroup::Reproductive,
+        TypeGroup::Respiratory,
+        TypeGroup::Sleep,
+        TypeGroup::Symptoms,
+        TypeGroup::Other,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        TypeGroup::ALL
+            .iter()
+            .copied()
+            .find(|group| group.to_string().eq_ignore_ascii_case(name))
+    }
+}
+
+impl fmt::Display for TypeGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TypeGroup::Activity => "activity",
+            TypeGroup::Body => "body",
+            TypeGroup::Heart => "heart",
+            TypeGroup::Hearing => "hearing",
+            TypeGroup::Mindfulness => "mindfulness",
+            TypeGroup::Nutrition => "nutrition",
+            TypeGroup::Reproductive => "reproductive",
+            TypeGroup::Respiratory => "respiratory",
+            TypeGroup::Sleep => "sleep",
+            TypeGroup::Symptoms => "symptoms",
+            TypeGroup::Other => "other",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Serialize for TypeGroup {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl fmt::Display for TypeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TypeKind::Quantity => "quantity",
+            TypeKind::Category => "category",
+            TypeKind::Correlation => "correlation",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Aggregation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Aggregation::Cumulative => "cumulative",
+            Aggregation::Discrete => "discrete",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl RecordType {
+    /// Maps an identifier, or a bare name such as `HeartRate`, to its variant.
+    pub fn from_identifier(identifier: &str) -> Self {
+        static INDEX: OnceLock<HashMap<&'static str, &'static RecordType>> = OnceLock::new();
+        let index = INDEX.get_or_init(|| {
+            RecordType::KNOWN
+                .iter()
+                .flat_map(|t| [(t.identifier(), t), (t.short_name(), t)])
+                .collect()
+        });
+        match index.get(identifier) {
+            Some(record_type) => (*record_type).clone(),
+            None => RecordType::Other(identifier.to_string()),
+        }
+    }
+
+    pub fn health_type(&self) -> Option<&'static HealthType> {
+        lookup(self.identifier())
+    }
+
+    /// The catalogue group, or `Other` for uncatalogued types.
+    pub fn group(&self) -> TypeGroup {
+        self.health_type()
+            .map_or(TypeGroup::Other, |health_type| health_type.group)
+    }
+
+    /// How values of the type combine; `None` for category, correlation and
+    /// uncatalogued types.
+    pub fn aggregation(&self) -> Option<Aggregation> {
+        self.health_type()
+            .and_then(|health_type| health_type.aggregation)
+    }
+}
+
+// `from_identifier` never puts a catalogued identifier in `Other`, so
+// ordering by identifier agrees with the derived equality.
+impl Ord for RecordType {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.identifier().cmp(other.identifier())
+    }
+}
+
+impl PartialOrd for RecordType {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl FromStr for RecordType {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(RecordType::from_identifier(s))
+    }
+}
+
+impl fmt::Display for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.identifier())
+    }
+}
+
+impl Serialize for RecordType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.identifier())
+    }
+}
+
+impl<'de> Deserialize<'de> for RecordType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let identifier = String::deserialize(deserializer)?;
+        Ok(RecordType::from_identifier(&identifier))
+    }
+}
@@ -0,0 +1,178 @@
+use crate::error::ParseError;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+/// How far below an extracted export directory to look for the XML; Apple
+/// nests it one level deep (`apple_health_export/export.xml`).
+const MAX_DIR_DEPTH: usize = 3;
+
+/// Bytes sniffed from a candidate file to confirm it is a Health export.
+const SNIFF_LEN: u64 = 4096;
+
+/// The two renditions of the data found in an export.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Document {
+    /// `export.xml`, Apple's own `HealthData` format.
+    Export,
+    /// `export_cda.xml`, the HL7 Clinical Document Architecture rendition.
+    Cda,
+}
+
+/// A located export document, either inside a zip archive or on disk (a
+/// bare XML file, or one found inside an already-extracted export directory).
+#[derive(Debug)]
+pub enum ExportInput {
+    Zip { path: PathBuf, entry: String },
+    Xml(PathBuf),
+}
+
+impl ExportInput {
+    /// Detects whether `path` is a zip, an extracted export directory or a
+    /// raw XML file, and locates the requested document within it.
+    pub fn detect(path: &Path, document: Document) -> Result<Self, ParseError> {
+        if path.is_dir() {
+            return locate_in_dir(path, document).map(ExportInput::Xml);
+        }
+
+        let mut magic = [0u8; 4];
+        let read = File::open(path)?.read(&mut magic)?;
+        if magic[..read] == *b"PK\x03\x04" {
+            let entry = locate_in_zip(path, document)?;
+            Ok(ExportInput::Zip {
+                path: path.to_path_buf(),
+                entry,
+            })
+        } else {
+            Ok(ExportInput::Xml(path.to_path_buf()))
+        }
+    }
+
+    /// The file on disk whose contents identify this export for caching.
+    pub fn source_path(&self) -> &Path {
+        match self {
+            ExportInput::Zip { path, .. } => path,
+            ExportInput::Xml(path) => path,
+        }
+    }
+}
+
+impl fmt::Display for ExportInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportInput::Zip { path, entry } => write!(f, "{} ({})", path.display(), entry),
+            ExportInput::Xml(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl Document {
+    fn file_name(self) -> &'static str {
+        match self {
+            Document::Export => "export.xml",
+            Document::Cda => "export_cda.xml",
+        }
+    }
+
+    /// The name of the document's root element.
+    pub fn root_element(self) -> &'static str {
+        match self {
+            Document::Export => "HealthData",
+            Document::Cda => "ClinicalDocument",
+        }
+    }
+
+    /// Localised exports use different folder names, so candidates are any
+    /// XML file of the right rendition; the canonical name is tried first.
+    fn is_candidate(self, name: &str) -> bool {
+        let lower = name.to_ascii_lowercase();
+        if !lower.ends_with(".xml") || lower.contains("__macosx") {
+            return false;
+        }
+        lower.ends_with("_cda.xml") == (self == Document::Cda)
+    }
+
+    fn candidate_rank(self, name: &str) -> (bool, usize) {
+        let file_name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+        (
+            !file_name.eq_ignore_ascii_case(self.file_name()),
+            name.matches(['/', '\\']).count(),
+        )
+    }
+
+    /// Real exports open with a long internal DTD, so the root element itself
+    /// may lie beyond the sniffed bytes; the DOCTYPE names it up front.
+    fn matches(self, head: &[u8]) -> bool {
+        let contains = |needle: &[u8]| head.windows(needle.len()).any(|window| window == needle);
+        match self {
+            Document::Export => contains(b"<!DOCTYPE HealthData") || contains(b"<HealthData"),
+            Document::Cda => contains(b"<ClinicalDocument"),
+        }
+    }
+}
+
+fn locate_in_zip(zip_path: &Path, document: Document) -> Result<String, ParseError> {
+    let mut archive = ZipArchive::new(BufReader::new(File::open(zip_path)?))?;
+    let mut candidates: Vec<String> = archive
+        .file_names()
+        .filter(|name| document.is_candidate(name))
+        .map(String::from)
+        .collect();
+    candidates.sort_by_key(|name| document.candidate_rank(name));
+
+    for name in candidates {
+        let mut head = Vec::new();
+        archive
+            .by_name(&name)?
+            .take(SNIFF_LEN)
+            .read_to_end(&mut head)?;
+        if document.matches(&head) {
+            return Ok(name);
+        }
+    }
+
+    Err(ParseError::MissingExport {
+        path: zip_path.to_path_buf(),
+        document: document.file_name().to_string(),
+    })
+}
+
+fn locate_in_dir(dir: &Path, document: Document) -> Result<PathBuf, ParseError> {
+    let mut candidates = Vec::new();
+    collect_xml_files(dir, document, 0, &mut candidates)?;
+    candidates.sort_by_key(|path| document.candidate_rank(&path.to_string_lossy()));
+
+    for path in candidates {
+        let mut head = Vec::new();
+        File::open(&path)?.take(SNIFF_LEN).read_to_end(&mut head)?;
+        if document.matches(&head) {
+            return Ok(path);
+        }
+    }
+
+    Err(ParseError::MissingExport {
+        path: dir.to_path_buf(),
+        document: document.file_name().to_string(),
+    })
+}
+
+fn collect_xml_files(
+    dir: &Path,
+    document: Document,
+    depth: usize,
+    out: &mut Vec<PathBuf>,
+) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if depth < MAX_DIR_DEPTH {
+                collect_xml_files(&path, document, depth + 1, out)?;
+            }
+        } else if document.is_candidate(&path.to_string_lossy()) {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
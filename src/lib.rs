@@ -0,0 +1,749 @@
+pub mod cache;
+pub mod cda;
+pub mod chunker;
+pub mod diagnostics;
+pub mod diff;
+pub mod error;
+pub mod generate;
+pub mod health_type;
+pub mod input;
+pub mod merge;
+pub mod order;
+pub mod progress;
+pub mod query;
+pub mod raw_element;
+pub mod recovery;
+pub mod state_of_mind;
+pub mod summary;
+pub mod type_filter;
+pub mod vision_prescription;
+pub mod workout_activity;
+use chrono::{Datelike, Duration, Utc};
+use csv::Writer;
+use diagnostics::{Diagnostics, DocumentIssue, SkipReason};
+use error::{ElementError, ElementFault, ParseError, line_at};
+use health_type::RecordType;
+use input::{Document, ExportInput};
+use memmap2::Mmap;
+use progress::{Progress, Task, Unit};
+use quick_xml::escape::unescape;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use raw_element::{UnknownElements, collect_unknown_elements};
+use serde::{Deserialize, Serialize};
+use smallstr::SmallString;
+use state_of_mind::{StateOfMind, parse_state_of_mind};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::SystemTime;
+use type_filter::TypeFilter;
+use vision_prescription::{VisionPrescription, parse_vision_prescriptions};
+use workout_activity::WorkoutActivityType;
+use zip::ZipArchive;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HealthRecord {
+    #[serde(rename = "type")]
+    pub record_type: Option<RecordType>,
+    pub unit: Option<SmallString<[u8; 16]>>,
+    pub value: Option<SmallString<[u8; 64]>>,
+    #[serde(rename = "startDate")]
+    pub start_date: Option<SmallString<[u8; 32]>>,
+    #[serde(rename = "endDate")]
+    pub end_date: Option<SmallString<[u8; 32]>>,
+    pub metadata: HashMap<SmallString<[u8; 16]>, SmallString<[u8; 32]>>,
+    #[serde(rename = "sourceName")]
+    pub source_name: Option<SmallString<[u8; 32]>>,
+    /// Short blake3 hash of the export the record was read from.
+    #[serde(rename = "exportId")]
+    pub export_id: Option<SmallString<[u8; 16]>>,
+}
+
+pub const METADATA_KEYS_TO_INCLUDE: &[&str] = &["HKActivityType", "HKPhysicalEffortEstimationType"];
+
+/// A record that borrows its strings from the document it was parsed from,
+/// so parsing allocates next to nothing until `into_owned` is called.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthRecordRef<'a> {
+    pub record_type: Option<RecordType>,
+    pub unit: Option<Cow<'a, str>>,
+    pub value: Option<Cow<'a, str>>,
+    pub start_date: Option<Cow<'a, str>>,
+    pub end_date: Option<Cow<'a, str>>,
+    /// The entries whose key is in `METADATA_KEYS_TO_INCLUDE`, in document
+    /// order.
+    pub metadata: Vec<(Cow<'a, str>, Cow<'a, str>)>,
+    pub source_name: Option<Cow<'a, str>>,
+}
+
+impl HealthRecordRef<'_> {
+    pub fn into_owned(self) -> HealthRecord {
+        HealthRecord {
+            record_type: self.record_type,
+            unit: self.unit.as_deref().map(SmallString::from),
+            value: self.value.as_deref().map(SmallString::from),
+            start_date: self.start_date.as_deref().map(SmallString::from),
+            end_date: self.end_date.as_deref().map(SmallString::from),
+            metadata: self
+                .metadata
+                .iter()
+                .map(|(key, value)| (SmallString::from(&**key), SmallString::from(&**value)))
+                .collect(),
+            source_name: self.source_name.as_deref().map(SmallString::from),
+            export_id: None,
+        }
+    }
+}
+
+impl From<HealthRecordRef<'_>> for HealthRecord {
+    fn from(record: HealthRecordRef<'_>) -> Self {
+        record.into_owned()
+    }
+}
+
+/// Everything extracted from one export; this is what the record cache stores.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ParsedExport {
+    pub records: Vec<HealthRecord>,
+    pub state_of_mind: Vec<StateOfMind>,
+    pub vision_prescriptions: Vec<VisionPrescription>,
+    pub unknown: UnknownElements,
+    /// Elements skipped in lenient mode because they could not be parsed.
+    pub errors: Vec<ElementError>,
+    pub diagnostics: Diagnostics,
+}
+
+/// Records parsed from a document, plus what was skipped and why. `R` is
+/// `HealthRecordRef` for records that still borrow from the document.
+#[derive(Debug)]
+pub struct ParsedRecords<R = HealthRecord> {
+    pub records: Vec<R>,
+    /// Elements skipped in lenient mode because they could not be parsed.
+    pub errors: Vec<ElementError>,
+    pub diagnostics: Diagnostics,
+}
+
+impl<R> Default for ParsedRecords<R> {
+    fn default() -> Self {
+        ParsedRecords {
+            records: Vec::new(),
+            errors: Vec::new(),
+            diagnostics: Diagnostics::default(),
+        }
+    }
+}
+
+impl<R> ParsedRecords<R> {
+    /// Notes an element starting at byte `start` of `xml` that failed to parse.
+    pub(crate) fn fail(&mut self, xml: &str, element: &str, start: usize, fault: ElementFault) {
+        let reason = SkipReason::from(fault.kind);
+        self.diagnostics
+            .skip(reason, None, start, &xml[start..], || fault.message.clone());
+        // Faults in attributes are placed where their tag's event began,
+        // which may be the whitespace before the tag.
+        let position = start + fault.position;
+        let offset = match xml.get(position..) {
+            Some(rest) => xml.len() - rest.trim_start().len(),
+            None => position,
+        };
+        self.errors.push(ElementError::new(
+            element,
+            fault.kind,
+            offset,
+            fault.message,
+        ));
+    }
+
+    /// Joins per-chunk results in document order. Unless `lenient`, the first
+    /// error in the document fails the whole parse.
+    pub(crate) fn concat(
+        xml: &str,
+        batches: Vec<ParsedRecords<R>>,
+        lenient: bool,
+    ) -> Result<Self, ParseError> {
+        let mut parsed = ParsedRecords::default();
+        for mut batch in batches {
+            if !lenient && !batch.errors.is_empty() {
+                let mut error = batch.errors.swap_remove(0);
+                error.line = line_at(xml, error.offset as usize);
+                return Err(error.into());
+            }
+            parsed.records.extend(batch.records);
+            parsed.errors.extend(batch.errors);
+            parsed.diagnostics.merge(batch.diagnostics);
+        }
+        parsed.diagnostics.resolve_lines(xml);
+        let errors = parsed.errors.iter_mut();
+        error::resolve_lines(xml, errors.map(|error| (error.offset, &mut error.line)));
+        Ok(parsed)
+    }
+}
+
+/// Hashes the export from a memory map in fixed-size slices, so progress can
+/// be reported without reading the whole archive into memory.
+pub fn get_file_hash(path: &Path, progress: &Progress) -> Result<String, ParseError> {
+    const SLICE_LEN: usize = 64 * 1024 * 1024;
+
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let task = progress.task("hashing", Some(len), Unit::Bytes);
+    let mut hasher = blake3::Hasher::new();
+
+    if len > 0 {
+        // SAFETY: the export is only read; a concurrent writer would at worst
+        // produce a hash that does not match any cache entry.
+        let mapped = unsafe { Mmap::map(&file) }?;
+        for slice in mapped.chunks(SLICE_LEN) {
+            hasher.update_rayon(slice);
+            task.inc(slice.len() as u64);
+        }
+    }
+
+    task.finish();
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// A cheaper stand-in for `get_file_hash` on very large exports: hashes the
+/// file size, mtime and, for zips, the central directory (names, sizes and
+/// CRCs) instead of every byte of the archive.
+pub fn get_fast_file_key(input: &ExportInput) -> Result<String, ParseError> {
+    let path = input.source_path();
+    let metadata = fs::metadata(path)?;
+    let mtime = metadata
+        .modified()?
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_err(io::Error::other)?;
+
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"fast-key");
+    hasher.update(&metadata.len().to_le_bytes());
+    hasher.update(&mtime.as_nanos().to_le_bytes());
+
+    if let ExportInput::Zip { entry, .. } = input {
+        hasher.update(entry.as_bytes());
+        let mut archive = ZipArchive::new(BufReader::new(File::open(path)?))?;
+        for i in 0..archive.len() {
+            let entry = archive.by_index_raw(i)?;
+            hasher.update(entry.name().as_bytes());
+            hasher.update(&entry.crc32().to_le_bytes());
+            hasher.update(&entry.compressed_size().to_le_bytes());
+            hasher.update(&entry.size().to_le_bytes());
+        }
+    }
+
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+pub fn cutoff_year_month() -> (i32, u32) {
+    let cutoff = Utc::now() - Duration::days(365);
+    (cutoff.year(), cutoff.month())
+}
+
+pub fn is_in_last_12_months(date_str: &str) -> bool {
+    is_on_or_after(date_str, cutoff_year_month())
+}
+
+/// Whether `date_str` falls in or after the month `(cutoff_year, cutoff_month)`.
+pub fn is_on_or_after(date_str: &str, (cutoff_year, cutoff_month): (i32, u32)) -> bool {
+    if date_str.len() < 7 {
+        return false;
+    }
+    let year: i32 = date_str[0..4].parse().unwrap_or(0);
+    let month: u32 = date_str[5..7].parse().unwrap_or(0);
+
+    if year > cutoff_year {
+        true
+    } else if year == cutoff_year {
+        month >= cutoff_month
+    } else {
+        false
+    }
+}
+
+/// Maps the export document into memory. Zipped exports are first streamed
+/// into an anonymous temp file so the document never has to fit in heap
+/// memory; bare XML files are mapped in place.
+pub fn read_export_xml(input: &ExportInput, progress: &Progress) -> Result<Mmap, ParseError> {
+    let (zip_path, entry) = match input {
+        ExportInput::Zip { path, entry } => (path, entry),
+        ExportInput::Xml(path) => {
+            let file = File::open(path)?;
+            // SAFETY: the export is only read; mapping it in place is what
+            // lets warm runs on extracted exports skip copying entirely.
+            return Ok(unsafe { Mmap::map(&file) }?);
+        }
+    };
+
+    let file = File::open(zip_path)?;
+    let mut archive = ZipArchive::new(BufReader::new(file))?;
+    let mut export_file = archive.by_name(entry)?;
+
+    let task = progress.task("extracting", Some(export_file.size()), Unit::Bytes);
+    let mut extracted = tempfile::tempfile()?;
+    let mut writer = BufWriter::new(&mut extracted);
+    io::copy(&mut task.wrap_read(&mut export_file), &mut writer)?;
+    writer.flush()?;
+    drop(writer);
+    task.finish();
+
+    // SAFETY: the temp file is unlinked and private to this process, so
+    // nothing else can modify it while it is mapped.
+    Ok(unsafe { Mmap::map(&extracted) }?)
+}
+
+/// Parses every `<Record>` of `export.xml` into a `HealthRecord`, those
+/// inside correlations included, in document order.
+pub fn parse_records(
+    xml: &str,
+    type_filter: &TypeFilter,
+    task: &Task,
+    lenient: bool,
+) -> Result<ParsedRecords, ParseError> {
+    parse_record_elements(xml, type_filter, task, lenient, HealthRecordRef::into_owned)
+}
+
+/// Like `parse_records`, but the records borrow from `xml` instead of
+/// copying every attribute, for callers that filter or aggregate in place.
+pub fn parse_record_refs<'a>(
+    xml: &'a str,
+    type_filter: &TypeFilter,
+    task: &Task,
+    lenient: bool,
+) -> Result<ParsedRecords<HealthRecordRef<'a>>, ParseError> {
+    parse_record_elements(xml, type_filter, task, lenient, |record| record)
+}
+
+fn parse_record_elements<'a, R: Send>(
+    xml: &'a str,
+    type_filter: &TypeFilter,
+    task: &Task,
+    lenient: bool,
+    keep: impl Fn(HealthRecordRef<'a>) -> R + Sync,
+) -> Result<ParsedRecords<R>, ParseError> {
+    let metadata_keys_to_include: HashSet<&str> =
+        METADATA_KEYS_TO_INCLUDE.iter().copied().collect();
+
+    // The body only skips the prolog, whose DTD may mention the element too.
+    let body = recovery::root_start(xml, Document::Export.root_element()).unwrap_or(0);
+    let parts = rayon::current_num_threads() * 8;
+    let batches = chunker::par_elements(xml, body, "Record", parts, |elements| {
+        let mut batch = ParsedRecords::default();
+        for (start, element) in elements {
+            batch.diagnostics.scanned += 1;
+            match parse_record(
+                element,
+                start,
+                type_filter,
+                &metadata_keys_to_include,
+                &mut batch.diagnostics,
+            ) {
+                Ok(Some(record)) => batch.records.push(keep(record)),
+                Ok(None) => {}
+                Err(fault) => batch.fail(xml, "Record", start, fault),
+            }
+        }
+        task.inc(batch.diagnostics.scanned);
+        batch
+    });
+
+    ParsedRecords::concat(xml, batches, lenient)
+}
+
+/// A raw attribute value, decoded and unescaped; it is only copied when it
+/// contains entities such as `&lt;`.
+pub(crate) fn attr_str(value: &[u8], position: u64) -> Result<Cow<'_, str>, ElementFault> {
+    let value = std::str::from_utf8(value).map_err(|e| ElementFault::encoding(position, e))?;
+    unescape(value).map_err(|e| ElementFault::xml(position, e))
+}
+
+/// A raw attribute value, unescaped, as a slice of `element`, which it
+/// normally points into. quick-xml leaves values escaped, so only a value
+/// with entities, or one quick-xml had to copy, is copied here.
+fn attr_cow<'a>(
+    element: &'a str,
+    value: &[u8],
+    position: u64,
+) -> Result<Cow<'a, str>, ElementFault> {
+    let offset = (value.as_ptr() as usize).wrapping_sub(element.as_ptr() as usize);
+    match offset
+        .checked_add(value.len())
+        .and_then(|end| element.get(offset..end))
+    {
+        Some(borrowed) => unescape(borrowed).map_err(|e| ElementFault::xml(position, e)),
+        None => Ok(Cow::Owned(attr_str(value, position)?.into_owned())),
+    }
+}
+
+/// Parses the record `element`, which starts at byte `start` of the
+/// document, noting in `diagnostics` why it was skipped if it does not
+/// become a record.
+fn parse_record<'a>(
+    element: &'a str,
+    start: usize,
+    type_filter: &TypeFilter,
+    metadata_keys_to_include: &HashSet<&str>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Option<HealthRecordRef<'a>>, ElementFault> {
+    let mut reader = Reader::from_str(element);
+    reader.config_mut().trim_text(true);
+
+    let mut record = HealthRecordRef {
+        record_type: None,
+        unit: None,
+        value: None,
+        start_date: None,
+        end_date: None,
+        metadata: Vec::new(),
+        source_name: None,
+    };
+
+    loop {
+        // Where the next tag starts, to locate errors in its attributes.
+        let position = reader.buffer_position();
+        let event = reader
+            .read_event()
+            .map_err(|e| ElementFault::xml(reader.error_position(), e))?;
+        match event {
+            Event::Empty(ref e) | Event::Start(ref e) => {
+                if e.name().as_ref() == b"Record" {
+                    // Apple writes `type` first, but anything before it is
+                    // held back until the record is known to be wanted.
+                    let mut before_type = Vec::new();
+                    for attr in recovery::attributes(e) {
+                        let attr = attr.map_err(|e| ElementFault::xml(position, e))?;
+                        let key = attr.key.into_inner();
+                        let value = attr_cow(element, &attr.value, position)?;
+
+                        if key != b"type" {
+                            if record.record_type.is_none() {
+                                before_type.push((key, value));
+                            } else if !set_record_attribute(
+                                &mut record,
+                                key,
+                                value,
+                                type_filter,
+                                start,
+                                element,
+                                diagnostics,
+                            ) {
+                                return Ok(None);
+                            }
+                            continue;
+                        }
+
+                        if !type_filter.allows(&value) {
+                            diagnostics.skip(
+                                SkipReason::FilteredByType,
+                                Some(&value),
+                                start,
+                                element,
+                                String::new,
+                            );
+                            return Ok(None);
+                        }
+                        record.record_type = Some(RecordType::from_identifier(&value));
+                        for (key, value) in before_type.drain(..) {
+                            if !set_record_attribute(
+                                &mut record,
+                                key,
+                                value,
+                                type_filter,
+                                start,
+                                element,
+                                diagnostics,
+                            ) {
+                                return Ok(None);
+                            }
+                        }
+                    }
+                } else if e.name().as_ref() == b"MetadataEntry" && record.record_type.is_some() {
+                    let mut key_opt = None;
+                    let mut value_opt = None;
+
+                    for attr in recovery::attributes(e) {
+                        let attr = attr.map_err(|e| ElementFault::xml(position, e))?;
+                        match attr.key.as_ref() {
+                            b"key" => key_opt = Some(attr_cow(element, &attr.value, position)?),
+                            b"value" => {
+                                value_opt = Some(attr_cow(element, &attr.value, position)?);
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    if let (Some(key), Some(mut value)) = (key_opt, value_opt)
+                        && metadata_keys_to_include.contains(&*key)
+                    {
+                        // Codes and identifiers alike get the display name, so
+                        // an activity has one spelling in the output.
+                        if key == "HKActivityType"
+                            && let Ok(activity) = value.parse::<WorkoutActivityType>()
+                        {
+                            value = Cow::Owned(activity.to_string());
+                        }
+                        // Later duplicates replace earlier ones, as they
+                        // would in a map.
+                        record.metadata.retain(|(existing, _)| *existing != key);
+                        record.metadata.push((key, value));
+                    }
+                }
+            }
+            Event::End(ref e) if e.name().as_ref() == b"Record" => {
+                break;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        // A self-closing record has no children; what follows belongs to
+        // its parent or the next element.
+        if matches!(event, Event::Empty(ref e) if e.name().as_ref() == b"Record") {
+            break;
+        }
+    }
+
+    if record.record_type.is_none() {
+        diagnostics.skip(SkipReason::MissingType, None, start, element, String::new);
+        return Ok(None);
+    }
+    diagnostics.parsed += 1;
+    Ok(Some(record))
+}
+
+/// Sets attribute `key` of `record`, whose type is already known, or
+/// notes in `diagnostics` and returns false when `type_filter` rejects its
+/// start date.
+fn set_record_attribute<'a>(
+    record: &mut HealthRecordRef<'a>,
+    key: &[u8],
+    value: Cow<'a, str>,
+    type_filter: &TypeFilter,
+    start: usize,
+    element: &str,
+    diagnostics: &mut Diagnostics,
+) -> bool {
+    match key {
+        b"startDate" => {
+            if !type_filter.allows_date(&value) {
+                let record_type = record.record_type.as_ref().map(RecordType::identifier);
+                diagnostics.skip(
+                    SkipReason::FilteredByDate,
+                    record_type,
+                    start,
+                    element,
+                    || format!("startDate {} is before the cutoff", value),
+                );
+                return false;
+            }
+            record.start_date = Some(value);
+        }
+        b"value" => record.value = Some(value),
+        b"unit" => record.unit = Some(value),
+        b"endDate" => record.end_date = Some(value),
+        b"sourceName" => record.source_name = Some(value),
+        _ => {}
+    }
+    true
+}
+
+pub fn write_csv<'a>(
+    records: impl IntoIterator<Item = &'a HealthRecord>,
+    path: impl AsRef<Path>,
+) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(path)?;
+
+    wtr.write_record([
+        "record_type",
+        "value",
+        "unit",
+        "start_date",
+        "end_date",
+        "metadata",
+        "source_name",
+        "export_id",
+    ])?;
+
+    for rec in records {
+        let meta_str = serde_json::to_string(&rec.metadata).unwrap_or_default();
+
+        wtr.write_record([
+            rec.record_type.as_ref().map_or("", RecordType::identifier),
+            rec.value.as_deref().unwrap_or(""),
+            rec.unit.as_deref().unwrap_or(""),
+            rec.start_date.as_deref().unwrap_or(""),
+            rec.end_date.as_deref().unwrap_or(""),
+            &meta_str,
+            rec.source_name.as_deref().unwrap_or(""),
+            rec.export_id.as_deref().unwrap_or(""),
+        ])?;
+    }
+
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Writes one CSV per record type into `dir`, named after the type's
+/// catalogue entry (e.g. `heart_rate.csv`).
+pub fn write_csv_per_type(records: &[HealthRecord], dir: &Path) -> Result<(), Box<dyn Error>> {
+    let mut by_type: BTreeMap<&RecordType, Vec<&HealthRecord>> = BTreeMap::new();
+    for rec in records {
+        if let Some(record_type) = &rec.record_type {
+            by_type.entry(record_type).or_default().push(rec);
+        }
+    }
+
+    fs::create_dir_all(dir)?;
+    for (record_type, records) in by_type {
+        let path = dir.join(format!("{}.csv", health_type::file_stem(record_type)));
+        write_csv(records, path)?;
+    }
+    Ok(())
+}
+
+/// Notes an error outside the elements the parsers split the document
+/// into: fatal unless `lenient`, in which case it is reported as a problem
+/// with the document rather than as a skipped element.
+fn document_error(
+    xml: &str,
+    mut error: ElementError,
+    lenient: bool,
+    diagnostics: &mut Diagnostics,
+) -> Result<(), ParseError> {
+    let offset = error.offset as usize;
+    if !lenient {
+        error.line = line_at(xml, offset);
+        return Err(error.into());
+    }
+    let rest = xml.get(offset..).unwrap_or_default();
+    diagnostics.document_issue(DocumentIssue::from(error.kind), offset, rest, || {
+        format!("in <{}>: {}", error.element, error.message)
+    });
+    Ok(())
+}
+
+/// Decodes `bytes`, which are not all UTF-8, with each invalid sequence read
+/// as U+FFFD, noting every one in `diagnostics` against the line it is on.
+fn decode_lossy(bytes: &[u8], diagnostics: &mut Diagnostics) -> String {
+    let mut xml = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        xml.push_str(chunk.valid());
+        let invalid = chunk.invalid();
+        if invalid.is_empty() {
+            continue;
+        }
+        let offset = xml.len();
+        let line_start = xml.rfind('\n').map_or(0, |i| i + 1);
+        xml.push(char::REPLACEMENT_CHARACTER);
+        // The rest of the line is not decoded yet; the excerpt shows the
+        // element up to the bad bytes.
+        diagnostics.document_issue(
+            DocumentIssue::BadEncoding,
+            offset,
+            &xml[line_start..],
+            || {
+                let hex: Vec<String> = invalid.iter().map(|b| format!("{:02x}", b)).collect();
+                format!(
+                    "invalid UTF-8 ({}) read as U+FFFD; the element containing it was kept",
+                    hex.join(" ")
+                )
+            },
+        );
+    }
+    xml
+}
+
+pub fn parse_export(
+    input: &ExportInput,
+    document: Document,
+    type_filter: &TypeFilter,
+    keep_unknown_elements: bool,
+    lenient: bool,
+    progress: &Progress,
+) -> Result<ParsedExport, ParseError> {
+    let mapped_xml = read_export_xml(input, progress)?;
+    let mut diagnostics = Diagnostics::default();
+    let xml = match std::str::from_utf8(&mapped_xml) {
+        Ok(xml) => Cow::Borrowed(xml),
+        Err(e) if !lenient => {
+            let valid = &mapped_xml[..e.valid_up_to()];
+            return Err(ParseError::Encoding {
+                offset: valid.len() as u64,
+                line: memchr::memchr_iter(b'\n', valid).count() as u64 + 1,
+            });
+        }
+        Err(_) => Cow::Owned(decode_lossy(&mapped_xml, &mut diagnostics)),
+    };
+    let full_xml = xml.as_ref();
+
+    let root = document.root_element();
+    let body = recovery::root_start(full_xml, root).unwrap_or(0);
+    // An interrupted export stops mid-element; everything before the last
+    // complete top-level element is still good.
+    let xml = match recovery::complete_len(full_xml, body, root) {
+        Some(len) => {
+            let dropped = full_xml[len..].trim_start();
+            let offset = full_xml.len() - dropped.len();
+            diagnostics.document_issue(DocumentIssue::Truncated, offset, dropped, || {
+                format!(
+                    "document ends without </{}>; dropped {} bytes after the last complete element",
+                    root,
+                    full_xml.len() - len
+                )
+            });
+            &full_xml[..len]
+        }
+        None => full_xml,
+    };
+
+    let mut errors = Vec::new();
+    let task = progress.task("parsing", None, Unit::Records);
+    let mut parsed = match document {
+        Document::Export => {
+            let records = parse_records(xml, type_filter, &task, lenient)?;
+            let state_of_mind = parse_state_of_mind(xml, type_filter, &task, lenient)?;
+            let vision_prescriptions = parse_vision_prescriptions(xml, &task, lenient)?;
+            errors.extend(records.errors);
+            errors.extend(state_of_mind.errors);
+            errors.extend(vision_prescriptions.errors);
+            diagnostics.merge(records.diagnostics);
+            diagnostics.merge(state_of_mind.diagnostics);
+            diagnostics.merge(vision_prescriptions.diagnostics);
+            let truncated = xml.len() < full_xml.len();
+            let (unknown, error) =
+                collect_unknown_elements(xml, body, keep_unknown_elements, truncated);
+            if let Some(error) = error {
+                document_error(xml, error, lenient, &mut diagnostics)?;
+            }
+            errors.sort_by_key(|error| error.offset);
+            ParsedExport {
+                records: records.records,
+                state_of_mind: state_of_mind.records,
+                vision_prescriptions: vision_prescriptions.records,
+                unknown,
+                errors,
+                diagnostics,
+            }
+        }
+        Document::Cda => {
+            let records = cda::parse_cda(xml, type_filter, &task, lenient)?;
+            errors.extend(records.errors);
+            diagnostics.merge(records.diagnostics);
+            ParsedExport {
+                records: records.records,
+                state_of_mind: Vec::new(),
+                vision_prescriptions: Vec::new(),
+                unknown: UnknownElements::default(),
+                errors,
+                diagnostics,
+            }
+        }
+    };
+    task.finish();
+    parsed.diagnostics.resolve_lines(full_xml);
+
+    Ok(parsed)
+}
@@ -1,333 +1,622 @@
-mod workout_activity;
-use blake3;
-use chrono::{Datelike, Duration, Utc};
-use csv::Writer;
-use quick_xml::events::Event;
-use quick_xml::reader::Reader;
-use rayon::prelude::*;
-use serde::{Deserialize, Serialize};
+use apple_health_export_parser_rs::generate::{self, GeneratorConfig};
+use apple_health_export_parser_rs::health_type;
+use apple_health_export_parser_rs::input::{Document, ExportInput};
+use apple_health_export_parser_rs::order::{self, RecordOrder};
+use apple_health_export_parser_rs::progress::Progress;
+use apple_health_export_parser_rs::query::Query;
+use apple_health_export_parser_rs::type_filter::{FilterConfig, TypeFilter};
+use apple_health_export_parser_rs::{
+    ParsedExport, cache, cda, diff, get_fast_file_key, get_file_hash, merge, parse_export, summary,
+    write_csv, write_csv_per_type,
+};
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand};
+use indicatif::{HumanBytes, HumanDuration};
 use smallstr::SmallString;
-use std::collections::HashMap;
-use std::collections::HashSet;
-use std::env;
-use std::error::Error;
 use std::fs;
-use std::fs::File;
-use std::io::{BufReader, Read};
 use std::path::{Path, PathBuf};
-use std::time::Instant;
-use workout_activity::WorkoutActivityType;
-use zip::ZipArchive;
-
-#[derive(Debug, Serialize, Deserialize)]
-struct HealthRecord {
-    #[serde(rename = "type")]
-    record_type: Option<SmallString<[u8; 32]>>,
-    unit: Option<SmallString<[u8; 16]>>,
-    value: Option<SmallString<[u8; 64]>>,
-    #[serde(rename = "startDate")]
-    start_date: Option<SmallString<[u8; 32]>>,
-    #[serde(rename = "endDate")]
-    end_date: Option<SmallString<[u8; 32]>>,
-    metadata: HashMap<SmallString<[u8; 16]>, SmallString<[u8; 32]>>,
+use std::process::ExitCode;
+use std::time::{Instant, SystemTime};
+
+/// How many skipped elements are listed individually in lenient mode.
+const MAX_REPORTED_ERRORS: usize = 10;
+
+#[derive(Parser)]
+#[command(about = "Parse an Apple Health export into JSON and CSV")]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Export zips, extracted export directories or bare export.xml files;
+    /// several inputs are merged into one deduplicated dataset
+    #[arg(default_value = "export.zip", num_args = 1..)]
+    input: Vec<PathBuf>,
+
+    /// Suppress progress bars and status output
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Emit progress as JSON lines on stderr
+    #[arg(long, global = true)]
+    progress_json: bool,
+
+    /// Read records from the HL7 CDA rendition (export_cda.xml) instead of export.xml
+    #[arg(long, global = true)]
+    from_cda: bool,
+
+    /// Also write the filtered records as an HL7 CDA document to output_cda.xml
+    #[arg(long)]
+    write_cda: bool,
+
+    /// Also write one CSV per record type into csv/, e.g. csv/heart_rate.csv
+    #[arg(long)]
+    csv_per_type: bool,
+
+    /// Also write record counts and value totals per type group to summary.json
+    #[arg(long)]
+    summary: bool,
+
+    /// Skip elements that fail to parse instead of aborting, and report them
+    #[arg(long, global = true)]
+    lenient: bool,
+
+    /// Write unrecognised top-level elements to unknown_elements.json
+    #[arg(long)]
+    unknown_elements: bool,
+
+    /// Type filter profile (TOML or JSON) with include and exclude patterns
+    #[arg(long, global = true)]
+    type_filter: Option<PathBuf>,
+
+    /// Record types to include: identifiers, globs such as
+    /// HKQuantityTypeIdentifierDietary* or groups such as @heart
+    #[arg(long, global = true, value_delimiter = ',')]
+    types: Vec<String>,
+
+    /// Record types to exclude, in the same forms as --types
+    #[arg(long, global = true, value_delimiter = ',')]
+    exclude_types: Vec<String>,
+
+    /// Cache location (defaults to the platform cache directory)
+    #[arg(long, global = true)]
+    cache_dir: Option<PathBuf>,
+
+    /// Neither read nor write the parsed record cache
+    #[arg(long, global = true)]
+    no_cache: bool,
+
+    /// Key the cache on size, mtime and zip CRCs instead of hashing the whole export
+    /// (the export is still hashed for its export id when it has to be parsed)
+    #[arg(long, global = true)]
+    fast_cache_key: bool,
+
+    /// Prune the least recently used cache entries beyond this size after a run (e.g. 2G)
+    #[arg(long, global = true, value_parser = cache::parse_size)]
+    cache_max_size: Option<u64>,
+
+    /// Keep only the records matching an expression, e.g.
+    /// 'type = HeartRate and value > 150 and hour(start) between 22 and 6';
+    /// records of every date, and of every type unless --types or
+    /// --type-filter narrow them, are parsed for it to choose from
+    #[arg(long, global = true)]
+    query: Option<Query>,
+
+    /// Output order: document, start (by start date) or type (by type, then start date)
+    #[arg(long, global = true, default_value = "document")]
+    order: RecordOrder,
+
+    /// Bytes of records to sort in memory before spilling sorted runs to disk (e.g. 512M)
+    #[arg(long, global = true, default_value = "1G", value_parser = cache::parse_size)]
+    sort_memory: u64,
 }
 
-fn get_cache_dir() -> PathBuf {
-    let mut temp_dir = env::temp_dir();
-    temp_dir.push("apple_health_export_parser_rs");
-    temp_dir
+#[derive(Subcommand)]
+enum Command {
+    /// Inspect or clean up the parsed record cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
+    /// List the known HealthKit record types, optionally only those of one group
+    Types {
+        /// e.g. heart, activity, sleep, nutrition or body
+        group: Option<String>,
+    },
+    /// Write only the records of NEW that OLD did not contain, to added.json and added.csv
+    Diff {
+        /// The previous export, its output.json, or a state file from --write-state
+        old: PathBuf,
+        /// The current export
+        new: PathBuf,
+        /// Save the keys of every record in NEW, to diff against on the next run
+        #[arg(long)]
+        write_state: Option<PathBuf>,
+    },
+    /// Write a synthetic export.zip with fake data, for benchmarks and demos
+    Generate {
+        /// Where to write the archive
+        #[arg(default_value = "synthetic_export.zip")]
+        output: PathBuf,
+        /// Days of data to generate, ending with --end
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+        /// Cover more days if needed to make export.xml about this size (e.g. 100M)
+        #[arg(long, value_parser = cache::parse_size)]
+        size: Option<u64>,
+        /// The last day covered, as YYYY-MM-DD (defaults to today)
+        #[arg(long)]
+        end: Option<NaiveDate>,
+        /// Seed for the random data; the same seed gives the same export
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Leave out the GPX routes of outdoor workouts
+        #[arg(long)]
+        no_routes: bool,
+    },
 }
 
-fn get_file_hash(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
-    let data = fs::read(path)?;
-    let hash = blake3::hash(&data);
-    Ok(hash.to_hex().to_string())
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// List cached exports with their size and age
+    List,
+    /// Remove every cache entry
+    Clear,
+    /// Remove entries older than --max-age, then the least recently used beyond --max-size
+    Prune {
+        /// e.g. 500M or 2G
+        #[arg(long, value_parser = cache::parse_size)]
+        max_size: Option<u64>,
+        /// e.g. 30d, 12h or 2w
+        #[arg(long, value_parser = cache::parse_age)]
+        max_age: Option<std::time::Duration>,
+    },
 }
 
-fn is_in_last_12_months(date_str: &str) -> bool {
-    if date_str.len() < 7 {
-        return false;
-    }
-    let year: i32 = date_str[0..4].parse().unwrap_or(0);
-    let month: u32 = date_str[5..7].parse().unwrap_or(0);
-
-    let now = Utc::now();
-    let cutoff_year = (now - Duration::days(365)).year();
-    let cutoff_month = (now - Duration::days(365)).month();
-
-    if year > cutoff_year {
-        true
-    } else if year == cutoff_year {
-        month >= cutoff_month
+/// Reads one export, from the record cache when possible, tags its records
+/// with the export id and applies the query.
+fn load_export(
+    path: &Path,
+    args: &Args,
+    type_filter: &TypeFilter,
+    cache_dir: &Path,
+    progress: &Progress,
+) -> Result<ParsedExport, Box<dyn std::error::Error>> {
+    let document = if args.from_cda {
+        Document::Cda
     } else {
-        false
-    }
-}
-
-fn try_load_cache(cache_dir: &Path, hash: &str) -> Option<String> {
-    let cache_path = cache_dir.join(format!("{}.xml", hash));
-    if cache_path.exists() {
-        fs::read_to_string(cache_path).ok()
+        Document::Export
+    };
+    let input = ExportInput::detect(path, document)?;
+    progress.message(format!("Reading export from {}", input));
+    let content_hash = if args.fast_cache_key {
+        None
     } else {
+        Some(get_file_hash(input.source_path(), progress)?)
+    };
+    let cache_key = if args.no_cache {
         None
+    } else {
+        let export_hash = match &content_hash {
+            Some(hash) => hash.clone(),
+            None => get_fast_file_key(&input)?,
+        };
+        let mut parser_config = type_filter.describe();
+        parser_config.push(format!("unknown_elements={}", args.unknown_elements));
+        parser_config.push(format!("document={:?}", document));
+        parser_config.push(format!("lenient={}", args.lenient));
+        Some(cache::cache_key(&export_hash, &parser_config))
+    };
+
+    let cached = cache_key
+        .as_deref()
+        .and_then(|key| cache::load::<ParsedExport>(cache_dir, key));
+    let mut parsed = match cached {
+        Some(parsed) => {
+            progress.message("Loaded parsed records from cache");
+            parsed
+        }
+        None => {
+            let mut parsed = parse_export(
+                &input,
+                document,
+                type_filter,
+                args.unknown_elements,
+                args.lenient,
+                progress,
+            )?;
+            // The export id is always the content hash, so it does not
+            // depend on --fast-cache-key; cached records keep theirs.
+            let content_hash = match content_hash {
+                Some(hash) => hash,
+                None => get_file_hash(input.source_path(), progress)?,
+            };
+            let export_id: SmallString<[u8; 16]> = SmallString::from(&content_hash[..16]);
+            for record in &mut parsed.records {
+                record.export_id = Some(export_id.clone());
+            }
+            if let Some(key) = &cache_key {
+                cache::save(cache_dir, key, &parsed)?;
+                if let Some(max_size) = args.cache_max_size {
+                    let legacy_dir = legacy_cache_dir(args, cache_dir);
+                    cache::prune(cache_dir, legacy_dir.as_deref(), Some(max_size), None)?;
+                }
+            }
+            parsed
+        }
+    };
+
+    if let Some(query) = &args.query {
+        let total = parsed.records.len();
+        parsed.records.retain(|record| query.matches(record));
+        progress.message(format!(
+            "Query kept {} of {} records",
+            parsed.records.len(),
+            total
+        ));
     }
-}
 
-fn save_cache(cache_dir: &Path, hash: &str, data: &str) -> Result<(), Box<dyn std::error::Error>> {
-    fs::create_dir_all(cache_dir)?;
-    let cache_path = cache_dir.join(format!("{}.xml", hash));
-    fs::write(cache_path, data)?;
-    Ok(())
+    Ok(parsed)
 }
 
-fn read_export_xml(zip_path: &Path) -> Result<String, Box<dyn std::error::Error>> {
-    let cache_dir = get_cache_dir();
-    let hash = get_file_hash(zip_path)?;
-
-    if let Some(cached_xml) = try_load_cache(&cache_dir, &hash) {
-        println!("Loaded XML from cache");
-        return Ok(cached_xml);
+/// Resolves the type filter from the profile file and CLI patterns; CLI
+/// patterns are added to those of the profile. A query is left to choose
+/// among records of any date, and of any type unless types are given.
+fn build_type_filter(args: &Args) -> Result<TypeFilter, Box<dyn std::error::Error>> {
+    let mut config = match &args.type_filter {
+        Some(path) => FilterConfig::load(path)?,
+        None => FilterConfig::default(),
+    };
+    config.include.extend(args.types.iter().cloned());
+    config.exclude.extend(args.exclude_types.iter().cloned());
+    if args.query.is_none() {
+        return Ok(TypeFilter::from_config(&config)?);
+    }
+    if config.include.is_empty() {
+        config.include.push("*".to_string());
     }
+    Ok(TypeFilter::from_config(&config)?.with_all_dates())
+}
 
-    let file = File::open(zip_path)?;
-    let mut archive = ZipArchive::new(BufReader::new(file))?;
-    let mut export_file = archive
-        .by_name("apple_health_export/export.xml")
-        .map_err(|_| "Could not find 'export.xml' in the archive")?;
+/// Compares two exports, or an export against a previous output.json or
+/// state file, and writes only the records that are new.
+fn run_diff(
+    old: &Path,
+    new: &Path,
+    write_state: Option<&Path>,
+    args: &Args,
+    type_filter: &TypeFilter,
+    cache_dir: &Path,
+    progress: &Progress,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let start = Instant::now();
 
-    let mut contents = String::new();
-    export_file.read_to_string(&mut contents)?;
+    let previous = if diff::is_state_file(old) {
+        progress.message(format!("Reading state from {}", old.display()));
+        diff::load_state(old)?
+    } else if diff::is_output_json(old) {
+        progress.message(format!("Reading previous output from {}", old.display()));
+        diff::record_hashes(&diff::load_output_json(old)?)
+    } else {
+        diff::record_hashes(&load_export(old, args, type_filter, cache_dir, progress)?.records)
+    };
+
+    let records = load_export(new, args, type_filter, cache_dir, progress)?.records;
+    let current = write_state.map(|_| diff::record_hashes(&records));
+    let total = records.len();
+    let mut added = diff::added_records(&previous, records);
+    order::sort_records(&mut added, args.order, args.sort_memory, progress)?;
+
+    let counts = diff::counts_by_type(&added);
+    progress.message(format!("{} of {} records are new", added.len(), total));
+    for (record_type, count) in &counts {
+        progress.message(format!("  {}: {}", record_type, count));
+    }
 
-    save_cache(&cache_dir, &hash, &contents)?;
+    let json_output = serde_json::to_string_pretty(&added)?;
+    fs::write("./added.json", json_output)?;
+    write_csv(&added, "added.csv")?;
+    let summary = serde_json::json!({
+        "previous": previous.len(),
+        "current": total,
+        "added": added.len(),
+        "by_type": counts,
+    });
+    fs::write(
+        "./diff_summary.json",
+        serde_json::to_string_pretty(&summary)?,
+    )?;
+
+    if let (Some(path), Some(current)) = (write_state, current) {
+        diff::save_state(path, &current)?;
+        progress.message(format!("Saved state to {}", path.display()));
+    }
 
-    Ok(contents)
+    progress.message(format!("Done in {:?}", start.elapsed()));
+    Ok(())
 }
 
-fn parse_records(xml: &str, allowed_types: &HashSet<&str>) -> Vec<HealthRecord> {
-    let allow_all = allowed_types.is_empty();
-    let chunks: Vec<&str> = xml.split("<Record ").collect();
-    let metadata_keys_to_include: HashSet<&str> =
-        ["HKActivityType", "HKPhysicalEffortEstimationType"]
-            .iter()
-            .copied()
-            .collect();
+fn list_types(group: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let group = match group {
+        Some(name) => Some(
+            health_type::TypeGroup::from_name(name)
+                .ok_or_else(|| format!("unknown type group '{}'", name))?,
+        ),
+        None => None,
+    };
+
+    for health_type in health_type::CATALOGUE {
+        if group.is_some_and(|group| health_type.group != group) {
+            continue;
+        }
+        println!(
+            "{:<58} {:<12} {:<11} {:<17} {:<10} {}",
+            health_type.identifier,
+            health_type.group.to_string(),
+            health_type.kind.to_string(),
+            health_type.unit.unwrap_or("-"),
+            health_type
+                .aggregation
+                .map_or("-".to_string(), |aggregation| aggregation.to_string()),
+            health_type.name
+        );
+    }
+    Ok(())
+}
 
-    chunks
-        .par_iter()
-        .skip(1)
-        .map(|chunk| {
-            let full_chunk = format!("<Record {}", chunk);
-            let mut reader = Reader::from_str(&full_chunk);
-            reader.config_mut().trim_text(true);
-
-            let mut buf = Vec::with_capacity(2048);
-
-            let mut record_type = None;
-            let mut value = None;
-            let mut unit = None;
-            let mut start_date = None;
-            let mut end_date = None;
-
-            let mut metadata: HashMap<SmallString<[u8; 16]>, SmallString<[u8; 32]>> =
-                HashMap::new();
-
-            let mut should_parse = allow_all;
-
-            while let Ok(event) = reader.read_event_into(&mut buf) {
-                match event {
-                    Event::Empty(ref e) | Event::Start(ref e) => {
-                        if e.name().as_ref() == b"Record" {
-                            for attr in e.attributes().flatten() {
-                                let key = attr.key.as_ref();
-                                let value_ref = attr.value.as_ref();
-
-                                if key == b"type" {
-                                    if let Ok(v_str) = std::str::from_utf8(value_ref) {
-                                        record_type = Some(SmallString::from(v_str));
-                                        should_parse = allow_all || allowed_types.contains(v_str);
-                                        if !should_parse {
-                                            break;
-                                        }
-                                    }
-                                    continue;
-                                }
-
-                                if !should_parse {
-                                    continue;
-                                }
-
-                                if key == b"startDate" {
-                                    if let Ok(v_str) = std::str::from_utf8(value_ref) {
-                                        if !is_in_last_12_months(v_str) {
-                                            should_parse = false;
-                                            continue;
-                                        }
-                                        start_date = Some(SmallString::from(v_str));
-                                    }
-                                }
-
-                                match key {
-                                    b"value" => {
-                                        if let Ok(v_str) = std::str::from_utf8(value_ref) {
-                                            value = Some(SmallString::from(v_str));
-                                        }
-                                    }
-                                    b"unit" => {
-                                        if let Ok(v_str) = std::str::from_utf8(value_ref) {
-                                            unit = Some(SmallString::from(v_str));
-                                        }
-                                    }
-                                    b"startDate" => {
-                                        if let Ok(v_str) = std::str::from_utf8(value_ref) {
-                                            start_date = Some(SmallString::from(v_str));
-                                        }
-                                    }
-                                    b"endDate" => {
-                                        if let Ok(v_str) = std::str::from_utf8(value_ref) {
-                                            end_date = Some(SmallString::from(v_str));
-                                        }
-                                    }
-                                    _ => {}
-                                }
-                            }
-                        } else if e.name().as_ref() == b"MetadataEntry" && should_parse {
-                            let mut key_opt: Option<SmallString<[u8; 16]>> = None;
-                            let mut value_opt: Option<SmallString<[u8; 32]>> = None;
-
-                            for attr in e.attributes().flatten() {
-                                match attr.key.as_ref() {
-                                    b"key" => {
-                                        let key_str =
-                                            std::str::from_utf8(attr.value.as_ref()).unwrap();
-                                        key_opt = Some(SmallString::from(key_str));
-                                    }
-                                    b"value" => {
-                                        let val_str =
-                                            std::str::from_utf8(attr.value.as_ref()).unwrap();
-                                        value_opt = Some(SmallString::from(val_str));
-                                    }
-                                    _ => {}
-                                }
-                            }
-
-                            if let (Some(key), Some(mut value)) = (key_opt, value_opt) {
-                                if key.as_str() == "HKActivityType" {
-                                    if let Ok(code) = value.parse::<u32>() {
-                                        let activity = WorkoutActivityType::from_u32(code);
-                                        value = SmallString::from(activity.to_string());
-                                    }
-                                }
-                                if metadata_keys_to_include.contains(key.as_str()) {
-                                    metadata.insert(key, value);
-                                }
-                            }
-                        }
-                    }
-                    Event::End(ref e) if e.name().as_ref() == b"Record" => {
-                        break;
-                    }
-                    Event::Eof => break,
-                    _ => {}
-                }
+/// The temp dir location older versions cached in, cleaned up along with
+/// the default cache dir but left alone when `--cache-dir` picks another.
+fn legacy_cache_dir(args: &Args, cache_dir: &Path) -> Option<PathBuf> {
+    match args.cache_dir {
+        Some(_) => None,
+        None => cache::default_legacy_dir(cache_dir),
+    }
+}
 
-                buf.clear();
+fn run_cache_command(
+    cache_dir: &Path,
+    legacy_dir: Option<&Path>,
+    action: &CacheCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (removed, freed) = match *action {
+        CacheCommand::List => {
+            let entries = cache::list_entries(cache_dir, legacy_dir)?;
+            let now = SystemTime::now();
+            for entry in &entries {
+                let age = now.duration_since(entry.modified).unwrap_or_default();
+                println!(
+                    "{:>12}  {:>16} ago  {}",
+                    HumanBytes(entry.size).to_string(),
+                    HumanDuration(age).to_string(),
+                    entry.path.display()
+                );
             }
-
-            if should_parse {
-                Some(HealthRecord {
-                    record_type,
-                    value,
-                    unit,
-                    start_date,
-                    end_date,
-                    metadata,
-                })
-            } else {
-                None
+            let total: u64 = entries.iter().map(|entry| entry.size).sum();
+            let mut dirs = cache_dir.display().to_string();
+            if let Some(legacy_dir) = legacy_dir {
+                dirs = format!("{} and {}", dirs, legacy_dir.display());
             }
-        })
-        .filter_map(|r| r)
-        .collect()
+            println!(
+                "{} entries, {} in {}",
+                entries.len(),
+                HumanBytes(total),
+                dirs
+            );
+            return Ok(());
+        }
+        CacheCommand::Clear => cache::clear(cache_dir, legacy_dir)?,
+        CacheCommand::Prune { max_size, max_age } => {
+            if max_size.is_none() && max_age.is_none() {
+                return Err("cache prune needs --max-size and/or --max-age".into());
+            }
+            cache::prune(cache_dir, legacy_dir, max_size, max_age)?
+        }
+    };
+    println!("Removed {} entries, freed {}", removed, HumanBytes(freed));
+    Ok(())
 }
 
-fn write_csv(records: &[HealthRecord], path: &str) -> Result<(), Box<dyn Error>> {
-    let mut wtr = Writer::from_path(path)?;
-
-    wtr.write_record(&[
-        "record_type",
-        "value",
-        "unit",
-        "start_date",
-        "end_date",
-        "metadata",
-    ])?;
-
-    for rec in records {
-        let meta_str = serde_json::to_string(&rec.metadata).unwrap_or_default();
-
-        wtr.write_record(&[
-            rec.record_type.as_deref().unwrap_or(""),
-            rec.value.as_deref().unwrap_or(""),
-            rec.unit.as_deref().unwrap_or(""),
-            rec.start_date.as_deref().unwrap_or(""),
-            rec.end_date.as_deref().unwrap_or(""),
-            &meta_str,
-        ])?;
-    }
-
-    wtr.flush()?;
+fn run_generate(
+    config: &GeneratorConfig,
+    output: &Path,
+    progress: &Progress,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    let generated = generate::generate_export(config, output, progress)?;
+    progress.message(format!(
+        "Wrote {} records and {} workouts ({} with routes) from {} to {} to {} ({} of XML)",
+        generated.records,
+        generated.workouts,
+        generated.routes,
+        generated.first_day,
+        generated.last_day,
+        output.display(),
+        HumanBytes(generated.xml_bytes)
+    ));
+    progress.message(format!("Done in {:?}", start.elapsed()));
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let cache_dir = args.cache_dir.clone().unwrap_or_else(cache::get_cache_dir);
+
+    let progress = Progress::new(args.quiet, args.progress_json);
+    let type_filter = build_type_filter(&args)?;
+
+    match &args.command {
+        Some(Command::Cache { action }) => {
+            let legacy_dir = legacy_cache_dir(&args, &cache_dir);
+            return run_cache_command(&cache_dir, legacy_dir.as_deref(), action);
+        }
+        Some(Command::Types { group }) => return list_types(group.as_deref()),
+        Some(Command::Diff {
+            old,
+            new,
+            write_state,
+        }) => {
+            return run_diff(
+                old,
+                new,
+                write_state.as_deref(),
+                &args,
+                &type_filter,
+                &cache_dir,
+                &progress,
+            );
+        }
+        Some(Command::Generate {
+            output,
+            days,
+            size,
+            end,
+            seed,
+            no_routes,
+        }) => {
+            let mut config = GeneratorConfig {
+                days: *days,
+                target_size: *size,
+                routes: !no_routes,
+                seed: *seed,
+                ..GeneratorConfig::default()
+            };
+            if let Some(end) = end {
+                config.end = *end;
+            }
+            return run_generate(&config, output, &progress);
+        }
+        None => {}
+    }
+
     let start = Instant::now();
-    let zip_path = "./export.zip";
-
-    let allowed_types: HashSet<&str> = [
-        "HKQuantityTypeIdentifierHeartRate",
-        "HKCategoryTypeIdentifierHighHeartRateEvent",
-        "HKQuantityTypeIdentifierRestingHeartRate",
-        "HKQuantityTypeIdentifierPhysicalEffort",
-        "HKQuantityTypeIdentifierBasalEnergyBurned",
-        "HKQuantityTypeIdentifierActiveEnergyBurned",
-        "HKQuantityTypeIdentifierDistanceWalkingRunning",
-        "HKQuantityTypeIdentifierWalkingSpeed",
-        "HKQuantityTypeIdentifierAppleStandTime",
-        "HKQuantityTypeIdentifierAppleExerciseTime",
-        "HKQuantityTypeIdentifierWalkingStepLength",
-        "HKQuantityTypeIdentifierStepCount",
-        "HKQuantityTypeIdentifierFlightsClimbed",
-        "HKCategoryTypeIdentifierSleepAnalysis",
-        "HKQuantityTypeIdentifierBodyMass",
-        "HKCategoryTypeIdentifierToothbrushingEvent",
-        "HKQuantityTypeIdentifierSixMinuteWalkTestDistance",
-        "HKQuantityTypeIdentifierDietaryCaffeine",
-        "HKQuantityTypeIdentifierDietaryWater",
-    ]
-    .iter()
-    .copied()
-    .collect();
-
-    let t_read = Instant::now();
-    let xml = read_export_xml(std::path::Path::new(zip_path))?;
-    println!("Reading XML took {:.2?}", t_read.elapsed());
-
-    let t_parse = Instant::now();
-    let records = parse_records(&xml, &allowed_types);
-    println!("Parsing XML took {:.2?}", t_parse.elapsed());
-    println!("Found {} records", records.len());
-
-    let t_serialize = Instant::now();
+
+    let mut exports = Vec::with_capacity(args.input.len());
+    for path in &args.input {
+        exports.push(load_export(
+            path,
+            &args,
+            &type_filter,
+            &cache_dir,
+            &progress,
+        )?);
+    }
+    let parsed = if exports.len() == 1 {
+        exports.pop().expect("one export")
+    } else {
+        let merged = merge::merge_exports(exports);
+        progress.message(format!(
+            "Merged {} exports, dropping {} duplicate records",
+            args.input.len(),
+            merged.duplicates
+        ));
+        merged.export
+    };
+
+    let ParsedExport {
+        mut records,
+        state_of_mind,
+        vision_prescriptions,
+        unknown,
+        errors,
+        diagnostics,
+    } = parsed;
+
+    progress.message(format!("Found {} records", records.len()));
+    order::sort_records(&mut records, args.order, args.sort_memory, &progress)?;
+    progress.message(format!(
+        "Found {} state of mind entries and {} vision prescriptions",
+        state_of_mind.len(),
+        vision_prescriptions.len()
+    ));
+    if !errors.is_empty() {
+        progress.message(format!("Skipped {} malformed elements:", errors.len()));
+        for error in errors.iter().take(MAX_REPORTED_ERRORS) {
+            progress.message(format!(
+                "  line {} (byte {}) in <{}>: {}",
+                error.line, error.offset, error.element, error.message
+            ));
+        }
+    }
+    if diagnostics.skipped_count() > 0 {
+        let reasons: Vec<String> = diagnostics
+            .skipped
+            .iter()
+            .map(|(reason, summary)| format!("{} {}", summary.count, reason))
+            .collect();
+        progress.message(format!(
+            "Skipped {} of {} scanned elements: {}",
+            diagnostics.skipped_count(),
+            diagnostics.scanned,
+            reasons.join(", ")
+        ));
+    }
+    if !diagnostics.document.is_empty() {
+        let issues: Vec<String> = diagnostics
+            .document
+            .iter()
+            .map(|(issue, summary)| format!("{} {}", summary.count, issue))
+            .collect();
+        progress.message(format!("Problems with the document: {}", issues.join(", ")));
+        for sample in diagnostics
+            .document
+            .values()
+            .flat_map(|summary| &summary.samples)
+            .take(MAX_REPORTED_ERRORS)
+        {
+            progress.message(format!(
+                "  line {} (byte {}): {}",
+                sample.line, sample.offset, sample.detail
+            ));
+        }
+    }
+    if !unknown.counts.is_empty() {
+        progress.message("Skipped unrecognised elements:");
+        for (name, count) in &unknown.counts {
+            progress.message(format!("  {}: {}", name, count));
+        }
+    }
+
     let json_output = serde_json::to_string_pretty(&records)?;
     fs::write("./output.json", json_output)?;
-    println!("JSON Serialization took {:.2?}", t_serialize.elapsed());
+    if !state_of_mind.is_empty() {
+        let json_output = serde_json::to_string_pretty(&state_of_mind)?;
+        fs::write("./state_of_mind.json", json_output)?;
+    }
+    if !vision_prescriptions.is_empty() {
+        let json_output = serde_json::to_string_pretty(&vision_prescriptions)?;
+        fs::write("./vision_prescriptions.json", json_output)?;
+    }
+    if args.unknown_elements {
+        let json_output = serde_json::to_string_pretty(&unknown.elements)?;
+        fs::write("./unknown_elements.json", json_output)?;
+    }
+    let json_output = serde_json::to_string_pretty(&diagnostics)?;
+    fs::write("./diagnostics.json", json_output)?;
+    if args.summary {
+        let json_output = serde_json::to_string_pretty(&summary::summarize(&records))?;
+        fs::write("./summary.json", json_output)?;
+    }
+
+    if args.write_cda {
+        cda::write_cda(&records, Path::new("output_cda.xml"))?;
+    }
 
-    let t_csv = Instant::now();
     write_csv(&records, "output.csv")?;
-    println!("CSV Serialization took {:.2?}", t_csv.elapsed());
+    if args.csv_per_type {
+        write_csv_per_type(&records, Path::new("csv"))?;
+    }
 
     let duration = start.elapsed();
-    println!("Done in {:?}", duration);
+    progress.message(format!("Done in {:?}", duration));
 
     Ok(())
 }
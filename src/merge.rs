@@ -0,0 +1,100 @@
+use crate::diagnostics::Diagnostics;
+use crate::health_type::RecordType;
+use crate::raw_element::UnknownElements;
+use crate::{HealthRecord, ParsedExport};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::iter;
+
+pub struct MergedExport {
+    pub export: ParsedExport,
+    /// Records dropped because an earlier export already contained them.
+    pub duplicates: usize,
+}
+
+/// Two records are the same measurement if they agree on type, source,
+/// start/end and value, whichever export they came from.
+pub fn record_key(record: &HealthRecord) -> [Option<&str>; 5] {
+    [
+        record.record_type.as_ref().map(RecordType::identifier),
+        record.source_name.as_deref(),
+        record.start_date.as_deref(),
+        record.end_date.as_deref(),
+        record.value.as_deref(),
+    ]
+}
+
+/// Combines exports in the order given, dropping records an earlier export
+/// already contained so each one stays tagged with the earliest export it
+/// appeared in. Repeats within one export are kept, as they are when that
+/// export is read on its own.
+pub fn merge_exports(exports: Vec<ParsedExport>) -> MergedExport {
+    let mut merged = ParsedExport {
+        records: Vec::new(),
+        state_of_mind: Vec::new(),
+        vision_prescriptions: Vec::new(),
+        unknown: UnknownElements::default(),
+        errors: Vec::new(),
+        diagnostics: Diagnostics::default(),
+    };
+
+    // The export each item came from, by position in the merged lists.
+    let mut record_sources = Vec::new();
+    let mut mood_sources = Vec::new();
+    let mut prescription_sources = Vec::new();
+    for (index, export) in exports.into_iter().enumerate() {
+        record_sources.extend(iter::repeat_n(index, export.records.len()));
+        mood_sources.extend(iter::repeat_n(index, export.state_of_mind.len()));
+        prescription_sources.extend(iter::repeat_n(index, export.vision_prescriptions.len()));
+        merged.records.extend(export.records);
+        merged.state_of_mind.extend(export.state_of_mind);
+        merged
+            .vision_prescriptions
+            .extend(export.vision_prescriptions);
+        for (name, count) in export.unknown.counts {
+            *merged.unknown.counts.entry(name).or_insert(0) += count;
+        }
+        merged.unknown.elements.extend(export.unknown.elements);
+        merged.errors.extend(export.errors);
+        merged.diagnostics.merge(export.diagnostics);
+    }
+
+    let keep = first_copies(merged.records.iter().map(record_key), &record_sources);
+    let duplicates = retain_marked(&mut merged.records, keep);
+    // State of mind entries and prescriptions carry no natural key, and
+    // repeated exports reproduce them byte for byte, so compare their JSON.
+    let keep = first_copies(merged.state_of_mind.iter().map(to_json), &mood_sources);
+    retain_marked(&mut merged.state_of_mind, keep);
+    let keep = first_copies(
+        merged.vision_prescriptions.iter().map(to_json),
+        &prescription_sources,
+    );
+    retain_marked(&mut merged.vision_prescriptions, keep);
+
+    MergedExport {
+        export: merged,
+        duplicates,
+    }
+}
+
+/// Whether each of `keys` is the first copy of its key, or a repeat within
+/// the export that first had it; `sources` holds the export of each key.
+fn first_copies<K: Hash + Eq>(keys: impl Iterator<Item = K>, sources: &[usize]) -> Vec<bool> {
+    let mut first_seen = HashMap::with_capacity(sources.len());
+    keys.zip(sources)
+        .map(|(key, &source)| *first_seen.entry(key).or_insert(source) == source)
+        .collect()
+}
+
+/// Keeps the items marked in `keep`, returning how many were dropped.
+fn retain_marked<T>(items: &mut Vec<T>, keep: Vec<bool>) -> usize {
+    let before = items.len();
+    let mut keep = keep.into_iter();
+    items.retain(|_| keep.next().unwrap_or(true));
+    before - items.len()
+}
+
+fn to_json<T: Serialize>(item: &T) -> String {
+    serde_json::to_string(item).unwrap_or_default()
+}
@@ -0,0 +1,234 @@
+//! Output ordering. Records come out of the parser in document order, and
+//! merged exports one after the other in the order given; the other orders
+//! are stable sorts on top of that, so records that tie keep document order.
+
+use crate::HealthRecord;
+use crate::health_type::RecordType;
+use crate::progress::{Progress, Unit};
+use chrono::DateTime;
+use smallstr::SmallString;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::mem;
+use std::str::FromStr;
+
+/// Apple's date format, e.g. `2026-10-01 08:30:00 +0000`.
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S %z";
+
+/// Whether the type is missing, the type when sorting by type, and the start
+/// time in UTC seconds; records without a type or a readable start date sort
+/// after the others.
+type SortKey = (bool, Option<RecordType>, i64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecordOrder {
+    /// As the records appear in the export.
+    #[default]
+    Document,
+    /// By start date, across all types.
+    Start,
+    /// By type identifier, then by start date within each type.
+    Type,
+}
+
+impl FromStr for RecordOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "document" => Ok(RecordOrder::Document),
+            "start" => Ok(RecordOrder::Start),
+            "type" => Ok(RecordOrder::Type),
+            other => Err(format!(
+                "unknown order '{}' (expected document, start or type)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for RecordOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RecordOrder::Document => "document",
+            RecordOrder::Start => "start",
+            RecordOrder::Type => "type",
+        })
+    }
+}
+
+/// Sorts `records` into `order`. At most `memory_limit` bytes of records
+/// are sorted in memory as they are, with a key each. Above that, runs of
+/// at most that size are split off the end of `records`, which shrinks as
+/// they go, sorted and spilled to temporary files, then merged back. The
+/// records themselves are in memory before and after either way; spilling
+/// keeps what the sort needs on top of them to about one run, instead of a
+/// key for every record.
+pub fn sort_records(
+    records: &mut Vec<HealthRecord>,
+    order: RecordOrder,
+    memory_limit: u64,
+    progress: &Progress,
+) -> Result<(), Box<dyn Error>> {
+    if order == RecordOrder::Document || records.len() < 2 {
+        return Ok(());
+    }
+
+    let key = |record: &HealthRecord| sort_key(record, order);
+    let size: u64 = records.iter().map(|r| record_size(r) as u64).sum();
+    if size <= memory_limit {
+        records.sort_by_cached_key(key);
+        return Ok(());
+    }
+
+    let total = records.len();
+    let run_len = ((total as u64 * memory_limit / size) as usize).max(1);
+    let task = progress.task("sorting", Some(total as u64 * 2), Unit::Records);
+
+    // Runs come off the end so `records` can give its memory back as it
+    // shrinks; they are put back in document order for the merge.
+    let mut runs = Vec::with_capacity(total.div_ceil(run_len));
+    while !records.is_empty() {
+        let at = records.len().saturating_sub(run_len);
+        let mut run: Vec<(SortKey, HealthRecord)> = records
+            .drain(at..)
+            .map(|record| (key(&record), record))
+            .collect();
+        records.shrink_to_fit();
+        run.sort_by(|(a, _), (b, _)| a.cmp(b));
+        task.inc(run.len() as u64);
+        runs.push(spill(run)?);
+    }
+    runs.reverse();
+    records.reserve_exact(total);
+
+    // Ties go to the earliest run, which keeps the merge stable.
+    let mut heap = BinaryHeap::with_capacity(runs.len());
+    for (index, run) in runs.iter_mut().enumerate() {
+        if let Some((key, record)) = run.next()? {
+            heap.push(Reverse(Head { key, index, record }));
+        }
+    }
+    while let Some(Reverse(head)) = heap.pop() {
+        if let Some((key, record)) = runs[head.index].next()? {
+            heap.push(Reverse(Head {
+                key,
+                index: head.index,
+                record,
+            }));
+        }
+        records.push(head.record);
+        task.inc(1);
+    }
+    task.finish();
+    Ok(())
+}
+
+fn sort_key(record: &HealthRecord, order: RecordOrder) -> SortKey {
+    let record_type = match order {
+        RecordOrder::Type => record.record_type.clone(),
+        RecordOrder::Document | RecordOrder::Start => None,
+    };
+    let start = record
+        .start_date
+        .as_deref()
+        .and_then(|date| DateTime::parse_from_str(date, DATE_FORMAT).ok())
+        .map_or(i64::MAX, |date| date.timestamp());
+    (
+        order == RecordOrder::Type && record.record_type.is_none(),
+        record_type,
+        start,
+    )
+}
+
+/// Heap bytes of a `SmallString` too long for its inline buffer.
+macro_rules! spilled {
+    ($s:expr) => {
+        if $s.spilled() { $s.capacity() } else { 0 }
+    };
+}
+
+/// Rough heap and inline size of a record, for the memory budget.
+fn record_size(record: &HealthRecord) -> usize {
+    let metadata: usize = record
+        .metadata
+        .iter()
+        .map(|(key, value)| spilled!(key) + spilled!(value))
+        .sum();
+    mem::size_of::<HealthRecord>()
+        + match &record.record_type {
+            Some(RecordType::Other(identifier)) => identifier.capacity(),
+            _ => 0,
+        }
+        + record.unit.as_ref().map_or(0, |s| spilled!(s))
+        + record.value.as_ref().map_or(0, |s| spilled!(s))
+        + record.start_date.as_ref().map_or(0, |s| spilled!(s))
+        + record.end_date.as_ref().map_or(0, |s| spilled!(s))
+        + record.source_name.as_ref().map_or(0, |s| spilled!(s))
+        + record.export_id.as_ref().map_or(0, |s| spilled!(s))
+        + record.metadata.capacity()
+            * mem::size_of::<(SmallString<[u8; 16]>, SmallString<[u8; 32]>)>()
+        + metadata
+}
+
+/// One sorted run, written to an anonymous temporary file.
+struct Run {
+    reader: BufReader<File>,
+    remaining: usize,
+}
+
+impl Run {
+    fn next(&mut self) -> Result<Option<(SortKey, HealthRecord)>, Box<dyn Error>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        Ok(Some(bincode::deserialize_from(&mut self.reader)?))
+    }
+}
+
+fn spill(run: Vec<(SortKey, HealthRecord)>) -> Result<Run, Box<dyn Error>> {
+    let mut writer = BufWriter::new(tempfile::tempfile()?);
+    let remaining = run.len();
+    for entry in &run {
+        bincode::serialize_into(&mut writer, entry)?;
+    }
+    writer.flush()?;
+    let mut file = writer.into_inner()?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(Run {
+        reader: BufReader::new(file),
+        remaining,
+    })
+}
+
+/// The next record of one run, ordered by key and then by run.
+struct Head {
+    key: SortKey,
+    index: usize,
+    record: HealthRecord,
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        (&self.key, self.index) == (&other.key, other.index)
+    }
+}
+
+impl Eq for Head {}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (&self.key, self.index).cmp(&(&other.key, other.index))
+    }
+}
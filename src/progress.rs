@@ -0,0 +1,157 @@
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use serde_json::json;
+use std::fmt::Display;
+use std::io::{self, Read};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Minimum gap between two JSON progress events for the same stage.
+const JSON_EVENT_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy)]
+pub enum Unit {
+    Bytes,
+    Records,
+}
+
+/// Routes human-readable output and progress for a run: indicatif bars and
+/// messages unless `quiet`, plus JSON lines on stderr when `json` is set.
+pub struct Progress {
+    quiet: bool,
+    json: bool,
+}
+
+pub struct Task {
+    stage: &'static str,
+    bar: Option<ProgressBar>,
+    json: bool,
+    total: Option<u64>,
+    position: AtomicU64,
+    started: Instant,
+    last_event: Mutex<Instant>,
+}
+
+impl Progress {
+    pub fn new(quiet: bool, json: bool) -> Self {
+        Progress { quiet, json }
+    }
+
+    /// Prints a status line to stdout, mirrored as a `message` event.
+    pub fn message(&self, message: impl Display) {
+        let message = message.to_string();
+        if self.json {
+            emit(json!({ "event": "message", "message": message }));
+        }
+        if !self.quiet {
+            println!("{}", message);
+        }
+    }
+
+    pub fn task(&self, stage: &'static str, total: Option<u64>, unit: Unit) -> Task {
+        // JSON events and bars would both write to stderr, so only draw bars
+        // for interactive runs.
+        let bar = (!self.quiet && !self.json).then(|| {
+            let bar = ProgressBar::with_draw_target(total, ProgressDrawTarget::stderr());
+            bar.set_style(style(total.is_some(), unit));
+            bar.set_prefix(stage);
+            bar.enable_steady_tick(Duration::from_millis(120));
+            bar
+        });
+
+        if self.json {
+            let unit = match unit {
+                Unit::Bytes => "bytes",
+                Unit::Records => "records",
+            };
+            emit(json!({ "event": "start", "stage": stage, "total": total, "unit": unit }));
+        }
+
+        let started = Instant::now();
+        Task {
+            stage,
+            bar,
+            json: self.json,
+            total,
+            position: AtomicU64::new(0),
+            started,
+            last_event: Mutex::new(started),
+        }
+    }
+}
+
+impl Task {
+    pub fn inc(&self, delta: u64) {
+        let position = self.position.fetch_add(delta, Ordering::Relaxed) + delta;
+        if let Some(bar) = &self.bar {
+            bar.inc(delta);
+        }
+        if !self.json {
+            return;
+        }
+
+        // Skip the event rather than wait when another worker holds the lock.
+        let Ok(mut last_event) = self.last_event.try_lock() else {
+            return;
+        };
+        if last_event.elapsed() >= JSON_EVENT_INTERVAL {
+            *last_event = Instant::now();
+            emit(json!({
+                "event": "progress",
+                "stage": self.stage,
+                "position": position,
+                "total": self.total,
+            }));
+        }
+    }
+
+    pub fn finish(self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+        if self.json {
+            emit(json!({
+                "event": "finish",
+                "stage": self.stage,
+                "position": self.position.load(Ordering::Relaxed),
+                "elapsed_ms": self.started.elapsed().as_millis() as u64,
+            }));
+        }
+    }
+
+    /// Wraps a reader so every byte read advances this task.
+    pub fn wrap_read<R: Read>(&self, inner: R) -> TaskReader<'_, R> {
+        TaskReader { task: self, inner }
+    }
+}
+
+pub struct TaskReader<'a, R> {
+    task: &'a Task,
+    inner: R,
+}
+
+impl<R: Read> Read for TaskReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.task.inc(read as u64);
+        Ok(read)
+    }
+}
+
+fn style(has_total: bool, unit: Unit) -> ProgressStyle {
+    let template = match (has_total, unit) {
+        (true, Unit::Bytes) => {
+            "{spinner} {prefix:>10} [{bar:40}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})"
+        }
+        (false, Unit::Bytes) => "{spinner} {prefix:>10} {bytes} ({bytes_per_sec})",
+        (true, Unit::Records) => "{spinner} {prefix:>10} [{bar:40}] {human_pos}/{human_len}",
+        (false, Unit::Records) => "{spinner} {prefix:>10} {human_pos} records ({per_sec})",
+    };
+    ProgressStyle::with_template(template)
+        .expect("progress templates are valid")
+        .progress_chars("=> ")
+}
+
+fn emit(event: serde_json::Value) {
+    eprintln!("{}", event);
+}
@@ -0,0 +1,589 @@
+//! A small expression language for selecting records, e.g.
+//!
+//! ```text
+//! type = HeartRate and value > 150 and source ~ "Watch" and hour(start) between 22 and 6
+//! ```
+//!
+//! Fields are `type`, `value`, `unit`, `source`, `start`, `end`, `duration`
+//! (seconds from start to end), `export` and `metadata.<key>`; `hour(...)`
+//! and `weekday(...)` (1 for Monday to 7, or a day name) apply to `start`
+//! and `end`. Comparisons are `=`, `!=`, `<`, `<=`, `>`, `>=`, `~` (contains,
+//! ignoring case) and `between low and high`, which wraps around when `low`
+//! is above `high`. They combine with `and`, `or`, `not` and parentheses.
+//!
+//! - `type` matches a short name such as `HeartRate`, a full identifier, a
+//!   glob such as `*Dietary*` or a group such as `@heart`.
+//! - `value` compares as a number when both sides are numbers.
+//! - Dates compare as written in the export, on as many characters as the
+//!   literal has, so `start = 2026-10` is any time in October 2026.
+//! - A comparison on a field the record does not have is false.
+
+use crate::HealthRecord;
+use crate::health_type::{RecordType, TypeGroup};
+use crate::type_filter::glob_match;
+use chrono::{DateTime, Datelike, NaiveDate};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// Apple's date format, e.g. `2026-10-01 08:30:00 +0000`.
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S %z";
+
+const WEEKDAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+/// A parsed query, ready to be matched against records.
+#[derive(Debug, Clone)]
+pub struct Query {
+    source: String,
+    expr: Expr,
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser {
+            input,
+            tokens: &tokens,
+            next: 0,
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(parser.error_at(token.start, "expected 'and', 'or' or the end"));
+        }
+        Ok(Query {
+            source: input.trim().to_string(),
+            expr,
+        })
+    }
+
+    pub fn matches(&self, record: &HealthRecord) -> bool {
+        self.expr.matches(record)
+    }
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Query::parse(s)
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Operand, Op, Literal),
+    Between(Operand, Literal, Literal),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Field {
+    Type,
+    Value,
+    Unit,
+    Source,
+    Start,
+    End,
+    Duration,
+    Export,
+    Metadata(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Operand {
+    Field(Field),
+    Hour(Field),
+    Weekday(Field),
+}
+
+#[derive(Debug, Clone)]
+struct Literal {
+    text: String,
+    number: Option<f64>,
+}
+
+/// What an operand evaluates to for one record.
+enum Value<'a> {
+    Text(&'a str),
+    /// A date string, compared on the literal's length.
+    Date(&'a str),
+    Number(f64),
+    Missing,
+}
+
+impl Expr {
+    fn matches(&self, record: &HealthRecord) -> bool {
+        match self {
+            Expr::And(left, right) => left.matches(record) && right.matches(record),
+            Expr::Or(left, right) => left.matches(record) || right.matches(record),
+            Expr::Not(inner) => !inner.matches(record),
+            Expr::Compare(Operand::Field(Field::Type), op, literal) => {
+                let Some(record_type) = &record.record_type else {
+                    return false;
+                };
+                match op {
+                    Op::Eq => type_matches(record_type, &literal.text),
+                    Op::Ne => !type_matches(record_type, &literal.text),
+                    _ => contains(record_type.identifier(), &literal.text),
+                }
+            }
+            Expr::Compare(operand, Op::Contains, literal) => match operand.value(record) {
+                Value::Text(text) | Value::Date(text) => contains(text, &literal.text),
+                Value::Number(_) | Value::Missing => false,
+            },
+            Expr::Compare(operand, op, literal) => compare(operand.value(record), literal)
+                .is_some_and(|ordering| match op {
+                    Op::Eq => ordering == Ordering::Equal,
+                    Op::Ne => ordering != Ordering::Equal,
+                    Op::Lt => ordering == Ordering::Less,
+                    Op::Le => ordering != Ordering::Greater,
+                    Op::Gt => ordering == Ordering::Greater,
+                    Op::Ge => ordering != Ordering::Less,
+                    Op::Contains => unreachable!("contains is matched above"),
+                }),
+            Expr::Between(operand, low, high) => {
+                let (Some(from_low), Some(from_high)) = (
+                    compare(operand.value(record), low),
+                    compare(operand.value(record), high),
+                ) else {
+                    return false;
+                };
+                let above_low = from_low != Ordering::Less;
+                let below_high = from_high != Ordering::Greater;
+                if compare_literals(low, high) == Ordering::Greater {
+                    above_low || below_high
+                } else {
+                    above_low && below_high
+                }
+            }
+        }
+    }
+}
+
+impl Operand {
+    /// Whether the operand is a number, so literals must be numbers too.
+    fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            Operand::Hour(_) | Operand::Weekday(_) | Operand::Field(Field::Duration)
+        )
+    }
+
+    fn value<'a>(&self, record: &'a HealthRecord) -> Value<'a> {
+        match self {
+            Operand::Field(field) => match field {
+                Field::Type => record
+                    .record_type
+                    .as_ref()
+                    .map_or(Value::Missing, |t| Value::Text(t.identifier())),
+                Field::Value => text(record.value.as_deref()),
+                Field::Unit => text(record.unit.as_deref()),
+                Field::Source => text(record.source_name.as_deref()),
+                Field::Export => text(record.export_id.as_deref()),
+                Field::Metadata(key) => text(record.metadata.get(key.as_str()).map(|v| v.as_str())),
+                Field::Start => date(record.start_date.as_deref()),
+                Field::End => date(record.end_date.as_deref()),
+                Field::Duration => {
+                    let start = record.start_date.as_deref().and_then(timestamp);
+                    let end = record.end_date.as_deref().and_then(timestamp);
+                    match (start, end) {
+                        (Some(start), Some(end)) => Value::Number((end - start) as f64),
+                        _ => Value::Missing,
+                    }
+                }
+            },
+            Operand::Hour(field) => date_of(record, field)
+                .and_then(|date| date.get(11..13)?.parse::<f64>().ok())
+                .map_or(Value::Missing, Value::Number),
+            Operand::Weekday(field) => date_of(record, field)
+                .and_then(|date| NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok())
+                .map_or(Value::Missing, |day| {
+                    Value::Number(day.weekday().number_from_monday() as f64)
+                }),
+        }
+    }
+}
+
+fn text(value: Option<&str>) -> Value<'_> {
+    value.map_or(Value::Missing, Value::Text)
+}
+
+fn date(value: Option<&str>) -> Value<'_> {
+    value.map_or(Value::Missing, Value::Date)
+}
+
+fn date_of<'a>(record: &'a HealthRecord, field: &Field) -> Option<&'a str> {
+    match field {
+        Field::Start => record.start_date.as_deref(),
+        _ => record.end_date.as_deref(),
+    }
+}
+
+fn timestamp(date: &str) -> Option<i64> {
+    DateTime::parse_from_str(date, DATE_FORMAT)
+        .ok()
+        .map(|date| date.timestamp())
+}
+
+/// How the operand's value compares to the literal; `None` when the record
+/// has no value to compare.
+fn compare(value: Value, literal: &Literal) -> Option<Ordering> {
+    match value {
+        Value::Missing => None,
+        Value::Number(number) => number.partial_cmp(&literal.number?),
+        Value::Date(date) => {
+            let end = literal.text.len().min(date.len());
+            Some(date.as_bytes()[..end].cmp(literal.text.as_bytes()))
+        }
+        Value::Text(text) => match (text.parse::<f64>().ok(), literal.number) {
+            (Some(number), Some(other)) => number.partial_cmp(&other),
+            _ if text.eq_ignore_ascii_case(&literal.text) => Some(Ordering::Equal),
+            _ => Some(text.cmp(&literal.text)),
+        },
+    }
+}
+
+fn compare_literals(low: &Literal, high: &Literal) -> Ordering {
+    match (low.number, high.number) {
+        (Some(low), Some(high)) => low.partial_cmp(&high).unwrap_or(Ordering::Equal),
+        _ => low.text.cmp(&high.text),
+    }
+}
+
+fn contains(text: &str, needle: &str) -> bool {
+    text.to_lowercase().contains(&needle.to_lowercase())
+}
+
+fn type_matches(record_type: &RecordType, pattern: &str) -> bool {
+    if let Some(name) = pattern.strip_prefix('@') {
+        let group = TypeGroup::from_name(name);
+        return record_type
+            .health_type()
+            .is_some_and(|health_type| Some(health_type.group) == group);
+    }
+    if pattern.contains(['*', '?']) {
+        return glob_match(pattern.as_bytes(), record_type.identifier().as_bytes())
+            || glob_match(pattern.as_bytes(), record_type.short_name().as_bytes());
+    }
+    record_type.identifier().eq_ignore_ascii_case(pattern)
+        || record_type.short_name().eq_ignore_ascii_case(pattern)
+}
+
+#[derive(Debug, PartialEq)]
+enum TokenKind {
+    Word(String),
+    Quoted(String),
+    Symbol(&'static str),
+}
+
+#[derive(Debug)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+}
+
+const SYMBOLS: &[&str] = &["!=", "<=", ">=", "=", "<", ">", "~", "(", ")"];
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '\\')) => text.extend(chars.next().map(|(_, c)| c)),
+                    Some((_, quote)) if quote == c => break,
+                    Some((_, other)) => text.push(other),
+                    None => {
+                        return Err(format!(
+                            "unterminated string at column {}",
+                            column(input, start)
+                        ));
+                    }
+                }
+            }
+            tokens.push(Token {
+                kind: TokenKind::Quoted(text),
+                start,
+            });
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| input[start..].starts_with(**s)) {
+            for _ in 0..symbol.len() {
+                chars.next();
+            }
+            tokens.push(Token {
+                kind: TokenKind::Symbol(symbol),
+                start,
+            });
+        } else {
+            let mut word = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if c.is_whitespace() || c == '"' || c == '\'' || "!=<>~()".contains(c) {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            if word.is_empty() {
+                return Err(format!(
+                    "unexpected '{}' at column {}",
+                    c,
+                    column(input, start)
+                ));
+            }
+            tokens.push(Token {
+                kind: TokenKind::Word(word),
+                start,
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+/// 1-based column of byte offset `offset`, for error messages.
+fn column(input: &str, offset: usize) -> usize {
+    input[..offset].chars().count() + 1
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: &'a [Token],
+    next: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn bump(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.next);
+        self.next += 1;
+        token
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(
+            self.peek(),
+            Some(Token { kind: TokenKind::Word(word), .. }) if word.eq_ignore_ascii_case(keyword)
+        );
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn symbol(&mut self, symbol: &str) -> bool {
+        let found =
+            matches!(self.peek(), Some(Token { kind: TokenKind::Symbol(s), .. }) if *s == symbol);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    /// Where the next token starts, or the end of the input.
+    fn position(&self) -> usize {
+        self.peek().map_or(self.input.len(), |token| token.start)
+    }
+
+    fn error_at(&self, offset: usize, message: &str) -> String {
+        format!("{} at column {}", message, column(self.input, offset))
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        if self.symbol("(") {
+            let expr = self.or()?;
+            if !self.symbol(")") {
+                return Err(self.error_at(self.position(), "expected ')'"));
+            }
+            return Ok(expr);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let start = self.position();
+        let operand = self.operand()?;
+        let op_start = self.position();
+        if self.keyword("between") {
+            let low = self.literal(&operand)?;
+            if !self.keyword("and") {
+                return Err(self.error_at(self.position(), "expected 'and' after the low bound"));
+            }
+            let high = self.literal(&operand)?;
+            return Ok(Expr::Between(operand, low, high));
+        }
+        let op = match self.bump().map(|token| &token.kind) {
+            Some(TokenKind::Symbol("=")) => Op::Eq,
+            Some(TokenKind::Symbol("!=")) => Op::Ne,
+            Some(TokenKind::Symbol("<")) => Op::Lt,
+            Some(TokenKind::Symbol("<=")) => Op::Le,
+            Some(TokenKind::Symbol(">")) => Op::Gt,
+            Some(TokenKind::Symbol(">=")) => Op::Ge,
+            Some(TokenKind::Symbol("~")) => Op::Contains,
+            _ => {
+                return Err(self.error_at(
+                    op_start,
+                    "expected a comparison (=, !=, <, <=, >, >=, ~ or between)",
+                ));
+            }
+        };
+        if operand == Operand::Field(Field::Type) && !matches!(op, Op::Eq | Op::Ne | Op::Contains) {
+            return Err(self.error_at(start, "type only supports =, != and ~"));
+        }
+        if operand.is_numeric() && op == Op::Contains {
+            return Err(self.error_at(op_start, "~ needs a text field"));
+        }
+        let literal = self.literal(&operand)?;
+        Ok(Expr::Compare(operand, op, literal))
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        let start = self.position();
+        let Some(Token {
+            kind: TokenKind::Word(word),
+            ..
+        }) = self.bump()
+        else {
+            return Err(self.error_at(start, "expected a field"));
+        };
+        let original = word.clone();
+        let word = word.to_ascii_lowercase();
+        if self.symbol("(") {
+            let argument_start = self.position();
+            let field = match self.bump().map(|token| &token.kind) {
+                Some(TokenKind::Word(field)) if field.eq_ignore_ascii_case("start") => Field::Start,
+                Some(TokenKind::Word(field)) if field.eq_ignore_ascii_case("end") => Field::End,
+                _ => return Err(self.error_at(argument_start, "expected start or end")),
+            };
+            if !self.symbol(")") {
+                return Err(self.error_at(self.position(), "expected ')'"));
+            }
+            return match word.as_str() {
+                "hour" => Ok(Operand::Hour(field)),
+                "weekday" => Ok(Operand::Weekday(field)),
+                _ => Err(self.error_at(
+                    start,
+                    &format!("unknown function '{}' (expected hour or weekday)", word),
+                )),
+            };
+        }
+        let field = match word.as_str() {
+            "type" => Field::Type,
+            "value" => Field::Value,
+            "unit" => Field::Unit,
+            "source" => Field::Source,
+            "start" => Field::Start,
+            "end" => Field::End,
+            "duration" => Field::Duration,
+            "export" => Field::Export,
+            // Keys keep their case; only the prefix ignores it.
+            _ if word.starts_with("metadata.") => {
+                Field::Metadata(original["metadata.".len()..].to_string())
+            }
+            _ => {
+                return Err(self.error_at(
+                    start,
+                    &format!(
+                        "unknown field '{}' (expected type, value, unit, source, start, end, duration, export or metadata.<key>)",
+                        original
+                    ),
+                ));
+            }
+        };
+        Ok(Operand::Field(field))
+    }
+
+    fn literal(&mut self, operand: &Operand) -> Result<Literal, String> {
+        let start = self.position();
+        let text = match self.bump().map(|token| &token.kind) {
+            Some(TokenKind::Word(word))
+                if !["and", "or", "not", "between"]
+                    .iter()
+                    .any(|keyword| word.eq_ignore_ascii_case(keyword)) =>
+            {
+                word.clone()
+            }
+            Some(TokenKind::Quoted(text)) => text.clone(),
+            _ => return Err(self.error_at(start, "expected a value")),
+        };
+
+        let number = match operand {
+            Operand::Weekday(_) => text.parse::<f64>().ok().or_else(|| {
+                let name = text.to_ascii_lowercase();
+                (name.len() >= 3)
+                    .then(|| WEEKDAYS.iter().position(|day| day.starts_with(&name)))
+                    .flatten()
+                    .map(|index| (index + 1) as f64)
+            }),
+            _ => text.parse::<f64>().ok(),
+        };
+        if operand.is_numeric() && number.is_none() {
+            return Err(self.error_at(start, &format!("expected a number, not '{}'", text)));
+        }
+        if let (Operand::Field(Field::Type), Some(name)) = (operand, text.strip_prefix('@'))
+            && TypeGroup::from_name(name).is_none()
+        {
+            let names: Vec<String> = TypeGroup::ALL.iter().map(|g| g.to_string()).collect();
+            return Err(self.error_at(
+                start,
+                &format!(
+                    "unknown type group '{}' (expected one of {})",
+                    name,
+                    names.join(", ")
+                ),
+            ));
+        }
+        Ok(Literal { text, number })
+    }
+}
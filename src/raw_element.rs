@@ -0,0 +1,221 @@
+use crate::error::{ElementError, ErrorKind};
+use crate::{chunker, recovery};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Top-level elements with a dedicated parser (correlations for the records
+/// nested in them); everything else is captured as a `RawElement`.
+pub const KNOWN_ELEMENTS: &[&str] = &["Record", "Correlation", "StateOfMind", "VisionPrescription"];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawElement {
+    name: String,
+    attributes: BTreeMap<String, String>,
+    children: Vec<RawElement>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UnknownElements {
+    pub counts: BTreeMap<String, usize>,
+    pub elements: Vec<RawElement>,
+}
+
+impl RawElement {
+    fn from_start(e: &BytesStart) -> Self {
+        let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+        let attributes = recovery::attributes(e)
+            .flatten()
+            .map(|attr| {
+                (
+                    String::from_utf8_lossy(attr.key.as_ref()).into_owned(),
+                    String::from_utf8_lossy(attr.value.as_ref()).into_owned(),
+                )
+            })
+            .collect();
+
+        RawElement {
+            name,
+            attributes,
+            children: Vec::new(),
+        }
+    }
+}
+
+/// Finds the children of the document root, which starts at byte `from` of
+/// `xml`, and tallies every element that is not in `KNOWN_ELEMENTS`. The
+/// element trees are only materialised when `keep_elements` is set, since
+/// workouts with routes can be large. A `truncated` document is expected
+/// to leave the root open.
+///
+/// The children are found by a parallel scan and each unknown one is read on
+/// its own; only when the scan finds tags that do not nest is the document
+/// walked from the start to locate the problem. Malformed XML ends the
+/// tally; what was found before it is returned along with the error, located
+/// in the top-level element it was found in.
+pub fn collect_unknown_elements(
+    xml: &str,
+    from: usize,
+    keep_elements: bool,
+    truncated: bool,
+) -> (UnknownElements, Option<ElementError>) {
+    let parts = rayon::current_num_threads() * 8;
+    let children = chunker::par_children(xml, from, parts, |name| !KNOWN_ELEMENTS.contains(&name));
+    let depth = if truncated { 0 } else { -1 };
+    if !children.balanced || children.depth != depth {
+        return walk_children(xml, from, keep_elements);
+    }
+
+    let read: Vec<_> = children
+        .elements
+        .par_iter()
+        .map(|&(offset, name)| read_element(xml, offset, name, keep_elements))
+        .collect();
+
+    let mut unknown = UnknownElements::default();
+    for (&(_, name), element) in children.elements.iter().zip(read) {
+        match element {
+            Ok(element) => {
+                *unknown.counts.entry(name.to_string()).or_insert(0) += 1;
+                unknown.elements.extend(element);
+            }
+            Err(error) => return (unknown, Some(error)),
+        }
+    }
+    (unknown, None)
+}
+
+/// Reads the element starting at byte `offset` of `xml`, checking that it is
+/// well-formed, and returns its tree if `keep` is set.
+fn read_element(
+    xml: &str,
+    offset: usize,
+    name: &str,
+    keep: bool,
+) -> Result<Option<RawElement>, ElementError> {
+    let mut reader = Reader::from_str(&xml[offset..]);
+    reader.config_mut().trim_text(true);
+
+    let mut depth = 0usize;
+    let mut stack: Vec<RawElement> = Vec::new();
+    let mut elements = Vec::new();
+    loop {
+        let event = reader.read_event().map_err(|e| {
+            let position = offset + reader.error_position() as usize;
+            ElementError::new(name, ErrorKind::MalformedXml, position, e.to_string())
+        })?;
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                if keep {
+                    stack.push(RawElement::from_start(e));
+                }
+                if matches!(event, Event::Empty(_)) {
+                    close_element(&mut stack, &mut elements);
+                } else {
+                    depth += 1;
+                }
+            }
+            Event::End(_) => {
+                depth = depth.saturating_sub(1);
+                close_element(&mut stack, &mut elements);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        if depth == 0 {
+            break;
+        }
+    }
+    Ok(elements.pop())
+}
+
+/// The sequential counterpart of `collect_unknown_elements`, for documents
+/// whose tags do not nest: quick-xml stops where they go wrong.
+fn walk_children(
+    xml: &str,
+    from: usize,
+    keep_elements: bool,
+) -> (UnknownElements, Option<ElementError>) {
+    let mut reader = Reader::from_str(&xml[from..]);
+    reader.config_mut().trim_text(true);
+
+    let mut unknown = UnknownElements::default();
+    let mut depth = 0usize;
+    // Where the top-level element being read starts, to name it in errors.
+    let mut top_level = from;
+    // Open elements of the unknown element currently being captured.
+    let mut stack: Vec<RawElement> = Vec::new();
+
+    loop {
+        let position = from + reader.buffer_position() as usize;
+        let event = match reader.read_event() {
+            Ok(event) => event,
+            Err(e) => {
+                let element = tag_name(&xml[top_level..]);
+                let offset = from + reader.error_position() as usize;
+                let error =
+                    ElementError::new(element, ErrorKind::MalformedXml, offset, e.to_string());
+                return (unknown, Some(error));
+            }
+        };
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let self_closing = matches!(event, Event::Empty(_));
+
+                if depth <= 1 {
+                    top_level = position;
+                }
+                if depth == 1 {
+                    let name = e.name();
+                    let is_known = KNOWN_ELEMENTS
+                        .iter()
+                        .any(|known| known.as_bytes() == name.as_ref());
+                    if !is_known {
+                        let name = String::from_utf8_lossy(name.as_ref()).into_owned();
+                        *unknown.counts.entry(name).or_insert(0) += 1;
+                        if keep_elements {
+                            stack.push(RawElement::from_start(e));
+                        }
+                    }
+                } else if depth > 1 && !stack.is_empty() {
+                    stack.push(RawElement::from_start(e));
+                }
+
+                if self_closing {
+                    close_element(&mut stack, &mut unknown.elements);
+                } else {
+                    depth += 1;
+                }
+            }
+            Event::End(_) => {
+                depth = depth.saturating_sub(1);
+                close_element(&mut stack, &mut unknown.elements);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    (unknown, None)
+}
+
+/// The name of the first tag in `xml`, which may start with whitespace.
+fn tag_name(xml: &str) -> &str {
+    let xml = xml.trim_start();
+    let name = xml.strip_prefix('<').unwrap_or(xml);
+    let end = name
+        .find(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/')
+        .unwrap_or(name.len());
+    &name[..end]
+}
+
+fn close_element(stack: &mut Vec<RawElement>, elements: &mut Vec<RawElement>) {
+    if let Some(element) = stack.pop() {
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(element),
+            None => elements.push(element),
+        }
+    }
+}
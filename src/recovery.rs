@@ -0,0 +1,72 @@
+//! Workarounds for the ways real exports fail to be well-formed: internal
+//! DTDs that do not parse, elements with duplicated attributes, and files cut
+//! short by an interrupted export.
+
+use quick_xml::events::attributes::Attributes;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+
+/// The attributes of `e`, without quick-xml's duplicate check: some iOS
+/// versions write the same attribute twice, and the last value wins.
+pub(crate) fn attributes<'a>(e: &'a BytesStart) -> Attributes<'a> {
+    let mut attributes = e.attributes();
+    attributes.with_checks(false);
+    attributes
+}
+
+/// Byte offset of the `root` start tag. The prolog is searched as text rather
+/// than parsed, so a broken DTD cannot swallow the document; the DTD itself
+/// only ever names the root in `<!DOCTYPE`, `<!ELEMENT` and `<!ATTLIST`.
+pub fn root_start(xml: &str, root: &str) -> Option<usize> {
+    let tag = format!("<{}", root);
+    let mut from = 0;
+    while let Some(found) = xml[from..].find(&tag) {
+        let start = from + found;
+        let next = xml.as_bytes().get(start + tag.len());
+        if next.is_none_or(|&b| b.is_ascii_whitespace() || b == b'>' || b == b'/') {
+            return Some(start);
+        }
+        from = start + tag.len();
+    }
+    None
+}
+
+/// For a document that stops before its closing `</root>` tag, the length of
+/// the prefix ending with the last complete top-level element; `None` when
+/// the document is closed properly. `body` is where the root element starts.
+pub fn complete_len(xml: &str, body: usize, root: &str) -> Option<usize> {
+    let closing = format!("</{}>", root);
+    if xml.trim_end().ends_with(&closing) {
+        return None;
+    }
+
+    let mut reader = Reader::from_str(&xml[body..]);
+    // Only nesting matters here; a mismatched end tag elsewhere is for the
+    // element parsers to report.
+    reader.config_mut().check_end_names = false;
+
+    let mut depth = 0usize;
+    let mut complete = body;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(_)) => {
+                depth += 1;
+                if depth == 1 {
+                    complete = body + reader.buffer_position() as usize;
+                }
+            }
+            Ok(Event::End(_)) => {
+                depth = depth.saturating_sub(1);
+                if depth == 1 {
+                    complete = body + reader.buffer_position() as usize;
+                }
+            }
+            Ok(Event::Empty(_)) if depth == 1 => {
+                complete = body + reader.buffer_position() as usize;
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            Ok(_) => {}
+        }
+    }
+    Some(complete)
+}
@@ -0,0 +1,490 @@
+use crate::diagnostics::{Diagnostics, SkipReason};
+use crate::error::{ElementFault, ParseError};
+use crate::input::Document;
+use crate::progress::Task;
+use crate::type_filter::TypeFilter;
+use crate::{ParsedRecords, attr_str, chunker, recovery};
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use smallstr::SmallString;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StateOfMind {
+    pub kind: StateOfMindKind,
+    pub valence: Option<f64>,
+    #[serde(rename = "valenceClassification")]
+    pub valence_classification: Option<ValenceClassification>,
+    pub labels: Vec<StateOfMindLabel>,
+    pub associations: Vec<StateOfMindAssociation>,
+    #[serde(rename = "sourceName")]
+    pub source_name: Option<SmallString<[u8; 32]>>,
+    #[serde(rename = "startDate")]
+    pub start_date: Option<SmallString<[u8; 32]>>,
+    #[serde(rename = "endDate")]
+    pub end_date: Option<SmallString<[u8; 32]>>,
+    pub metadata: HashMap<SmallString<[u8; 16]>, SmallString<[u8; 32]>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StateOfMindKind {
+    MomentaryEmotion,
+    DailyMood,
+    Unknown(String),
+}
+
+impl StateOfMindKind {
+    pub fn from_hk(value: &str) -> Self {
+        match value.trim_start_matches("HKStateOfMindKind") {
+            "MomentaryEmotion" | "1" => StateOfMindKind::MomentaryEmotion,
+            "DailyMood" | "2" => StateOfMindKind::DailyMood,
+            other => StateOfMindKind::Unknown(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for StateOfMindKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StateOfMindKind::MomentaryEmotion => "Momentary Emotion",
+            StateOfMindKind::DailyMood => "Daily Mood",
+            StateOfMindKind::Unknown(raw) => return write!(f, "Unknown({})", raw),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ValenceClassification {
+    VeryUnpleasant,
+    Unpleasant,
+    SlightlyUnpleasant,
+    Neutral,
+    SlightlyPleasant,
+    Pleasant,
+    VeryPleasant,
+    Unknown(String),
+}
+
+impl ValenceClassification {
+    pub fn from_hk(value: &str) -> Self {
+        match value.trim_start_matches("HKStateOfMindValenceClassification") {
+            "VeryUnpleasant" | "1" => ValenceClassification::VeryUnpleasant,
+            "Unpleasant" | "2" => ValenceClassification::Unpleasant,
+            "SlightlyUnpleasant" | "3" => ValenceClassification::SlightlyUnpleasant,
+            "Neutral" | "4" => ValenceClassification::Neutral,
+            "SlightlyPleasant" | "5" => ValenceClassification::SlightlyPleasant,
+            "Pleasant" | "6" => ValenceClassification::Pleasant,
+            "VeryPleasant" | "7" => ValenceClassification::VeryPleasant,
+            other => ValenceClassification::Unknown(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ValenceClassification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValenceClassification::VeryUnpleasant => "Very Unpleasant",
+            ValenceClassification::Unpleasant => "Unpleasant",
+            ValenceClassification::SlightlyUnpleasant => "Slightly Unpleasant",
+            ValenceClassification::Neutral => "Neutral",
+            ValenceClassification::SlightlyPleasant => "Slightly Pleasant",
+            ValenceClassification::Pleasant => "Pleasant",
+            ValenceClassification::VeryPleasant => "Very Pleasant",
+            ValenceClassification::Unknown(raw) => return write!(f, "Unknown({})", raw),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StateOfMindLabel {
+    Amazed,
+    Amused,
+    Angry,
+    Annoyed,
+    Anxious,
+    Ashamed,
+    Brave,
+    Calm,
+    Confident,
+    Content,
+    Disappointed,
+    Discouraged,
+    Disgusted,
+    Drained,
+    Embarrassed,
+    Excited,
+    Frustrated,
+    Grateful,
+    Guilty,
+    Happy,
+    Hopeful,
+    Hopeless,
+    Indifferent,
+    Irritated,
+    Jealous,
+    Joyful,
+    Lonely,
+    Overwhelmed,
+    Passionate,
+    Peaceful,
+    Proud,
+    Relieved,
+    Sad,
+    Satisfied,
+    Scared,
+    Stressed,
+    Surprised,
+    Worried,
+    Unknown(String),
+}
+
+impl StateOfMindLabel {
+    pub fn from_hk(value: &str) -> Self {
+        match value.trim_start_matches("HKStateOfMindLabel") {
+            "Amazed" => StateOfMindLabel::Amazed,
+            "Amused" => StateOfMindLabel::Amused,
+            "Angry" => StateOfMindLabel::Angry,
+            "Annoyed" => StateOfMindLabel::Annoyed,
+            "Anxious" => StateOfMindLabel::Anxious,
+            "Ashamed" => StateOfMindLabel::Ashamed,
+            "Brave" => StateOfMindLabel::Brave,
+            "Calm" => StateOfMindLabel::Calm,
+            "Confident" => StateOfMindLabel::Confident,
+            "Content" => StateOfMindLabel::Content,
+            "Disappointed" => StateOfMindLabel::Disappointed,
+            "Discouraged" => StateOfMindLabel::Discouraged,
+            "Disgusted" => StateOfMindLabel::Disgusted,
+            "Drained" => StateOfMindLabel::Drained,
+            "Embarrassed" => StateOfMindLabel::Embarrassed,
+            "Excited" => StateOfMindLabel::Excited,
+            "Frustrated" => StateOfMindLabel::Frustrated,
+            "Grateful" => StateOfMindLabel::Grateful,
+            "Guilty" => StateOfMindLabel::Guilty,
+            "Happy" => StateOfMindLabel::Happy,
+            "Hopeful" => StateOfMindLabel::Hopeful,
+            "Hopeless" => StateOfMindLabel::Hopeless,
+            "Indifferent" => StateOfMindLabel::Indifferent,
+            "Irritated" => StateOfMindLabel::Irritated,
+            "Jealous" => StateOfMindLabel::Jealous,
+            "Joyful" => StateOfMindLabel::Joyful,
+            "Lonely" => StateOfMindLabel::Lonely,
+            "Overwhelmed" => StateOfMindLabel::Overwhelmed,
+            "Passionate" => StateOfMindLabel::Passionate,
+            "Peaceful" => StateOfMindLabel::Peaceful,
+            "Proud" => StateOfMindLabel::Proud,
+            "Relieved" => StateOfMindLabel::Relieved,
+            "Sad" => StateOfMindLabel::Sad,
+            "Satisfied" => StateOfMindLabel::Satisfied,
+            "Scared" => StateOfMindLabel::Scared,
+            "Stressed" => StateOfMindLabel::Stressed,
+            "Surprised" => StateOfMindLabel::Surprised,
+            "Worried" => StateOfMindLabel::Worried,
+            other => StateOfMindLabel::Unknown(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for StateOfMindLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StateOfMindLabel::Amazed => "Amazed",
+            StateOfMindLabel::Amused => "Amused",
+            StateOfMindLabel::Angry => "Angry",
+            StateOfMindLabel::Annoyed => "Annoyed",
+            StateOfMindLabel::Anxious => "Anxious",
+            StateOfMindLabel::Ashamed => "Ashamed",
+            StateOfMindLabel::Brave => "Brave",
+            StateOfMindLabel::Calm => "Calm",
+            StateOfMindLabel::Confident => "Confident",
+            StateOfMindLabel::Content => "Content",
+            StateOfMindLabel::Disappointed => "Disappointed",
+            StateOfMindLabel::Discouraged => "Discouraged",
+            StateOfMindLabel::Disgusted => "Disgusted",
+            StateOfMindLabel::Drained => "Drained",
+            StateOfMindLabel::Embarrassed => "Embarrassed",
+            StateOfMindLabel::Excited => "Excited",
+            StateOfMindLabel::Frustrated => "Frustrated",
+            StateOfMindLabel::Grateful => "Grateful",
+            StateOfMindLabel::Guilty => "Guilty",
+            StateOfMindLabel::Happy => "Happy",
+            StateOfMindLabel::Hopeful => "Hopeful",
+            StateOfMindLabel::Hopeless => "Hopeless",
+            StateOfMindLabel::Indifferent => "Indifferent",
+            StateOfMindLabel::Irritated => "Irritated",
+            StateOfMindLabel::Jealous => "Jealous",
+            StateOfMindLabel::Joyful => "Joyful",
+            StateOfMindLabel::Lonely => "Lonely",
+            StateOfMindLabel::Overwhelmed => "Overwhelmed",
+            StateOfMindLabel::Passionate => "Passionate",
+            StateOfMindLabel::Peaceful => "Peaceful",
+            StateOfMindLabel::Proud => "Proud",
+            StateOfMindLabel::Relieved => "Relieved",
+            StateOfMindLabel::Sad => "Sad",
+            StateOfMindLabel::Satisfied => "Satisfied",
+            StateOfMindLabel::Scared => "Scared",
+            StateOfMindLabel::Stressed => "Stressed",
+            StateOfMindLabel::Surprised => "Surprised",
+            StateOfMindLabel::Worried => "Worried",
+            StateOfMindLabel::Unknown(raw) => return write!(f, "Unknown({})", raw),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StateOfMindAssociation {
+    Community,
+    CurrentEvents,
+    Dating,
+    Education,
+    Family,
+    Fitness,
+    Friends,
+    Health,
+    Hobbies,
+    Identity,
+    Money,
+    Partner,
+    SelfCare,
+    Spirituality,
+    Tasks,
+    Travel,
+    Weather,
+    Work,
+    Unknown(String),
+}
+
+impl StateOfMindAssociation {
+    pub fn from_hk(value: &str) -> Self {
+        match value.trim_start_matches("HKStateOfMindAssociation") {
+            "Community" => StateOfMindAssociation::Community,
+            "CurrentEvents" => StateOfMindAssociation::CurrentEvents,
+            "Dating" => StateOfMindAssociation::Dating,
+            "Education" => StateOfMindAssociation::Education,
+            "Family" => StateOfMindAssociation::Family,
+            "Fitness" => StateOfMindAssociation::Fitness,
+            "Friends" => StateOfMindAssociation::Friends,
+            "Health" => StateOfMindAssociation::Health,
+            "Hobbies" => StateOfMindAssociation::Hobbies,
+            "Identity" => StateOfMindAssociation::Identity,
+            "Money" => StateOfMindAssociation::Money,
+            "Partner" => StateOfMindAssociation::Partner,
+            "SelfCare" => StateOfMindAssociation::SelfCare,
+            "Spirituality" => StateOfMindAssociation::Spirituality,
+            "Tasks" => StateOfMindAssociation::Tasks,
+            "Travel" => StateOfMindAssociation::Travel,
+            "Weather" => StateOfMindAssociation::Weather,
+            "Work" => StateOfMindAssociation::Work,
+            other => StateOfMindAssociation::Unknown(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for StateOfMindAssociation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StateOfMindAssociation::Community => "Community",
+            StateOfMindAssociation::CurrentEvents => "Current Events",
+            StateOfMindAssociation::Dating => "Dating",
+            StateOfMindAssociation::Education => "Education",
+            StateOfMindAssociation::Family => "Family",
+            StateOfMindAssociation::Fitness => "Fitness",
+            StateOfMindAssociation::Friends => "Friends",
+            StateOfMindAssociation::Health => "Health",
+            StateOfMindAssociation::Hobbies => "Hobbies",
+            StateOfMindAssociation::Identity => "Identity",
+            StateOfMindAssociation::Money => "Money",
+            StateOfMindAssociation::Partner => "Partner",
+            StateOfMindAssociation::SelfCare => "Self Care",
+            StateOfMindAssociation::Spirituality => "Spirituality",
+            StateOfMindAssociation::Tasks => "Tasks",
+            StateOfMindAssociation::Travel => "Travel",
+            StateOfMindAssociation::Weather => "Weather",
+            StateOfMindAssociation::Work => "Work",
+            StateOfMindAssociation::Unknown(raw) => return write!(f, "Unknown({})", raw),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+macro_rules! display_serde {
+    ($($ty:ident),*) => {
+        $(
+            impl Serialize for $ty {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.collect_str(self)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $ty {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let name = String::deserialize(deserializer)?;
+                    match name.strip_prefix("Unknown(").and_then(|raw| raw.strip_suffix(')')) {
+                        Some(raw) => Ok($ty::Unknown(raw.to_string())),
+                        None => Ok($ty::from_hk(&name.replace(' ', ""))),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+display_serde!(
+    StateOfMindKind,
+    ValenceClassification,
+    StateOfMindLabel,
+    StateOfMindAssociation
+);
+
+/// Parses every `<StateOfMind>` of `export.xml`, in document order, keeping
+/// the entries whose start date `type_filter` allows.
+pub fn parse_state_of_mind(
+    xml: &str,
+    type_filter: &TypeFilter,
+    task: &Task,
+    lenient: bool,
+) -> Result<ParsedRecords<StateOfMind>, ParseError> {
+    // The body only skips the prolog, whose DTD may mention the element too.
+    let body = recovery::root_start(xml, Document::Export.root_element()).unwrap_or(0);
+    let parts = rayon::current_num_threads() * 8;
+    let batches = chunker::par_elements(xml, body, "StateOfMind", parts, |elements| {
+        let mut batch = ParsedRecords::default();
+        for (start, element) in elements {
+            batch.diagnostics.scanned += 1;
+            match parse_entry(element, start, type_filter, &mut batch.diagnostics) {
+                Ok(Some(entry)) => batch.records.push(entry),
+                Ok(None) => {}
+                Err(fault) => batch.fail(xml, "StateOfMind", start, fault),
+            }
+        }
+        task.inc(batch.diagnostics.scanned);
+        batch
+    });
+
+    ParsedRecords::concat(xml, batches, lenient)
+}
+
+/// Parses the state of mind `element`, which starts at byte `start` of the
+/// document, noting in `diagnostics` why it was skipped if it is not kept.
+fn parse_entry(
+    element: &str,
+    start: usize,
+    type_filter: &TypeFilter,
+    diagnostics: &mut Diagnostics,
+) -> Result<Option<StateOfMind>, ElementFault> {
+    let mut reader = Reader::from_str(element);
+    reader.config_mut().trim_text(true);
+
+    let mut kind = None;
+    let mut valence = None;
+    let mut valence_classification = None;
+    let mut labels = Vec::new();
+    let mut associations = Vec::new();
+    let mut source_name = None;
+    let mut start_date: Option<SmallString<[u8; 32]>> = None;
+    let mut end_date = None;
+    let mut metadata: HashMap<SmallString<[u8; 16]>, SmallString<[u8; 32]>> = HashMap::new();
+
+    loop {
+        // Where the next tag starts, to locate errors in its attributes.
+        let position = reader.buffer_position();
+        let event = reader
+            .read_event()
+            .map_err(|e| ElementFault::xml(reader.error_position(), e))?;
+        let self_closing = matches!(event, Event::Empty(_));
+        match event {
+            Event::Empty(ref e) | Event::Start(ref e) => match e.name().as_ref() {
+                b"StateOfMind" => {
+                    for attr in recovery::attributes(e) {
+                        let attr = attr.map_err(|e| ElementFault::xml(position, e))?;
+                        let v_str = attr_str(&attr.value, position)?;
+                        match attr.key.as_ref() {
+                            b"kind" => kind = Some(StateOfMindKind::from_hk(&v_str)),
+                            b"valence" => valence = v_str.parse::<f64>().ok(),
+                            b"valenceClassification" => {
+                                valence_classification =
+                                    Some(ValenceClassification::from_hk(&v_str));
+                            }
+                            b"sourceName" => source_name = Some(SmallString::from(&*v_str)),
+                            b"startDate" => start_date = Some(SmallString::from(&*v_str)),
+                            b"endDate" => end_date = Some(SmallString::from(&*v_str)),
+                            _ => {}
+                        }
+                    }
+                    if self_closing {
+                        break;
+                    }
+                }
+                b"Label" | b"Association" => {
+                    let is_label = e.name().as_ref() == b"Label";
+                    for attr in recovery::attributes(e) {
+                        let attr = attr.map_err(|e| ElementFault::xml(position, e))?;
+                        if attr.key.as_ref() != b"value" {
+                            continue;
+                        }
+                        let v_str = attr_str(&attr.value, position)?;
+                        if is_label {
+                            labels.push(StateOfMindLabel::from_hk(&v_str));
+                        } else {
+                            associations.push(StateOfMindAssociation::from_hk(&v_str));
+                        }
+                    }
+                }
+                b"MetadataEntry" => {
+                    let mut key_opt = None;
+                    let mut value_opt = None;
+                    for attr in recovery::attributes(e) {
+                        let attr = attr.map_err(|e| ElementFault::xml(position, e))?;
+                        let v_str = attr_str(&attr.value, position)?;
+                        match attr.key.as_ref() {
+                            b"key" => key_opt = Some(SmallString::from(&*v_str)),
+                            b"value" => value_opt = Some(SmallString::from(&*v_str)),
+                            _ => {}
+                        }
+                    }
+                    if let (Some(key), Some(value)) = (key_opt, value_opt) {
+                        metadata.insert(key, value);
+                    }
+                }
+                _ => {}
+            },
+            Event::End(ref e) if e.name().as_ref() == b"StateOfMind" => break,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if !start_date
+        .as_deref()
+        .is_some_and(|date| type_filter.allows_date(date))
+    {
+        diagnostics.skip(
+            SkipReason::FilteredByDate,
+            None,
+            start,
+            element,
+            || match &start_date {
+                Some(date) => format!("startDate {} is before the cutoff", date),
+                None => "no startDate".to_string(),
+            },
+        );
+        return Ok(None);
+    }
+
+    diagnostics.parsed += 1;
+    Ok(Some(StateOfMind {
+        kind: kind.unwrap_or(StateOfMindKind::Unknown(String::new())),
+        valence,
+        valence_classification,
+        labels,
+        associations,
+        source_name,
+        start_date,
+        end_date,
+        metadata,
+    }))
+}
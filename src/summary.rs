@@ -0,0 +1,69 @@
+//! Record counts and value totals per type, under each type's catalogue
+//! group. Values combine as the catalogue says: cumulative types (steps,
+//! energy) are summed, discrete ones (heart rate, body mass) are averaged
+//! and reduced to a minimum and maximum.
+
+use crate::HealthRecord;
+use crate::health_type::{Aggregation, RecordType, TypeGroup};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TypeSummary {
+    pub records: u64,
+    /// Records with a numeric value, which the figures below are over.
+    pub values: u64,
+    /// Sum of the values, for cumulative types.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<f64>,
+    /// Mean, minimum and maximum of the values, for discrete types.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+}
+
+pub type Summary = BTreeMap<TypeGroup, BTreeMap<RecordType, TypeSummary>>;
+
+pub fn summarize<'a>(records: impl IntoIterator<Item = &'a HealthRecord>) -> Summary {
+    // Sum, minimum and maximum of each type's numeric values.
+    let mut totals: BTreeMap<&RecordType, (TypeSummary, f64)> = BTreeMap::new();
+    for record in records {
+        let Some(record_type) = &record.record_type else {
+            continue;
+        };
+        let (summary, sum) = totals.entry(record_type).or_default();
+        summary.records += 1;
+        let Some(value) = record.value.as_deref().and_then(|v| v.parse::<f64>().ok()) else {
+            continue;
+        };
+        summary.values += 1;
+        *sum += value;
+        summary.min = Some(summary.min.map_or(value, |min| min.min(value)));
+        summary.max = Some(summary.max.map_or(value, |max| max.max(value)));
+    }
+
+    let mut out = Summary::new();
+    for (record_type, (mut summary, sum)) in totals {
+        match record_type.aggregation() {
+            Some(Aggregation::Cumulative) => {
+                summary.total = Some(sum);
+                summary.min = None;
+                summary.max = None;
+            }
+            Some(Aggregation::Discrete) => {
+                summary.mean = (summary.values > 0).then(|| sum / summary.values as f64);
+            }
+            None => {
+                summary.min = None;
+                summary.max = None;
+            }
+        }
+        out.entry(record_type.group())
+            .or_default()
+            .insert(record_type.clone(), summary);
+    }
+    out
+}
@@ -0,0 +1,198 @@
+use crate::health_type::{self, TypeGroup};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// The types parsed when no include patterns are configured.
+pub const DEFAULT_TYPES: &[&str] = &[
+    "HKQuantityTypeIdentifierHeartRate",
+    "HKCategoryTypeIdentifierHighHeartRateEvent",
+    "HKQuantityTypeIdentifierRestingHeartRate",
+    "HKQuantityTypeIdentifierPhysicalEffort",
+    "HKQuantityTypeIdentifierBasalEnergyBurned",
+    "HKQuantityTypeIdentifierActiveEnergyBurned",
+    "HKQuantityTypeIdentifierDistanceWalkingRunning",
+    "HKQuantityTypeIdentifierWalkingSpeed",
+    "HKQuantityTypeIdentifierAppleStandTime",
+    "HKQuantityTypeIdentifierAppleExerciseTime",
+    "HKQuantityTypeIdentifierWalkingStepLength",
+    "HKQuantityTypeIdentifierStepCount",
+    "HKQuantityTypeIdentifierFlightsClimbed",
+    "HKCategoryTypeIdentifierSleepAnalysis",
+    "HKQuantityTypeIdentifierBodyMass",
+    "HKCategoryTypeIdentifierToothbrushingEvent",
+    "HKQuantityTypeIdentifierSixMinuteWalkTestDistance",
+    "HKQuantityTypeIdentifierDietaryCaffeine",
+    "HKQuantityTypeIdentifierDietaryWater",
+];
+
+/// A filter profile as written in a TOML or JSON file, e.g.
+///
+/// ```toml
+/// include = ["@heart", "HKQuantityTypeIdentifierDietary*"]
+/// exclude = ["HKQuantityTypeIdentifierDietaryWater"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilterConfig {
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl FilterConfig {
+    /// Reads a profile, as JSON for `.json` files and TOML otherwise.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let contents = fs::read_to_string(path)?;
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let config = if is_json {
+            serde_json::from_str(&contents)
+                .map_err(|e| format!("invalid type filter {}: {}", path.display(), e))?
+        } else {
+            toml::from_str(&contents)
+                .map_err(|e| format!("invalid type filter {}: {}", path.display(), e))?
+        };
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Default)]
+struct PatternSet {
+    exact: HashSet<String>,
+    globs: Vec<String>,
+}
+
+impl PatternSet {
+    fn add(&mut self, pattern: &str) -> Result<(), String> {
+        let pattern = pattern.trim();
+        if let Some(name) = pattern.strip_prefix('@') {
+            let group = TypeGroup::from_name(name).ok_or_else(|| {
+                let names: Vec<String> = TypeGroup::ALL.iter().map(|g| g.to_string()).collect();
+                format!(
+                    "unknown type group '{}' (expected one of {})",
+                    name,
+                    names.join(", ")
+                )
+            })?;
+            for health_type in health_type::in_group(group) {
+                self.exact.insert(health_type.identifier.to_string());
+            }
+        } else if pattern.contains(['*', '?']) {
+            self.globs.push(pattern.to_string());
+        } else if !pattern.is_empty() {
+            self.exact.insert(pattern.to_string());
+        }
+        Ok(())
+    }
+
+    fn matches(&self, record_type: &str) -> bool {
+        self.exact.contains(record_type)
+            || self
+                .globs
+                .iter()
+                .any(|glob| glob_match(glob.as_bytes(), record_type.as_bytes()))
+    }
+
+    fn describe(&self, prefix: &str, out: &mut Vec<String>) {
+        let mut patterns: Vec<&String> = self.exact.iter().chain(&self.globs).collect();
+        patterns.sort();
+        out.extend(patterns.into_iter().map(|p| format!("{}{}", prefix, p)));
+    }
+}
+
+/// Decides which records are parsed: a type passes if it matches an include
+/// pattern and no exclude pattern, and a record if its type passes and it
+/// starts no earlier than the cutoff month.
+#[derive(Debug, Default)]
+pub struct TypeFilter {
+    include: PatternSet,
+    exclude: PatternSet,
+    /// Year and month of the oldest records kept; `None` keeps every date.
+    cutoff: Option<(i32, u32)>,
+}
+
+impl TypeFilter {
+    /// Builds a filter from a profile. Without include patterns the default
+    /// types are used, so a profile may consist of exclusions alone. Only
+    /// the last 12 months are kept until `with_all_dates` lifts the cutoff.
+    pub fn from_config(config: &FilterConfig) -> Result<Self, String> {
+        let mut filter = TypeFilter {
+            cutoff: Some(crate::cutoff_year_month()),
+            ..TypeFilter::default()
+        };
+        if config.include.is_empty() {
+            for record_type in DEFAULT_TYPES {
+                filter.include.add(record_type)?;
+            }
+        }
+        for pattern in &config.include {
+            filter.include.add(pattern)?;
+        }
+        for pattern in &config.exclude {
+            filter.exclude.add(pattern)?;
+        }
+        Ok(filter)
+    }
+
+    /// Keeps records of any age.
+    pub fn with_all_dates(mut self) -> Self {
+        self.cutoff = None;
+        self
+    }
+
+    pub fn allows(&self, record_type: &str) -> bool {
+        self.include.matches(record_type) && !self.exclude.matches(record_type)
+    }
+
+    /// Whether a record starting at `start_date` is recent enough to keep.
+    pub fn allows_date(&self, start_date: &str) -> bool {
+        self.cutoff
+            .is_none_or(|cutoff| crate::is_on_or_after(start_date, cutoff))
+    }
+
+    /// The resolved patterns and cutoff in a stable order, for the cache key.
+    pub fn describe(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        self.include.describe("include=", &mut out);
+        self.exclude.describe("exclude=", &mut out);
+        out.push(match self.cutoff {
+            Some((year, month)) => format!("cutoff={}-{:02}", year, month),
+            None => "cutoff=none".to_string(),
+        });
+        out
+    }
+}
+
+/// Matches `*` (any run of characters) and `?` (any one character).
+pub(crate) fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Where to resume after the last `*` if the current attempt fails.
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
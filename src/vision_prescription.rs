@@ -0,0 +1,186 @@
+use crate::error::{ElementFault, ParseError};
+use crate::input::Document;
+use crate::progress::Task;
+use crate::{ParsedRecords, attr_str, chunker, recovery};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use serde::{Deserialize, Serialize};
+use smallstr::SmallString;
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VisionPrescription {
+    #[serde(rename = "type")]
+    pub prescription_type: Option<SmallString<[u8; 32]>>,
+    #[serde(rename = "dateIssued")]
+    pub date_issued: Option<SmallString<[u8; 32]>>,
+    #[serde(rename = "expirationDate")]
+    pub expiration_date: Option<SmallString<[u8; 32]>>,
+    pub brand: Option<SmallString<[u8; 32]>>,
+    #[serde(rename = "rightEye")]
+    pub right_eye: Option<EyePrescription>,
+    #[serde(rename = "leftEye")]
+    pub left_eye: Option<EyePrescription>,
+    pub metadata: HashMap<SmallString<[u8; 16]>, SmallString<[u8; 32]>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EyePrescription {
+    pub sphere: Option<Measurement>,
+    pub cylinder: Option<Measurement>,
+    pub axis: Option<Measurement>,
+    pub add: Option<Measurement>,
+    pub vertex: Option<Measurement>,
+    #[serde(rename = "prismAmount")]
+    pub prism_amount: Option<Measurement>,
+    #[serde(rename = "prismAngle")]
+    pub prism_angle: Option<Measurement>,
+    #[serde(rename = "farPD")]
+    pub far_pd: Option<Measurement>,
+    #[serde(rename = "nearPD")]
+    pub near_pd: Option<Measurement>,
+    #[serde(rename = "baseCurve")]
+    pub base_curve: Option<Measurement>,
+    pub diameter: Option<Measurement>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Measurement {
+    pub value: f64,
+    pub unit: Option<SmallString<[u8; 16]>>,
+}
+
+/// Each measurement is exported as a value attribute (`sphere`) with an
+/// optional sibling unit attribute (`sphereUnit`).
+fn parse_eye(e: &BytesStart, position: u64) -> Result<EyePrescription, ElementFault> {
+    let mut values: HashMap<Vec<u8>, f64> = HashMap::new();
+    let mut units: HashMap<Vec<u8>, SmallString<[u8; 16]>> = HashMap::new();
+
+    for attr in recovery::attributes(e) {
+        let attr = attr.map_err(|e| ElementFault::xml(position, e))?;
+        let v_str = attr_str(&attr.value, position)?;
+        let key = attr.key.as_ref();
+        if let Some(name) = key.strip_suffix(b"Unit") {
+            units.insert(name.to_vec(), SmallString::from(&*v_str));
+        } else if let Ok(value) = v_str.trim().parse::<f64>() {
+            values.insert(key.to_vec(), value);
+        }
+    }
+
+    let mut measurement = |name: &[u8]| {
+        values.remove(name).map(|value| Measurement {
+            value,
+            unit: units.remove(name),
+        })
+    };
+
+    Ok(EyePrescription {
+        sphere: measurement(b"sphere"),
+        cylinder: measurement(b"cylinder"),
+        axis: measurement(b"axis"),
+        add: measurement(b"add"),
+        vertex: measurement(b"vertex"),
+        prism_amount: measurement(b"prismAmount"),
+        prism_angle: measurement(b"prismAngle"),
+        far_pd: measurement(b"farPD"),
+        near_pd: measurement(b"nearPD"),
+        base_curve: measurement(b"baseCurve"),
+        diameter: measurement(b"diameter"),
+    })
+}
+
+/// Parses every `<VisionPrescription>` of `export.xml`, in document order.
+pub fn parse_vision_prescriptions(
+    xml: &str,
+    task: &Task,
+    lenient: bool,
+) -> Result<ParsedRecords<VisionPrescription>, ParseError> {
+    // The body only skips the prolog, whose DTD may mention the element too.
+    let body = recovery::root_start(xml, Document::Export.root_element()).unwrap_or(0);
+    let parts = rayon::current_num_threads() * 8;
+    let batches = chunker::par_elements(xml, body, "VisionPrescription", parts, |elements| {
+        let mut batch = ParsedRecords::default();
+        for (start, element) in elements {
+            batch.diagnostics.scanned += 1;
+            match parse_prescription(element) {
+                Ok(prescription) => {
+                    batch.diagnostics.parsed += 1;
+                    batch.records.push(prescription);
+                }
+                Err(fault) => batch.fail(xml, "VisionPrescription", start, fault),
+            }
+        }
+        task.inc(batch.diagnostics.scanned);
+        batch
+    });
+
+    ParsedRecords::concat(xml, batches, lenient)
+}
+
+fn parse_prescription(element: &str) -> Result<VisionPrescription, ElementFault> {
+    let mut reader = Reader::from_str(element);
+    reader.config_mut().trim_text(true);
+
+    let mut prescription = VisionPrescription {
+        prescription_type: None,
+        date_issued: None,
+        expiration_date: None,
+        brand: None,
+        right_eye: None,
+        left_eye: None,
+        metadata: HashMap::new(),
+    };
+
+    loop {
+        // Where the next tag starts, to locate errors in its attributes.
+        let position = reader.buffer_position();
+        let event = reader
+            .read_event()
+            .map_err(|e| ElementFault::xml(reader.error_position(), e))?;
+        let self_closing = matches!(event, Event::Empty(_));
+        match event {
+            Event::Empty(ref e) | Event::Start(ref e) => match e.name().as_ref() {
+                b"VisionPrescription" => {
+                    for attr in recovery::attributes(e) {
+                        let attr = attr.map_err(|e| ElementFault::xml(position, e))?;
+                        let value = Some(SmallString::from(&*attr_str(&attr.value, position)?));
+                        match attr.key.as_ref() {
+                            b"type" => prescription.prescription_type = value,
+                            b"dateIssued" => prescription.date_issued = value,
+                            b"expirationDate" => prescription.expiration_date = value,
+                            b"brand" => prescription.brand = value,
+                            _ => {}
+                        }
+                    }
+                    if self_closing {
+                        break;
+                    }
+                }
+                b"RightEye" => prescription.right_eye = Some(parse_eye(e, position)?),
+                b"LeftEye" => prescription.left_eye = Some(parse_eye(e, position)?),
+                b"MetadataEntry" => {
+                    let mut key_opt = None;
+                    let mut value_opt = None;
+                    for attr in recovery::attributes(e) {
+                        let attr = attr.map_err(|e| ElementFault::xml(position, e))?;
+                        let v_str = attr_str(&attr.value, position)?;
+                        match attr.key.as_ref() {
+                            b"key" => key_opt = Some(SmallString::from(&*v_str)),
+                            b"value" => value_opt = Some(SmallString::from(&*v_str)),
+                            _ => {}
+                        }
+                    }
+                    if let (Some(key), Some(value)) = (key_opt, value_opt) {
+                        prescription.metadata.insert(key, value);
+                    }
+                }
+                _ => {}
+            },
+            Event::End(ref e) if e.name().as_ref() == b"VisionPrescription" => break,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(prescription)
+}
@@ -1,5 +1,13 @@
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
 use std::fmt;
+use std::str::FromStr;
 
+const IDENTIFIER_PREFIX: &str = "HKWorkoutActivityType";
+
+/// `HKWorkoutActivityType` codes. Code 81 is unused in the public HealthKit
+/// SDK and has no known name, so it parses as `Unknown(81)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u32)]
 pub enum WorkoutActivityType {
     AmericanFootball = 1,
@@ -275,3 +283,362 @@ impl fmt::Display for WorkoutActivityType {
         write!(f, "{}", name)
     }
 }
+
+/// Broad families of workouts, for grouping and summaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WorkoutCategory {
+    Cardio,
+    Strength,
+    Water,
+    Winter,
+    TeamSports,
+    Racket,
+    MindAndBody,
+    Other,
+}
+
+impl WorkoutActivityType {
+    /// Every named activity type, in code order.
+    pub fn all() -> impl Iterator<Item = Self> {
+        (1..=84)
+            .chain([3000])
+            .map(WorkoutActivityType::from_u32)
+            .filter(|activity| !matches!(activity, WorkoutActivityType::Unknown(_)))
+    }
+
+    pub fn as_u32(self) -> u32 {
+        match self {
+            WorkoutActivityType::AmericanFootball => 1,
+            WorkoutActivityType::Archery => 2,
+            WorkoutActivityType::AustralianFootball => 3,
+            WorkoutActivityType::Badminton => 4,
+            WorkoutActivityType::Baseball => 5,
+            WorkoutActivityType::Basketball => 6,
+            WorkoutActivityType::Bowling => 7,
+            WorkoutActivityType::Boxing => 8,
+            WorkoutActivityType::Climbing => 9,
+            WorkoutActivityType::Cricket => 10,
+            WorkoutActivityType::CrossTraining => 11,
+            WorkoutActivityType::Curling => 12,
+            WorkoutActivityType::Cycling => 13,
+            WorkoutActivityType::Dance => 14,
+            WorkoutActivityType::DanceInspiredTraining => 15,
+            WorkoutActivityType::Elliptical => 16,
+            WorkoutActivityType::EquestrianSports => 17,
+            WorkoutActivityType::Fencing => 18,
+            WorkoutActivityType::Fishing => 19,
+            WorkoutActivityType::FunctionalStrengthTraining => 20,
+            WorkoutActivityType::Golf => 21,
+            WorkoutActivityType::Gymnastics => 22,
+            WorkoutActivityType::Handball => 23,
+            WorkoutActivityType::Hiking => 24,
+            WorkoutActivityType::Hockey => 25,
+            WorkoutActivityType::Hunting => 26,
+            WorkoutActivityType::Lacrosse => 27,
+            WorkoutActivityType::MartialArts => 28,
+            WorkoutActivityType::MindAndBody => 29,
+            WorkoutActivityType::MixedMetabolicCardioTraining => 30,
+            WorkoutActivityType::PaddleSports => 31,
+            WorkoutActivityType::Play => 32,
+            WorkoutActivityType::PreparationAndRecovery => 33,
+            WorkoutActivityType::Racquetball => 34,
+            WorkoutActivityType::Rowing => 35,
+            WorkoutActivityType::Rugby => 36,
+            WorkoutActivityType::Running => 37,
+            WorkoutActivityType::Sailing => 38,
+            WorkoutActivityType::SkatingSports => 39,
+            WorkoutActivityType::SnowSports => 40,
+            WorkoutActivityType::Soccer => 41,
+            WorkoutActivityType::Softball => 42,
+            WorkoutActivityType::Squash => 43,
+            WorkoutActivityType::StairClimbing => 44,
+            WorkoutActivityType::SurfingSports => 45,
+            WorkoutActivityType::Swimming => 46,
+            WorkoutActivityType::TableTennis => 47,
+            WorkoutActivityType::Tennis => 48,
+            WorkoutActivityType::TrackAndField => 49,
+            WorkoutActivityType::TraditionalStrengthTraining => 50,
+            WorkoutActivityType::Volleyball => 51,
+            WorkoutActivityType::Walking => 52,
+            WorkoutActivityType::WaterFitness => 53,
+            WorkoutActivityType::WaterPolo => 54,
+            WorkoutActivityType::WaterSports => 55,
+            WorkoutActivityType::Wrestling => 56,
+            WorkoutActivityType::Yoga => 57,
+            WorkoutActivityType::Barre => 58,
+            WorkoutActivityType::CoreTraining => 59,
+            WorkoutActivityType::CrossCountrySkiing => 60,
+            WorkoutActivityType::DownhillSkiing => 61,
+            WorkoutActivityType::Flexibility => 62,
+            WorkoutActivityType::HighIntensityIntervalTraining => 63,
+            WorkoutActivityType::JumpRope => 64,
+            WorkoutActivityType::Kickboxing => 65,
+            WorkoutActivityType::Pilates => 66,
+            WorkoutActivityType::Snowboarding => 67,
+            WorkoutActivityType::Stairs => 68,
+            WorkoutActivityType::StepTraining => 69,
+            WorkoutActivityType::WheelchairWalkPace => 70,
+            WorkoutActivityType::WheelchairRunPace => 71,
+            WorkoutActivityType::TaiChi => 72,
+            WorkoutActivityType::MixedCardio => 73,
+            WorkoutActivityType::HandCycling => 74,
+            WorkoutActivityType::DiscSports => 75,
+            WorkoutActivityType::FitnessGaming => 76,
+            WorkoutActivityType::CardioDance => 77,
+            WorkoutActivityType::SocialDance => 78,
+            WorkoutActivityType::Pickleball => 79,
+            WorkoutActivityType::Cooldown => 80,
+            WorkoutActivityType::SwimBikeRun => 82,
+            WorkoutActivityType::Transition => 83,
+            WorkoutActivityType::UnderwaterDiving => 84,
+            WorkoutActivityType::Other => 3000,
+            WorkoutActivityType::Unknown(code) => code,
+        }
+    }
+
+    /// The `HKWorkoutActivityType...` identifier used in export.xml; unknown
+    /// codes have none.
+    pub fn identifier(self) -> Option<&'static str> {
+        let identifier = match self {
+            WorkoutActivityType::AmericanFootball => "HKWorkoutActivityTypeAmericanFootball",
+            WorkoutActivityType::Archery => "HKWorkoutActivityTypeArchery",
+            WorkoutActivityType::AustralianFootball => "HKWorkoutActivityTypeAustralianFootball",
+            WorkoutActivityType::Badminton => "HKWorkoutActivityTypeBadminton",
+            WorkoutActivityType::Baseball => "HKWorkoutActivityTypeBaseball",
+            WorkoutActivityType::Basketball => "HKWorkoutActivityTypeBasketball",
+            WorkoutActivityType::Bowling => "HKWorkoutActivityTypeBowling",
+            WorkoutActivityType::Boxing => "HKWorkoutActivityTypeBoxing",
+            WorkoutActivityType::Climbing => "HKWorkoutActivityTypeClimbing",
+            WorkoutActivityType::Cricket => "HKWorkoutActivityTypeCricket",
+            WorkoutActivityType::CrossTraining => "HKWorkoutActivityTypeCrossTraining",
+            WorkoutActivityType::Curling => "HKWorkoutActivityTypeCurling",
+            WorkoutActivityType::Cycling => "HKWorkoutActivityTypeCycling",
+            WorkoutActivityType::Dance => "HKWorkoutActivityTypeDance",
+            WorkoutActivityType::DanceInspiredTraining => {
+                "HKWorkoutActivityTypeDanceInspiredTraining"
+            }
+            WorkoutActivityType::Elliptical => "HKWorkoutActivityTypeElliptical",
+            WorkoutActivityType::EquestrianSports => "HKWorkoutActivityTypeEquestrianSports",
+            WorkoutActivityType::Fencing => "HKWorkoutActivityTypeFencing",
+            WorkoutActivityType::Fishing => "HKWorkoutActivityTypeFishing",
+            WorkoutActivityType::FunctionalStrengthTraining => {
+                "HKWorkoutActivityTypeFunctionalStrengthTraining"
+            }
+            WorkoutActivityType::Golf => "HKWorkoutActivityTypeGolf",
+            WorkoutActivityType::Gymnastics => "HKWorkoutActivityTypeGymnastics",
+            WorkoutActivityType::Handball => "HKWorkoutActivityTypeHandball",
+            WorkoutActivityType::Hiking => "HKWorkoutActivityTypeHiking",
+            WorkoutActivityType::Hockey => "HKWorkoutActivityTypeHockey",
+            WorkoutActivityType::Hunting => "HKWorkoutActivityTypeHunting",
+            WorkoutActivityType::Lacrosse => "HKWorkoutActivityTypeLacrosse",
+            WorkoutActivityType::MartialArts => "HKWorkoutActivityTypeMartialArts",
+            WorkoutActivityType::MindAndBody => "HKWorkoutActivityTypeMindAndBody",
+            WorkoutActivityType::MixedMetabolicCardioTraining => {
+                "HKWorkoutActivityTypeMixedMetabolicCardioTraining"
+            }
+            WorkoutActivityType::PaddleSports => "HKWorkoutActivityTypePaddleSports",
+            WorkoutActivityType::Play => "HKWorkoutActivityTypePlay",
+            WorkoutActivityType::PreparationAndRecovery => {
+                "HKWorkoutActivityTypePreparationAndRecovery"
+            }
+            WorkoutActivityType::Racquetball => "HKWorkoutActivityTypeRacquetball",
+            WorkoutActivityType::Rowing => "HKWorkoutActivityTypeRowing",
+            WorkoutActivityType::Rugby => "HKWorkoutActivityTypeRugby",
+            WorkoutActivityType::Running => "HKWorkoutActivityTypeRunning",
+            WorkoutActivityType::Sailing => "HKWorkoutActivityTypeSailing",
+            WorkoutActivityType::SkatingSports => "HKWorkoutActivityTypeSkatingSports",
+            WorkoutActivityType::SnowSports => "HKWorkoutActivityTypeSnowSports",
+            WorkoutActivityType::Soccer => "HKWorkoutActivityTypeSoccer",
+            WorkoutActivityType::Softball => "HKWorkoutActivityTypeSoftball",
+            WorkoutActivityType::Squash => "HKWorkoutActivityTypeSquash",
+            WorkoutActivityType::StairClimbing => "HKWorkoutActivityTypeStairClimbing",
+            WorkoutActivityType::SurfingSports => "HKWorkoutActivityTypeSurfingSports",
+            WorkoutActivityType::Swimming => "HKWorkoutActivityTypeSwimming",
+            WorkoutActivityType::TableTennis => "HKWorkoutActivityTypeTableTennis",
+            WorkoutActivityType::Tennis => "HKWorkoutActivityTypeTennis",
+            WorkoutActivityType::TrackAndField => "HKWorkoutActivityTypeTrackAndField",
+            WorkoutActivityType::TraditionalStrengthTraining => {
+                "HKWorkoutActivityTypeTraditionalStrengthTraining"
+            }
+            WorkoutActivityType::Volleyball => "HKWorkoutActivityTypeVolleyball",
+            WorkoutActivityType::Walking => "HKWorkoutActivityTypeWalking",
+            WorkoutActivityType::WaterFitness => "HKWorkoutActivityTypeWaterFitness",
+            WorkoutActivityType::WaterPolo => "HKWorkoutActivityTypeWaterPolo",
+            WorkoutActivityType::WaterSports => "HKWorkoutActivityTypeWaterSports",
+            WorkoutActivityType::Wrestling => "HKWorkoutActivityTypeWrestling",
+            WorkoutActivityType::Yoga => "HKWorkoutActivityTypeYoga",
+            WorkoutActivityType::Barre => "HKWorkoutActivityTypeBarre",
+            WorkoutActivityType::CoreTraining => "HKWorkoutActivityTypeCoreTraining",
+            WorkoutActivityType::CrossCountrySkiing => "HKWorkoutActivityTypeCrossCountrySkiing",
+            WorkoutActivityType::DownhillSkiing => "HKWorkoutActivityTypeDownhillSkiing",
+            WorkoutActivityType::Flexibility => "HKWorkoutActivityTypeFlexibility",
+            WorkoutActivityType::HighIntensityIntervalTraining => {
+                "HKWorkoutActivityTypeHighIntensityIntervalTraining"
+            }
+            WorkoutActivityType::JumpRope => "HKWorkoutActivityTypeJumpRope",
+            WorkoutActivityType::Kickboxing => "HKWorkoutActivityTypeKickboxing",
+            WorkoutActivityType::Pilates => "HKWorkoutActivityTypePilates",
+            WorkoutActivityType::Snowboarding => "HKWorkoutActivityTypeSnowboarding",
+            WorkoutActivityType::Stairs => "HKWorkoutActivityTypeStairs",
+            WorkoutActivityType::StepTraining => "HKWorkoutActivityTypeStepTraining",
+            WorkoutActivityType::WheelchairWalkPace => "HKWorkoutActivityTypeWheelchairWalkPace",
+            WorkoutActivityType::WheelchairRunPace => "HKWorkoutActivityTypeWheelchairRunPace",
+            WorkoutActivityType::TaiChi => "HKWorkoutActivityTypeTaiChi",
+            WorkoutActivityType::MixedCardio => "HKWorkoutActivityTypeMixedCardio",
+            WorkoutActivityType::HandCycling => "HKWorkoutActivityTypeHandCycling",
+            WorkoutActivityType::DiscSports => "HKWorkoutActivityTypeDiscSports",
+            WorkoutActivityType::FitnessGaming => "HKWorkoutActivityTypeFitnessGaming",
+            WorkoutActivityType::CardioDance => "HKWorkoutActivityTypeCardioDance",
+            WorkoutActivityType::SocialDance => "HKWorkoutActivityTypeSocialDance",
+            WorkoutActivityType::Pickleball => "HKWorkoutActivityTypePickleball",
+            WorkoutActivityType::Cooldown => "HKWorkoutActivityTypeCooldown",
+            WorkoutActivityType::SwimBikeRun => "HKWorkoutActivityTypeSwimBikeRun",
+            WorkoutActivityType::Transition => "HKWorkoutActivityTypeTransition",
+            WorkoutActivityType::UnderwaterDiving => "HKWorkoutActivityTypeUnderwaterDiving",
+            WorkoutActivityType::Other => "HKWorkoutActivityTypeOther",
+            WorkoutActivityType::Unknown(_) => return None,
+        };
+        Some(identifier)
+    }
+
+    pub fn category(self) -> WorkoutCategory {
+        match self {
+            WorkoutActivityType::Cycling
+            | WorkoutActivityType::Dance
+            | WorkoutActivityType::DanceInspiredTraining
+            | WorkoutActivityType::Elliptical
+            | WorkoutActivityType::Hiking
+            | WorkoutActivityType::MixedMetabolicCardioTraining
+            | WorkoutActivityType::Rowing
+            | WorkoutActivityType::Running
+            | WorkoutActivityType::StairClimbing
+            | WorkoutActivityType::TrackAndField
+            | WorkoutActivityType::Walking
+            | WorkoutActivityType::HighIntensityIntervalTraining
+            | WorkoutActivityType::JumpRope
+            | WorkoutActivityType::Kickboxing
+            | WorkoutActivityType::Stairs
+            | WorkoutActivityType::StepTraining
+            | WorkoutActivityType::WheelchairWalkPace
+            | WorkoutActivityType::WheelchairRunPace
+            | WorkoutActivityType::MixedCardio
+            | WorkoutActivityType::HandCycling
+            | WorkoutActivityType::FitnessGaming
+            | WorkoutActivityType::CardioDance
+            | WorkoutActivityType::SocialDance
+            | WorkoutActivityType::SwimBikeRun => WorkoutCategory::Cardio,
+            WorkoutActivityType::CrossTraining
+            | WorkoutActivityType::FunctionalStrengthTraining
+            | WorkoutActivityType::TraditionalStrengthTraining
+            | WorkoutActivityType::Barre
+            | WorkoutActivityType::CoreTraining
+            | WorkoutActivityType::Climbing
+            | WorkoutActivityType::Gymnastics => WorkoutCategory::Strength,
+            WorkoutActivityType::PaddleSports
+            | WorkoutActivityType::Sailing
+            | WorkoutActivityType::SurfingSports
+            | WorkoutActivityType::Swimming
+            | WorkoutActivityType::WaterFitness
+            | WorkoutActivityType::WaterPolo
+            | WorkoutActivityType::WaterSports
+            | WorkoutActivityType::UnderwaterDiving => WorkoutCategory::Water,
+            WorkoutActivityType::Curling
+            | WorkoutActivityType::SkatingSports
+            | WorkoutActivityType::SnowSports
+            | WorkoutActivityType::CrossCountrySkiing
+            | WorkoutActivityType::DownhillSkiing
+            | WorkoutActivityType::Snowboarding => WorkoutCategory::Winter,
+            WorkoutActivityType::AmericanFootball
+            | WorkoutActivityType::AustralianFootball
+            | WorkoutActivityType::Baseball
+            | WorkoutActivityType::Basketball
+            | WorkoutActivityType::Cricket
+            | WorkoutActivityType::Handball
+            | WorkoutActivityType::Hockey
+            | WorkoutActivityType::Lacrosse
+            | WorkoutActivityType::Rugby
+            | WorkoutActivityType::Soccer
+            | WorkoutActivityType::Softball
+            | WorkoutActivityType::Volleyball
+            | WorkoutActivityType::DiscSports => WorkoutCategory::TeamSports,
+            WorkoutActivityType::Badminton
+            | WorkoutActivityType::Racquetball
+            | WorkoutActivityType::Squash
+            | WorkoutActivityType::TableTennis
+            | WorkoutActivityType::Tennis
+            | WorkoutActivityType::Pickleball => WorkoutCategory::Racket,
+            WorkoutActivityType::MindAndBody
+            | WorkoutActivityType::Yoga
+            | WorkoutActivityType::Pilates
+            | WorkoutActivityType::TaiChi
+            | WorkoutActivityType::Flexibility
+            | WorkoutActivityType::PreparationAndRecovery
+            | WorkoutActivityType::Cooldown => WorkoutCategory::MindAndBody,
+            _ => WorkoutCategory::Other,
+        }
+    }
+}
+
+/// Accepts `HKWorkoutActivityType` identifiers (`HKWorkoutActivityTypeRunning`),
+/// display names (`Mind and Body`, case-insensitive), numeric codes and the
+/// `Unknown(81)` form produced by `Display`.
+impl FromStr for WorkoutActivityType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(code) = s.parse::<u32>() {
+            return Ok(WorkoutActivityType::from_u32(code));
+        }
+        if let Some(code) = s
+            .strip_prefix("Unknown(")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .and_then(|code| code.parse::<u32>().ok())
+        {
+            return Ok(WorkoutActivityType::from_u32(code));
+        }
+
+        let name = s.strip_prefix(IDENTIFIER_PREFIX).unwrap_or(s);
+        WorkoutActivityType::all()
+            .find(|activity| {
+                activity
+                    .identifier()
+                    .and_then(|identifier| identifier.strip_prefix(IDENTIFIER_PREFIX))
+                    .is_some_and(|suffix| suffix.eq_ignore_ascii_case(name))
+                    || activity.to_string().eq_ignore_ascii_case(name)
+            })
+            .ok_or_else(|| format!("unknown workout activity type '{}'", s))
+    }
+}
+
+impl Serialize for WorkoutActivityType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.identifier() {
+            Some(identifier) => serializer.serialize_str(identifier),
+            None => serializer.collect_str(self),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for WorkoutActivityType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        raw.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for WorkoutCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            WorkoutCategory::Cardio => "Cardio",
+            WorkoutCategory::Strength => "Strength",
+            WorkoutCategory::Water => "Water",
+            WorkoutCategory::Winter => "Winter",
+            WorkoutCategory::TeamSports => "Team Sports",
+            WorkoutCategory::Racket => "Racket",
+            WorkoutCategory::MindAndBody => "Mind and Body",
+            WorkoutCategory::Other => "Other",
+        };
+        write!(f, "{}", name)
+    }
+}
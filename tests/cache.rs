@@ -0,0 +1,254 @@
+mod common;
+
+use apple_health_export_parser_rs::HealthRecord;
+use apple_health_export_parser_rs::cache::{self, CACHE_FORMAT_VERSION, cache_key};
+use common::{ExportBuilder, HEART_RATE, STEP_COUNT, days_ago, parse, type_filter};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+fn config(parts: &[&str]) -> Vec<String> {
+    parts.iter().map(|part| part.to_string()).collect()
+}
+
+fn entry_path(dir: &Path, key: &str) -> PathBuf {
+    dir.join(format!("{}.bin", key))
+}
+
+#[test]
+fn keys_change_with_the_export_and_every_config_part() {
+    let key = cache_key("abc", &config(&["lenient", "include=*"]));
+
+    assert_eq!(key, cache_key("abc", &config(&["lenient", "include=*"])));
+    assert_ne!(key, cache_key("abd", &config(&["lenient", "include=*"])));
+    assert_ne!(
+        key,
+        cache_key("abc", &config(&["lenient", "include=Heart*"]))
+    );
+    assert_ne!(key, cache_key("abc", &config(&["lenient"])));
+    // Parts are length-prefixed, so moving text between them is not a collision.
+    assert_ne!(key, cache_key("abc", &config(&["lenientinclude=*", ""])));
+}
+
+#[test]
+fn saved_records_load_back() {
+    let dir = tempfile::tempdir().unwrap();
+    let xml = ExportBuilder::new()
+        .record(HEART_RATE, "count/min", "72", days_ago(1))
+        .record(STEP_COUNT, "count", "1200", days_ago(1))
+        .xml();
+    let records = parse(&xml, &type_filter(&["*"], &[])).records;
+
+    cache::save(dir.path(), "key", &records).unwrap();
+    let loaded: Vec<HealthRecord> = cache::load(dir.path(), "key").unwrap();
+
+    assert_eq!(format!("{:?}", loaded), format!("{:?}", records));
+    assert!(cache::load::<Vec<HealthRecord>>(dir.path(), "other").is_none());
+}
+
+#[test]
+fn corrupt_entries_are_ignored() {
+    let dir = tempfile::tempdir().unwrap();
+    cache::save(dir.path(), "key", &vec!["a".to_string(); 100]).unwrap();
+
+    let path = entry_path(dir.path(), "key");
+    let bytes = fs::read(&path).unwrap();
+    fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+    assert!(cache::load::<Vec<String>>(dir.path(), "key").is_none());
+
+    fs::write(&path, b"not a cache entry").unwrap();
+    assert!(cache::load::<Vec<String>>(dir.path(), "key").is_none());
+}
+
+#[test]
+fn entries_from_another_format_version_are_ignored() {
+    let dir = tempfile::tempdir().unwrap();
+    cache::save(dir.path(), "key", &vec!["a".to_string()]).unwrap();
+
+    let path = entry_path(dir.path(), "key");
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[4..8].copy_from_slice(&(CACHE_FORMAT_VERSION - 1).to_le_bytes());
+    fs::write(&path, bytes).unwrap();
+
+    assert!(cache::load::<Vec<String>>(dir.path(), "key").is_none());
+}
+
+/// Writes an entry of `size` bytes last used `age` ago.
+fn write_entry(dir: &Path, name: &str, size: usize, age: Duration) {
+    let path = dir.join(name);
+    fs::write(&path, vec![0u8; size]).unwrap();
+    File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(SystemTime::now() - age)
+        .unwrap();
+}
+
+/// A file name shaped like a cache key, ordered by `n`.
+fn key(n: u8, extension: &str) -> String {
+    format!("{:064x}.{}", n, extension)
+}
+
+fn names(dir: &Path, legacy_dir: Option<&Path>) -> Vec<String> {
+    cache::list_entries(dir, legacy_dir)
+        .unwrap()
+        .iter()
+        .map(|entry| {
+            entry
+                .path
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect()
+}
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+#[test]
+fn entries_are_listed_oldest_first() {
+    let dir = tempfile::tempdir().unwrap();
+    write_entry(dir.path(), &key(1, "bin"), 10, HOUR);
+    write_entry(dir.path(), &key(2, "bin"), 10, 2 * HOUR);
+    write_entry(dir.path(), "notes.txt", 10, 4 * HOUR);
+
+    assert_eq!(names(dir.path(), None), [key(2, "bin"), key(1, "bin")]);
+    assert!(
+        cache::list_entries(&dir.path().join("missing"), None)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn clear_removes_every_entry() {
+    let dir = tempfile::tempdir().unwrap();
+    write_entry(dir.path(), &key(1, "bin"), 10, HOUR);
+    write_entry(dir.path(), &key(2, "bin"), 20, HOUR);
+    write_entry(dir.path(), "notes.txt", 30, HOUR);
+
+    assert_eq!(cache::clear(dir.path(), None).unwrap(), (2, 30));
+    assert!(names(dir.path(), None).is_empty());
+    assert!(dir.path().join("notes.txt").exists());
+}
+
+#[test]
+fn files_not_named_like_entries_survive_clear_and_prune() {
+    let dir = tempfile::tempdir().unwrap();
+    write_entry(dir.path(), &key(1, "bin"), 10, 48 * HOUR);
+    write_entry(dir.path(), "export.xml", 100, 48 * HOUR);
+    write_entry(dir.path(), "notes.bin", 100, 48 * HOUR);
+    // Raw XML entries only count in the legacy dir.
+    write_entry(dir.path(), &key(2, "xml"), 100, 48 * HOUR);
+
+    assert_eq!(
+        cache::prune(dir.path(), None, Some(0), Some(HOUR)).unwrap(),
+        (1, 10)
+    );
+    assert_eq!(cache::clear(dir.path(), None).unwrap(), (0, 0));
+    for name in ["export.xml", "notes.bin", &key(2, "xml")] {
+        assert!(dir.path().join(name).exists(), "{}", name);
+    }
+}
+
+#[test]
+fn prune_evicts_least_recently_used_entries_to_fit() {
+    let dir = tempfile::tempdir().unwrap();
+    write_entry(dir.path(), &key(1, "bin"), 100, 3 * HOUR);
+    write_entry(dir.path(), &key(2, "bin"), 100, 2 * HOUR);
+    write_entry(dir.path(), &key(3, "bin"), 100, HOUR);
+
+    assert_eq!(
+        cache::prune(dir.path(), None, Some(250), None).unwrap(),
+        (1, 100)
+    );
+    assert_eq!(names(dir.path(), None), [key(2, "bin"), key(3, "bin")]);
+
+    // Loading an entry makes it the most recently used.
+    let _ = cache::load::<Vec<String>>(dir.path(), &format!("{:064x}", 2));
+    assert_eq!(
+        cache::prune(dir.path(), None, Some(100), None).unwrap(),
+        (1, 100)
+    );
+    assert_eq!(names(dir.path(), None), [key(2, "bin")]);
+}
+
+#[test]
+fn prune_removes_expired_entries_before_sizing() {
+    let dir = tempfile::tempdir().unwrap();
+    write_entry(dir.path(), &key(1, "bin"), 100, 48 * HOUR);
+    write_entry(dir.path(), &key(2, "bin"), 100, 2 * HOUR);
+    write_entry(dir.path(), &key(3, "bin"), 100, HOUR);
+
+    assert_eq!(
+        cache::prune(dir.path(), None, Some(200), Some(24 * HOUR)).unwrap(),
+        (1, 100)
+    );
+    assert_eq!(names(dir.path(), None), [key(2, "bin"), key(3, "bin")]);
+    assert_eq!(cache::prune(dir.path(), None, None, None).unwrap(), (0, 0));
+}
+
+#[test]
+fn entries_left_in_the_legacy_temp_dir_are_listed_cleared_and_pruned() {
+    let dir = tempfile::tempdir().unwrap();
+    let (current, legacy) = (dir.path().join("cache"), dir.path().join("tmp"));
+    fs::create_dir_all(&current).unwrap();
+    fs::create_dir_all(&legacy).unwrap();
+    write_entry(&current, &key(1, "bin"), 100, HOUR);
+    write_entry(&legacy, &key(2, "xml"), 1000, 3 * HOUR);
+    write_entry(&legacy, &key(3, "xml"), 1000, 2 * HOUR);
+    write_entry(&legacy, "export.xml", 1000, 4 * HOUR);
+
+    assert_eq!(
+        names(&current, Some(&legacy)),
+        [key(2, "xml"), key(3, "xml"), key(1, "bin")]
+    );
+    // Without the legacy dir only the current one is touched.
+    assert_eq!(names(&current, None), [key(1, "bin")]);
+
+    assert_eq!(
+        cache::prune(&current, Some(&legacy), Some(1100), None).unwrap(),
+        (1, 1000)
+    );
+    assert!(!legacy.join(key(2, "xml")).exists());
+    assert_eq!(cache::clear(&current, Some(&legacy)).unwrap(), (2, 1100));
+    assert!(names(&current, Some(&legacy)).is_empty());
+    assert!(legacy.join("export.xml").exists());
+}
+
+#[test]
+fn the_legacy_temp_dir_is_only_cleaned_up_when_it_differs() {
+    let dir = tempfile::tempdir().unwrap();
+    assert_eq!(
+        cache::default_legacy_dir(dir.path()),
+        Some(cache::legacy_cache_dir())
+    );
+    assert_eq!(cache::default_legacy_dir(&cache::legacy_cache_dir()), None);
+}
+
+#[test]
+fn sizes_parse_with_binary_units() {
+    assert_eq!(cache::parse_size("1048576"), Ok(1 << 20));
+    assert_eq!(cache::parse_size("512k"), Ok(512 << 10));
+    assert_eq!(cache::parse_size("500M"), Ok(500 << 20));
+    assert_eq!(cache::parse_size("2GiB"), Ok(2 << 30));
+    assert_eq!(cache::parse_size("1.5 GB"), Ok(3 << 29));
+    assert_eq!(cache::parse_size(" 10b "), Ok(10));
+    assert!(cache::parse_size("10X").is_err());
+    assert!(cache::parse_size("M").is_err());
+    assert!(cache::parse_size("").is_err());
+}
+
+#[test]
+fn ages_parse_with_a_unit() {
+    assert_eq!(cache::parse_age("30s"), Ok(Duration::from_secs(30)));
+    assert_eq!(cache::parse_age("5m"), Ok(Duration::from_secs(300)));
+    assert_eq!(cache::parse_age("12h"), Ok(12 * HOUR));
+    assert_eq!(cache::parse_age("30d"), Ok(720 * HOUR));
+    assert_eq!(cache::parse_age("2w"), Ok(336 * HOUR));
+    assert!(cache::parse_age("30").is_err());
+    assert!(cache::parse_age("1y").is_err());
+    assert!(cache::parse_age("1.5d").is_err());
+}
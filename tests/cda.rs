@@ -0,0 +1,102 @@
+mod common;
+
+use apple_health_export_parser_rs::cda::write_cda;
+use apple_health_export_parser_rs::input::{Document, ExportInput};
+use apple_health_export_parser_rs::progress::Progress;
+use apple_health_export_parser_rs::{HealthRecord, parse_export};
+use common::{ExportBuilder, HEART_RATE, STEP_COUNT, days_ago, parse, type_filter};
+use std::fs;
+use std::path::Path;
+
+fn records() -> Vec<HealthRecord> {
+    let xml = ExportBuilder::new()
+        .record(HEART_RATE, "count/min", "72", days_ago(2))
+        .record(STEP_COUNT, "count", "1200", days_ago(2))
+        .record_with_metadata(
+            "HKQuantityTypeIdentifierAppleExerciseTime",
+            "min",
+            "30",
+            days_ago(1),
+            &[("HKPhysicalEffortEstimationType", "1")],
+        )
+        .record(
+            "HKCategoryTypeIdentifierSleepAnalysis",
+            "",
+            "HKCategoryValueSleepAnalysisAsleepCore",
+            days_ago(1),
+        )
+        .xml()
+        // Category records carry no unit in real exports.
+        .replace(r#"unit="" "#, "")
+        // Escaped once in each document, never twice.
+        .replacen(
+            r#"sourceName="Apple Watch""#,
+            r#"sourceName="Watch &lt;Ultra&gt; &amp; co""#,
+            1,
+        );
+    parse(&xml, &type_filter(&["*"], &[])).records
+}
+
+fn parse_cda_file(path: &Path, include: &[&str]) -> Vec<HealthRecord> {
+    let input = ExportInput::detect(path, Document::Cda).unwrap();
+    parse_export(
+        &input,
+        Document::Cda,
+        &type_filter(include, &[]),
+        false,
+        false,
+        &Progress::new(true, false),
+    )
+    .unwrap()
+    .records
+}
+
+#[test]
+fn written_records_parse_back_unchanged() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("export_cda.xml");
+    let records = records();
+
+    write_cda(&records, &path).unwrap();
+    let parsed = parse_cda_file(&path, &["*"]);
+
+    assert_eq!(
+        records[0].source_name.as_deref(),
+        Some("Watch <Ultra> & co")
+    );
+    assert!(
+        fs::read_to_string(&path)
+            .unwrap()
+            .contains("Watch &lt;Ultra&gt; &amp; co")
+    );
+    assert_eq!(format!("{:?}", parsed), format!("{:?}", records));
+}
+
+#[test]
+fn parsed_cda_records_are_filtered_by_type() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("export_cda.xml");
+    write_cda(&records(), &path).unwrap();
+
+    let parsed = parse_cda_file(&path, &[HEART_RATE]);
+
+    assert_eq!(parsed.len(), 1);
+    assert_eq!(parsed[0].value.as_deref(), Some("72"));
+}
+
+#[test]
+fn types_with_a_loinc_equivalent_are_coded() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("export_cda.xml");
+    write_cda(&records(), &path).unwrap();
+    let cda = fs::read_to_string(&path).unwrap();
+
+    assert!(cda.contains(r#"<code code="8867-4" codeSystem="2.16.840.1.113883.6.1" codeSystemName="LOINC" displayName="Heart rate"/>"#));
+    assert!(cda.contains(r#"<value xsi:type="PQ" value="72" unit="/min"/>"#));
+    assert!(cda.contains(r#"<code code="55423-8" "#));
+    assert!(cda.contains(r#"<value xsi:type="PQ" value="1200" unit="{count}"/>"#));
+    assert!(cda.contains(
+        "<code nullFlavor=\"OTH\"><originalText>HKQuantityTypeIdentifierAppleExerciseTime</originalText></code>"
+    ));
+    assert!(cda.contains(r#"<value xsi:type="ST">HKCategoryValueSleepAnalysisAsleepCore</value>"#));
+}
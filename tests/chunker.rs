@@ -0,0 +1,121 @@
+use apple_health_export_parser_rs::chunker::{par_children, par_elements};
+use std::fmt::Write as _;
+
+/// Records in every position the chunker has to get right, repeated so that
+/// any split point lands on each of them for some number of parts.
+fn document() -> String {
+    let mut xml =
+        String::from("<HealthData locale=\"en_GB\">\n <ExportDate value=\"2026-01-01\"/>\n");
+    for i in 0..50 {
+        let _ = writeln!(xml, r#" <Record type="HeartRate" value="{i}"/>"#);
+        let _ = writeln!(
+            xml,
+            r#" <Correlation type="BloodPressure" note="a > b">
+  <Record type="Systolic" value="{i}">
+   <MetadataEntry key="HKActivityType" value="<Record type='HeartRate' value='x'/>"/>
+  </Record>
+  <Record type="Diastolic" value="{i}"/>
+ </Correlation>"#
+        );
+        let _ = writeln!(xml, r#" <!-- <Record type="Comment" value="{i}"/> -->"#);
+        let _ = writeln!(xml, r#" <Note><![CDATA[<Record type="Cdata">]]></Note>"#);
+        let _ = writeln!(
+            xml,
+            r#" <Workout name='<Record '><Event type="pause"/></Workout>"#
+        );
+    }
+    xml.push_str("</HealthData>\n");
+    xml
+}
+
+fn elements(xml: &str, parts: usize) -> Vec<(usize, &str)> {
+    par_elements(xml, 0, "Record", parts, |elements| {
+        elements.collect::<Vec<_>>()
+    })
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+#[test]
+fn only_real_records_are_found_whole_and_in_order() {
+    let xml = document();
+    let found = elements(&xml, 1);
+
+    assert_eq!(found.len(), 150);
+    for (start, element) in &found {
+        assert!(xml[*start..].starts_with(element));
+        assert!(element.starts_with("<Record type=\""));
+        assert!(element.ends_with("/>") || element.ends_with("</Record>"));
+    }
+    assert!(found[1].1.contains("<MetadataEntry"));
+    assert!(found.windows(2).all(|pair| pair[0].0 < pair[1].0));
+}
+
+#[test]
+fn any_number_of_parts_finds_the_same_records() {
+    let xml = document();
+    let expected = elements(&xml, 1);
+
+    for parts in 2..=300 {
+        assert_eq!(elements(&xml, parts), expected, "{} parts", parts);
+    }
+}
+
+#[test]
+fn record_missing_its_closing_tag_ends_at_the_next_record() {
+    let xml = "<HealthData>\n <Record value=\"1\">\n  <MetadataEntry key=\"a\" value=\"b\"/>\n <Record value=\"2\"/>\n</HealthData>\n";
+    let found = elements(xml, 4);
+
+    assert_eq!(found.len(), 2);
+    assert!(found[0].1.trim_end().ends_with("value=\"b\"/>"));
+    assert_eq!(found[1].1, "<Record value=\"2\"/>");
+}
+
+fn unknown_children(xml: &str, parts: usize) -> Vec<(usize, &str)> {
+    let children = par_children(xml, 0, parts, |name| {
+        name != "Record" && name != "Correlation"
+    });
+    assert!(children.balanced);
+    assert_eq!(children.depth, -1);
+    children.elements
+}
+
+#[test]
+fn only_direct_children_of_the_root_are_found() {
+    let xml = document();
+    let found = unknown_children(&xml, 1);
+
+    let names: Vec<&str> = found.iter().map(|(_, name)| *name).collect();
+    assert_eq!(names.len(), 101);
+    assert_eq!(names[0], "ExportDate");
+    assert!(names[1..].chunks(2).all(|pair| pair == ["Note", "Workout"]));
+    for (start, name) in &found {
+        assert!(xml[start + 1..].starts_with(name));
+    }
+}
+
+#[test]
+fn any_number_of_parts_finds_the_same_children() {
+    let xml = document();
+    let expected = unknown_children(&xml, 1);
+
+    for parts in 2..=300 {
+        assert_eq!(unknown_children(&xml, parts), expected, "{} parts", parts);
+    }
+}
+
+#[test]
+fn depth_tells_an_unclosed_child_from_a_closed_root() {
+    let extra_end = "<HealthData>\n <Me></Me></Me>\n <Foo/>\n</HealthData>\n";
+    let unclosed = "<HealthData>\n <Me>\n <Foo/>\n</HealthData>\n";
+    let truncated = "<HealthData>\n <Me></Me>\n";
+
+    assert!(!par_children(extra_end, 0, 3, |_| true).balanced);
+    let unclosed = par_children(unclosed, 0, 3, |_| true);
+    assert!(unclosed.balanced);
+    assert_eq!(unclosed.depth, 0);
+    let truncated = par_children(truncated, 0, 3, |_| true);
+    assert!(truncated.balanced);
+    assert_eq!(truncated.depth, 0);
+}
@@ -0,0 +1,252 @@
+//! Builds small synthetic exports shaped like Apple's, so the tests never
+//! depend on a real (and personal) Health export.
+
+#![allow(dead_code)]
+
+use apple_health_export_parser_rs::progress::{Progress, Unit};
+use apple_health_export_parser_rs::type_filter::{FilterConfig, TypeFilter};
+use apple_health_export_parser_rs::{ParsedRecords, parse_records};
+use chrono::{DateTime, Duration, Utc};
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+pub const HEART_RATE: &str = "HKQuantityTypeIdentifierHeartRate";
+pub const STEP_COUNT: &str = "HKQuantityTypeIdentifierStepCount";
+pub const SYSTOLIC: &str = "HKQuantityTypeIdentifierBloodPressureSystolic";
+pub const DIASTOLIC: &str = "HKQuantityTypeIdentifierBloodPressureDiastolic";
+pub const BLOOD_PRESSURE: &str = "HKCorrelationTypeIdentifierBloodPressure";
+
+const PROLOG: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE HealthData [
+<!-- HealthKit Export Version: 14 -->
+<!ELEMENT HealthData (ExportDate,Me,(Record|Correlation|Workout|ActivitySummary)*)>
+<!ATTLIST HealthData
+  locale CDATA #REQUIRED
+>
+<!ELEMENT Record ((MetadataEntry|HeartRateVariabilityMetadataList)*)>
+<!ATTLIST Record
+  type          CDATA #REQUIRED
+  unit          CDATA #IMPLIED
+  value         CDATA #IMPLIED
+  sourceName    CDATA #REQUIRED
+  startDate     CDATA #REQUIRED
+  endDate       CDATA #REQUIRED
+>
+]>
+"#;
+
+/// Apple's date format, e.g. `2026-10-01 08:30:00 +0000`.
+pub fn apple_date(date: DateTime<Utc>) -> String {
+    date.format("%Y-%m-%d %H:%M:%S %z").to_string()
+}
+
+/// Noon `days` days ago, inside the parser's 12-month window for small
+/// values and outside it for anything over a year.
+pub fn days_ago(days: i64) -> DateTime<Utc> {
+    let today = Utc::now()
+        .date_naive()
+        .and_hms_opt(12, 0, 0)
+        .unwrap()
+        .and_utc();
+    today - Duration::days(days)
+}
+
+/// A synthetic `export.xml`, plus the GPX files its workouts refer to.
+#[derive(Default)]
+pub struct ExportBuilder {
+    body: String,
+    routes: Vec<(String, String)>,
+}
+
+impl ExportBuilder {
+    pub fn new() -> Self {
+        ExportBuilder::default()
+    }
+
+    pub fn record(
+        &mut self,
+        record_type: &str,
+        unit: &str,
+        value: &str,
+        start: DateTime<Utc>,
+    ) -> &mut Self {
+        self.record_with_metadata(record_type, unit, value, start, &[])
+    }
+
+    pub fn record_with_metadata(
+        &mut self,
+        record_type: &str,
+        unit: &str,
+        value: &str,
+        start: DateTime<Utc>,
+        metadata: &[(&str, &str)],
+    ) -> &mut Self {
+        write_record(
+            &mut self.body,
+            " ",
+            record_type,
+            unit,
+            value,
+            start,
+            metadata,
+        );
+        self
+    }
+
+    /// A correlation such as blood pressure, holding one record per
+    /// `(type, unit, value)`.
+    pub fn correlation(
+        &mut self,
+        correlation_type: &str,
+        start: DateTime<Utc>,
+        records: &[(&str, &str, &str)],
+    ) -> &mut Self {
+        let date = apple_date(start);
+        let _ = writeln!(
+            self.body,
+            r#" <Correlation type="{}" sourceName="Omron" creationDate="{date}" startDate="{date}" endDate="{date}">"#,
+            correlation_type
+        );
+        for (record_type, unit, value) in records {
+            write_record(&mut self.body, "  ", record_type, unit, value, start, &[]);
+        }
+        self.body.push_str(" </Correlation>\n");
+        self
+    }
+
+    /// A workout with statistics and, if `with_route`, a GPX route file.
+    pub fn workout(
+        &mut self,
+        activity: &str,
+        start: DateTime<Utc>,
+        minutes: i64,
+        with_route: bool,
+    ) -> &mut Self {
+        let end = start + Duration::minutes(minutes);
+        let (start_date, end_date) = (apple_date(start), apple_date(end));
+        let _ = writeln!(
+            self.body,
+            r#" <Workout workoutActivityType="{activity}" duration="{minutes}" durationUnit="min" sourceName="Apple Watch" creationDate="{end_date}" startDate="{start_date}" endDate="{end_date}">"#
+        );
+        let _ = writeln!(
+            self.body,
+            r#"  <MetadataEntry key="HKIndoorWorkout" value="0"/>
+  <WorkoutStatistics type="HKQuantityTypeIdentifierActiveEnergyBurned" startDate="{start_date}" endDate="{end_date}" sum="{}" unit="kcal"/>"#,
+            minutes * 10
+        );
+        if with_route {
+            let name = format!("route_{}.gpx", start.format("%Y-%m-%d_%H.%M"));
+            let _ = writeln!(
+                self.body,
+                r#"  <WorkoutRoute sourceName="Apple Watch" creationDate="{end_date}" startDate="{start_date}" endDate="{end_date}">
+   <FileReference path="/workout-routes/{name}"/>
+  </WorkoutRoute>"#
+            );
+            self.routes.push((name, gpx(start, minutes)));
+        }
+        self.body.push_str(" </Workout>\n");
+        self
+    }
+
+    pub fn xml(&self) -> String {
+        format!(
+            "{PROLOG}<HealthData locale=\"en_GB\">\n <ExportDate value=\"{}\"/>\n <Me HKCharacteristicTypeIdentifierBiologicalSex=\"HKBiologicalSexNotSet\"/>\n{}</HealthData>\n",
+            apple_date(Utc::now()),
+            self.body
+        )
+    }
+
+    /// Writes a bare `export.xml` into `dir`.
+    pub fn write_xml(&self, dir: &Path) -> PathBuf {
+        let path = dir.join("export.xml");
+        fs::write(&path, self.xml()).unwrap();
+        path
+    }
+
+    /// Writes `export.zip` into `dir`, laid out like the archive the Health
+    /// app shares.
+    pub fn write_zip(&self, dir: &Path) -> PathBuf {
+        let path = dir.join("export.zip");
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        let options = SimpleFileOptions::default();
+
+        zip.start_file("apple_health_export/export.xml", options)
+            .unwrap();
+        zip.write_all(self.xml().as_bytes()).unwrap();
+        for (name, contents) in &self.routes {
+            zip.start_file(
+                format!("apple_health_export/workout-routes/{name}"),
+                options,
+            )
+            .unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    }
+}
+
+fn write_record(
+    out: &mut String,
+    indent: &str,
+    record_type: &str,
+    unit: &str,
+    value: &str,
+    start: DateTime<Utc>,
+    metadata: &[(&str, &str)],
+) {
+    let date = apple_date(start);
+    let _ = write!(
+        out,
+        r#"{indent}<Record type="{record_type}" sourceName="Apple Watch" sourceVersion="11.0" unit="{unit}" creationDate="{date}" startDate="{date}" endDate="{date}" value="{value}""#
+    );
+    if metadata.is_empty() {
+        out.push_str("/>\n");
+        return;
+    }
+    out.push_str(">\n");
+    for (key, value) in metadata {
+        let _ = writeln!(
+            out,
+            r#"{indent} <MetadataEntry key="{key}" value="{value}"/>"#
+        );
+    }
+    let _ = writeln!(out, "{indent}</Record>");
+}
+
+fn gpx(start: DateTime<Utc>, minutes: i64) -> String {
+    let mut gpx = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<gpx version=\"1.1\" creator=\"Apple Health Export\">\n <trk><trkseg>\n",
+    );
+    for minute in 0..=minutes {
+        let time = start + Duration::minutes(minute);
+        let _ = writeln!(
+            gpx,
+            r#"  <trkpt lon="-0.{:04}" lat="51.5{:03}"><ele>12.0</ele><time>{}</time></trkpt>"#,
+            1000 + minute,
+            minute,
+            time.format("%Y-%m-%dT%H:%M:%SZ")
+        );
+    }
+    gpx.push_str(" </trkseg></trk>\n</gpx>\n");
+    gpx
+}
+
+pub fn type_filter(include: &[&str], exclude: &[&str]) -> TypeFilter {
+    let config = FilterConfig {
+        include: include.iter().map(|s| s.to_string()).collect(),
+        exclude: exclude.iter().map(|s| s.to_string()).collect(),
+    };
+    TypeFilter::from_config(&config).unwrap()
+}
+
+/// Runs `parse_records` in strict mode without progress output.
+pub fn parse(xml: &str, type_filter: &TypeFilter) -> ParsedRecords {
+    let progress = Progress::new(true, false);
+    let task = progress.task("parsing", None, Unit::Records);
+    parse_records(xml, type_filter, &task, false).unwrap()
+}
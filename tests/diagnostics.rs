@@ -0,0 +1,164 @@
+mod common;
+
+use apple_health_export_parser_rs::diagnostics::{Diagnostics, DocumentIssue, SkipReason};
+use apple_health_export_parser_rs::input::{Document, ExportInput};
+use apple_health_export_parser_rs::progress::Progress;
+use apple_health_export_parser_rs::{ParsedExport, parse_export};
+use common::{ExportBuilder, HEART_RATE, STEP_COUNT, days_ago, parse, type_filter};
+
+/// Twelve heart rate and three step records, plus two old heart rate
+/// records and one without a type.
+fn export_xml() -> String {
+    let mut export = ExportBuilder::new();
+    for i in 0..12 {
+        export.record(HEART_RATE, "count/min", &format!("{}", 60 + i), days_ago(2));
+    }
+    for i in 0..3 {
+        export.record(STEP_COUNT, "count", &format!("{}", 100 * i), days_ago(2));
+    }
+    export
+        .record(HEART_RATE, "count/min", "70", days_ago(800))
+        .record(HEART_RATE, "count/min", "71", days_ago(900))
+        .record("", "count", "1", days_ago(1));
+    export.xml().replace(r#"type="" "#, "")
+}
+
+#[test]
+fn every_scanned_element_is_parsed_or_skipped_for_a_reason() {
+    let parsed = parse(&export_xml(), &type_filter(&[STEP_COUNT], &[]));
+    let diagnostics = &parsed.diagnostics;
+
+    assert_eq!(diagnostics.scanned, 18);
+    assert_eq!(diagnostics.parsed, 3);
+    assert_eq!(diagnostics.skipped_count(), 15);
+
+    let by_type = &diagnostics.skipped[&SkipReason::FilteredByType];
+    assert_eq!(by_type.count, 14);
+    assert_eq!(by_type.by_type.get(HEART_RATE), Some(&14));
+    assert_eq!(diagnostics.skipped[&SkipReason::MissingType].count, 1);
+    assert!(
+        !diagnostics
+            .skipped
+            .contains_key(&SkipReason::FilteredByDate)
+    );
+}
+
+#[test]
+fn only_the_first_few_samples_are_kept_with_their_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let xml = export_xml();
+    let path = dir.path().join("export.xml");
+    std::fs::write(&path, &xml).unwrap();
+    let input = ExportInput::detect(&path, Document::Export).unwrap();
+
+    let parsed = parse_export(
+        &input,
+        Document::Export,
+        &type_filter(&[STEP_COUNT], &[]),
+        false,
+        false,
+        &Progress::new(true, false),
+    )
+    .unwrap();
+
+    let by_type = &parsed.diagnostics.skipped[&SkipReason::FilteredByType];
+    assert_eq!(by_type.count, 14);
+    assert_eq!(by_type.samples.len(), 5);
+    let lines: Vec<&str> = xml.lines().collect();
+    for (i, sample) in by_type.samples.iter().enumerate() {
+        let line = lines[sample.line as usize - 1].trim();
+        assert!(line.contains(&format!("value=\"{}\"", 60 + i)), "{}", line);
+        // Excerpts are cut short, before the value on these long lines.
+        assert!(line.starts_with(&sample.excerpt));
+        assert!(sample.excerpt.len() <= 200);
+        assert_eq!(sample.record_type.as_deref(), Some(HEART_RATE));
+    }
+    assert!(
+        by_type
+            .samples
+            .windows(2)
+            .all(|w| w[0].offset < w[1].offset)
+    );
+}
+
+#[test]
+fn merging_sums_counts_and_keeps_the_earliest_samples() {
+    let filter = type_filter(&[STEP_COUNT], &[]);
+    let mut merged = Diagnostics::default();
+    let first = parse(&export_xml(), &filter).diagnostics;
+    let second = parse(&export_xml(), &filter).diagnostics;
+    let first_samples: Vec<u64> = first.skipped[&SkipReason::FilteredByType]
+        .samples
+        .iter()
+        .map(|sample| sample.offset)
+        .collect();
+
+    merged.merge(first);
+    merged.merge(second);
+
+    assert_eq!(merged.scanned, 36);
+    assert_eq!(merged.parsed, 6);
+    let by_type = &merged.skipped[&SkipReason::FilteredByType];
+    assert_eq!(by_type.count, 28);
+    assert_eq!(by_type.by_type.get(HEART_RATE), Some(&28));
+    let offsets: Vec<u64> = by_type.samples.iter().map(|sample| sample.offset).collect();
+    assert_eq!(offsets, first_samples);
+}
+
+/// Parses `bytes` as a bare export.xml in lenient mode.
+fn parse_lenient(bytes: &[u8]) -> ParsedExport {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("export.xml");
+    std::fs::write(&path, bytes).unwrap();
+    let input = ExportInput::detect(&path, Document::Export).unwrap();
+    parse_export(
+        &input,
+        Document::Export,
+        &type_filter(&[STEP_COUNT], &[]),
+        false,
+        true,
+        &Progress::new(true, false),
+    )
+    .unwrap()
+}
+
+fn assert_every_scanned_element_is_accounted_for(diagnostics: &Diagnostics) {
+    assert_eq!(
+        diagnostics.scanned,
+        diagnostics.parsed + diagnostics.skipped_count()
+    );
+}
+
+#[test]
+fn truncation_is_a_document_problem_not_a_skipped_element() {
+    let xml = export_xml();
+    let cut = xml.rfind("<Record").unwrap() + 30;
+    let parsed = parse_lenient(&xml.as_bytes()[..cut]);
+    let diagnostics = &parsed.diagnostics;
+
+    assert_every_scanned_element_is_accounted_for(diagnostics);
+    assert_eq!(diagnostics.scanned, 17);
+    assert_eq!(diagnostics.document[&DocumentIssue::Truncated].count, 1);
+    assert!(parsed.errors.is_empty());
+}
+
+#[test]
+fn records_with_invalid_utf8_are_kept_and_flagged() {
+    let xml = export_xml();
+    let at = xml.find(r#"value="200""#).unwrap() + 8;
+    let mut bytes = xml.into_bytes();
+    bytes[at] = 0xff;
+    let parsed = parse_lenient(&bytes);
+    let diagnostics = &parsed.diagnostics;
+
+    assert_every_scanned_element_is_accounted_for(diagnostics);
+    assert_eq!(diagnostics.parsed, 3);
+    assert_eq!(parsed.records.len(), 3);
+    assert_eq!(parsed.records[2].value.as_deref(), Some("2\u{fffd}0"));
+    let bad_encoding = &diagnostics.document[&DocumentIssue::BadEncoding];
+    assert_eq!(bad_encoding.count, 1);
+    let sample = &bad_encoding.samples[0];
+    assert!(sample.excerpt.contains(STEP_COUNT), "{}", sample.excerpt);
+    assert!(sample.detail.contains("ff"), "{}", sample.detail);
+    assert!(parsed.errors.is_empty());
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use apple_health_export_parser_rs::HealthRecord;
+use apple_health_export_parser_rs::diff::{
+    added_records, counts_by_type, is_output_json, is_state_file, load_output_json, load_state,
+    record_hash, record_hashes, save_state,
+};
+use common::{ExportBuilder, HEART_RATE, STEP_COUNT, days_ago, parse, type_filter};
+use std::fs;
+
+fn previous() -> Vec<HealthRecord> {
+    let xml = ExportBuilder::new()
+        .record(HEART_RATE, "count/min", "70", days_ago(3))
+        .record(HEART_RATE, "count/min", "72", days_ago(2))
+        .record(STEP_COUNT, "count", "900", days_ago(2))
+        .xml();
+    parse(&xml, &type_filter(&["*"], &[])).records
+}
+
+/// The previous export minus its oldest record, plus two new ones.
+fn current() -> Vec<HealthRecord> {
+    let xml = ExportBuilder::new()
+        .record(HEART_RATE, "count/min", "72", days_ago(2))
+        .record(STEP_COUNT, "count", "900", days_ago(2))
+        .record(HEART_RATE, "count/min", "75", days_ago(1))
+        .record(STEP_COUNT, "count", "1200", days_ago(1))
+        .xml();
+    parse(&xml, &type_filter(&["*"], &[])).records
+}
+
+fn values(records: &[HealthRecord]) -> Vec<&str> {
+    records
+        .iter()
+        .map(|record| record.value.as_deref().unwrap())
+        .collect()
+}
+
+#[test]
+fn only_records_missing_from_the_previous_export_are_added() {
+    let added = added_records(&record_hashes(&previous()), current());
+
+    // The record dropped from the current export is not reported either.
+    assert_eq!(values(&added), ["75", "1200"]);
+    assert_eq!(
+        counts_by_type(&added).into_iter().collect::<Vec<_>>(),
+        [(HEART_RATE.to_string(), 1), (STEP_COUNT.to_string(), 1)]
+    );
+    assert!(added_records(&record_hashes(&current()), current()).is_empty());
+}
+
+#[test]
+fn hashes_ignore_which_export_a_record_came_from() {
+    let mut records = previous();
+    let hash = record_hash(&records[0]);
+    records[0].export_id = Some("0123456789abcdef".into());
+
+    assert_eq!(record_hash(&records[0]), hash);
+    assert_ne!(record_hash(&records[1]), hash);
+}
+
+#[test]
+fn state_files_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state/previous.state");
+    let keys = record_hashes(&previous());
+
+    save_state(&path, &keys).unwrap();
+
+    assert!(is_state_file(&path));
+    assert_eq!(load_state(&path).unwrap(), keys);
+    let bytes = fs::read(&path).unwrap();
+    save_state(&path, &keys).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), bytes);
+}
+
+#[test]
+fn other_files_are_not_taken_for_state_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let json = dir.path().join("output.json");
+    fs::write(&json, serde_json::to_string(&previous()).unwrap()).unwrap();
+    let export = ExportBuilder::new().write_xml(dir.path());
+
+    assert!(!is_state_file(&json));
+    assert!(!is_state_file(&export));
+    assert!(is_output_json(&json));
+    assert!(!is_output_json(&export));
+    assert!(load_state(&json).is_err());
+    assert_eq!(
+        record_hashes(&load_output_json(&json).unwrap()),
+        record_hashes(&previous())
+    );
+}
+
+#[test]
+fn state_files_from_another_format_version_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("previous.state");
+    save_state(&path, &record_hashes(&previous())).unwrap();
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[4] += 1;
+    fs::write(&path, bytes).unwrap();
+
+    let error = load_state(&path).unwrap_err().to_string();
+    assert!(error.contains("incompatible version"), "{}", error);
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use apple_health_export_parser_rs::generate::{GeneratorConfig, generate_export, generate_xml};
+use apple_health_export_parser_rs::input::{Document, ExportInput};
+use apple_health_export_parser_rs::parse_export;
+use apple_health_export_parser_rs::progress::Progress;
+use common::{HEART_RATE, type_filter};
+use std::fs::File;
+use zip::ZipArchive;
+
+#[test]
+fn generated_export_parses_with_every_record_and_route() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("export.zip");
+    let config = GeneratorConfig {
+        days: 14,
+        seed: 7,
+        ..GeneratorConfig::default()
+    };
+    let progress = Progress::new(true, false);
+    let generated = generate_export(&config, &path, &progress).unwrap();
+
+    let input = ExportInput::detect(&path, Document::Export).unwrap();
+    let parsed = parse_export(
+        &input,
+        Document::Export,
+        &type_filter(&["*"], &[]),
+        false,
+        false,
+        &progress,
+    )
+    .unwrap();
+    assert_eq!(parsed.records.len() as u64, generated.records);
+    assert_eq!(
+        parsed.unknown.counts.get("Workout").copied().unwrap_or(0) as u64,
+        generated.workouts
+    );
+    assert!(
+        parsed
+            .records
+            .iter()
+            .filter(|record| record.record_type.as_ref().unwrap().identifier() == HEART_RATE)
+            .count()
+            > 14 * 200
+    );
+
+    let archive = ZipArchive::new(File::open(&path).unwrap()).unwrap();
+    let routes = archive
+        .file_names()
+        .filter(|name| name.starts_with("apple_health_export/workout-routes/"))
+        .count();
+    assert_eq!(routes as u64, generated.routes);
+}
+
+#[test]
+fn same_seed_gives_the_same_export_and_size_extends_the_span() {
+    let config = GeneratorConfig {
+        days: 3,
+        seed: 42,
+        ..GeneratorConfig::default()
+    };
+    let (mut first, mut second) = (Vec::new(), Vec::new());
+    generate_xml(&config, &mut first).unwrap();
+    generate_xml(&config, &mut second).unwrap();
+    assert_eq!(first, second);
+
+    let sized = GeneratorConfig {
+        target_size: Some(4 << 20),
+        ..config
+    };
+    let generated = generate_xml(&sized, std::io::sink()).unwrap();
+    assert!(generated.last_day - generated.first_day > chrono::Duration::days(3));
+    assert!(generated.xml_bytes > 2 << 20 && generated.xml_bytes < 8 << 20);
+}
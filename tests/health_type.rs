@@ -0,0 +1,133 @@
+use apple_health_export_parser_rs::health_type::{
+    Aggregation, CATALOGUE, RecordType, TypeGroup, file_stem, in_group, lookup,
+};
+
+#[test]
+fn every_catalogued_identifier_round_trips_through_its_variant() {
+    for health_type in CATALOGUE {
+        let record_type = RecordType::from_identifier(health_type.identifier);
+
+        assert!(
+            !matches!(record_type, RecordType::Other(_)),
+            "{} has no variant",
+            health_type.identifier
+        );
+        assert_eq!(record_type.identifier(), health_type.identifier);
+        assert_eq!(
+            record_type.health_type().map(|t| t.name),
+            Some(health_type.name)
+        );
+        assert_eq!(
+            RecordType::from_identifier(record_type.short_name()),
+            record_type
+        );
+    }
+}
+
+#[test]
+fn uncatalogued_identifiers_are_kept_verbatim() {
+    let identifier = "HKQuantityTypeIdentifierFutureThing";
+    let record_type: RecordType = identifier.parse().unwrap();
+
+    assert_eq!(record_type, RecordType::Other(identifier.to_string()));
+    assert_eq!(record_type.to_string(), identifier);
+    assert_eq!(record_type.short_name(), "FutureThing");
+    assert!(record_type.health_type().is_none());
+    assert_eq!(file_stem(&record_type), "future_thing");
+}
+
+#[test]
+fn record_types_serialize_as_their_identifier() {
+    let types = vec![
+        RecordType::HeartRate,
+        RecordType::SleepAnalysis,
+        RecordType::BloodPressure,
+        RecordType::Other("HKDataTypeFuture".to_string()),
+    ];
+
+    let json = serde_json::to_string(&types).unwrap();
+    assert_eq!(
+        json,
+        r#"["HKQuantityTypeIdentifierHeartRate","HKCategoryTypeIdentifierSleepAnalysis","HKCorrelationTypeIdentifierBloodPressure","HKDataTypeFuture"]"#
+    );
+    assert_eq!(
+        serde_json::from_str::<Vec<RecordType>>(&json).unwrap(),
+        types
+    );
+
+    let bytes = bincode::serialize(&types).unwrap();
+    assert_eq!(
+        bincode::deserialize::<Vec<RecordType>>(&bytes).unwrap(),
+        types
+    );
+    assert_eq!(file_stem(&RecordType::HeartRate), "heart_rate");
+}
+
+#[test]
+fn every_catalogued_type_is_in_its_group_and_only_there() {
+    for group in TypeGroup::ALL {
+        for health_type in in_group(*group) {
+            assert_eq!(health_type.group, *group, "{}", health_type.identifier);
+        }
+    }
+    let grouped: usize = TypeGroup::ALL.iter().map(|g| in_group(*g).count()).sum();
+    assert_eq!(grouped, CATALOGUE.len());
+}
+
+#[test]
+fn breathing_types_are_respiratory() {
+    for name in [
+        "RespiratoryRate",
+        "OxygenSaturation",
+        "PeakExpiratoryFlowRate",
+    ] {
+        assert_eq!(
+            RecordType::from_identifier(name).group(),
+            TypeGroup::Respiratory,
+            "{}",
+            name
+        );
+    }
+    assert_eq!(
+        lookup("HKQuantityTypeIdentifierBodyMass").unwrap().group,
+        TypeGroup::Body
+    );
+    assert_eq!(
+        RecordType::from_identifier("HKQuantityTypeIdentifierFutureThing").group(),
+        TypeGroup::Other
+    );
+}
+
+#[test]
+fn groups_parse_from_their_names() {
+    for group in TypeGroup::ALL {
+        assert_eq!(TypeGroup::from_name(&group.to_string()), Some(*group));
+    }
+    assert_eq!(TypeGroup::from_name("HEART"), Some(TypeGroup::Heart));
+    assert_eq!(TypeGroup::from_name("cardio"), None);
+}
+
+#[test]
+fn quantity_types_aggregate_and_others_do_not() {
+    let aggregation = |name| RecordType::from_identifier(name).aggregation();
+    assert_eq!(aggregation("StepCount"), Some(Aggregation::Cumulative));
+    assert_eq!(aggregation("HeartRate"), Some(Aggregation::Discrete));
+    assert_eq!(aggregation("SleepAnalysis"), None);
+    assert_eq!(aggregation("BloodPressure"), None);
+}
+
+#[test]
+fn types_order_by_identifier() {
+    let mut types: Vec<RecordType> = CATALOGUE
+        .iter()
+        .map(|t| RecordType::from_identifier(t.identifier))
+        .chain([RecordType::Other("HKDataTypeFuture".to_string())])
+        .collect();
+    types.sort();
+
+    let identifiers: Vec<&str> = types.iter().map(RecordType::identifier).collect();
+    let mut expected = identifiers.clone();
+    expected.sort();
+    assert_eq!(identifiers, expected);
+    assert_eq!(identifiers[0], "HKCategoryTypeIdentifierAbdominalCramps");
+}
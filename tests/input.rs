@@ -0,0 +1,121 @@
+mod common;
+
+use apple_health_export_parser_rs::error::ParseError;
+use apple_health_export_parser_rs::input::{Document, ExportInput};
+use common::{ExportBuilder, HEART_RATE, days_ago};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+const CDA: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ClinicalDocument xmlns="urn:hl7-org:v3">
+</ClinicalDocument>
+"#;
+
+fn export_xml() -> String {
+    ExportBuilder::new()
+        .record(HEART_RATE, "count/min", "72", days_ago(1))
+        .xml()
+}
+
+fn write_zip(path: &Path, entries: &[(&str, &str)]) {
+    let mut zip = ZipWriter::new(File::create(path).unwrap());
+    for (name, contents) in entries {
+        zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(contents.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+fn write_file(path: &Path, contents: &str) -> PathBuf {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+    path.to_path_buf()
+}
+
+fn zip_entry(input: ExportInput) -> String {
+    match input {
+        ExportInput::Zip { entry, .. } => entry,
+        ExportInput::Xml(path) => panic!("detected {} as XML", path.display()),
+    }
+}
+
+fn xml_path(input: ExportInput) -> PathBuf {
+    match input {
+        ExportInput::Xml(path) => path,
+        ExportInput::Zip { path, .. } => panic!("detected {} as a zip", path.display()),
+    }
+}
+
+#[test]
+fn zips_are_recognised_by_content_not_extension() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("Export.data");
+    write_zip(&path, &[("apple_health_export/export.xml", &export_xml())]);
+
+    let entry = zip_entry(ExportInput::detect(&path, Document::Export).unwrap());
+
+    assert_eq!(entry, "apple_health_export/export.xml");
+}
+
+#[test]
+fn localized_folders_and_names_are_found_by_content() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("export.zip");
+    write_zip(
+        &path,
+        &[
+            ("__MACOSX/exportar/._Exportar.xml", &export_xml()),
+            ("exportar/notas.xml", "<notes/>"),
+            ("exportar/Exportar_cda.xml", CDA),
+            ("exportar/Exportar.xml", &export_xml()),
+        ],
+    );
+
+    let export = zip_entry(ExportInput::detect(&path, Document::Export).unwrap());
+    let cda = zip_entry(ExportInput::detect(&path, Document::Cda).unwrap());
+
+    assert_eq!(export, "exportar/Exportar.xml");
+    assert_eq!(cda, "exportar/Exportar_cda.xml");
+}
+
+#[test]
+fn the_canonical_name_is_preferred() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(&dir.path().join("a/backup.xml"), &export_xml());
+    let canonical = write_file(&dir.path().join("Health-Export/export.xml"), &export_xml());
+
+    let path = xml_path(ExportInput::detect(dir.path(), Document::Export).unwrap());
+
+    assert_eq!(path, canonical);
+}
+
+#[test]
+fn bare_xml_files_are_taken_as_given() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(&dir.path().join("anything.txt"), &export_xml());
+
+    assert_eq!(
+        xml_path(ExportInput::detect(&path, Document::Export).unwrap()),
+        path
+    );
+}
+
+#[test]
+fn directories_are_searched_only_a_few_levels_deep() {
+    let dir = tempfile::tempdir().unwrap();
+    let shallow = write_file(&dir.path().join("a/b/c/export.xml"), &export_xml());
+    assert_eq!(
+        xml_path(ExportInput::detect(dir.path(), Document::Export).unwrap()),
+        shallow
+    );
+
+    fs::remove_file(&shallow).unwrap();
+    write_file(&dir.path().join("a/b/c/d/export.xml"), &export_xml());
+    match ExportInput::detect(dir.path(), Document::Export) {
+        Err(ParseError::MissingExport { document, .. }) => assert_eq!(document, "export.xml"),
+        other => panic!("expected MissingExport, got {:?}", other),
+    }
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use apple_health_export_parser_rs::input::{Document, ExportInput};
+use apple_health_export_parser_rs::merge::{merge_exports, record_key};
+use apple_health_export_parser_rs::progress::Progress;
+use apple_health_export_parser_rs::{ParsedExport, parse_export};
+use common::{ExportBuilder, HEART_RATE, STEP_COUNT, apple_date, days_ago, type_filter};
+use smallstr::SmallString;
+
+fn mood(days: i64, valence: &str) -> String {
+    let date = apple_date(days_ago(days));
+    format!(
+        r#" <StateOfMind kind="HKStateOfMindKindDailyMood" valence="{valence}" sourceName="iPhone" startDate="{date}" endDate="{date}">
+  <Label value="HKStateOfMindLabelCalm"/>
+ </StateOfMind>
+"#
+    )
+}
+
+/// Parses `export` as if it had been read from the export tagged `id`.
+fn parsed(export: &ExportBuilder, moods: &str, id: &str) -> ParsedExport {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("export.xml");
+    let xml = export
+        .xml()
+        .replace("</HealthData>", &format!("{}</HealthData>", moods));
+    std::fs::write(&path, xml).unwrap();
+    let input = ExportInput::detect(&path, Document::Export).unwrap();
+
+    let mut parsed = parse_export(
+        &input,
+        Document::Export,
+        &type_filter(&["*"], &[]),
+        false,
+        false,
+        &Progress::new(true, false),
+    )
+    .unwrap();
+    for record in &mut parsed.records {
+        record.export_id = Some(SmallString::from(id));
+    }
+    parsed
+}
+
+#[test]
+fn records_match_on_measurement_regardless_of_export() {
+    let mut export = ExportBuilder::new();
+    export
+        .record_with_metadata(
+            HEART_RATE,
+            "count/min",
+            "72",
+            days_ago(1),
+            &[("HKPhysicalEffortEstimationType", "1")],
+        )
+        .record(HEART_RATE, "count/min", "72", days_ago(1))
+        .record(HEART_RATE, "count/min", "73", days_ago(1))
+        .record(STEP_COUNT, "count", "72", days_ago(1));
+    let a = parsed(&export, "", "a").records;
+    let b = parsed(&export, "", "b").records;
+
+    assert_eq!(record_key(&a[0]), record_key(&b[0]));
+    assert_eq!(record_key(&a[0]), record_key(&a[1]));
+    assert_ne!(record_key(&a[1]), record_key(&a[2]));
+    assert_ne!(record_key(&a[1]), record_key(&a[3]));
+}
+
+#[test]
+fn overlapping_exports_keep_the_first_copy_of_each_record() {
+    let mut older = ExportBuilder::new();
+    older
+        .record(HEART_RATE, "count/min", "70", days_ago(3))
+        .record(HEART_RATE, "count/min", "72", days_ago(2));
+    let mut newer = ExportBuilder::new();
+    newer
+        .record(HEART_RATE, "count/min", "72", days_ago(2))
+        .record(STEP_COUNT, "count", "1200", days_ago(1));
+
+    let merged = merge_exports(vec![
+        parsed(&older, &mood(2, "0.5"), "older"),
+        parsed(
+            &newer,
+            &format!("{}{}", mood(2, "0.5"), mood(1, "0.1")),
+            "newer",
+        ),
+    ]);
+
+    assert_eq!(merged.duplicates, 1);
+    let records: Vec<(&str, &str)> = merged
+        .export
+        .records
+        .iter()
+        .map(|record| {
+            (
+                record.value.as_deref().unwrap(),
+                record.export_id.as_deref().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        records,
+        [("70", "older"), ("72", "older"), ("1200", "newer")]
+    );
+    let valences: Vec<Option<f64>> = merged
+        .export
+        .state_of_mind
+        .iter()
+        .map(|mood| mood.valence)
+        .collect();
+    assert_eq!(valences, [Some(0.5), Some(0.1)]);
+}
+
+#[test]
+fn repeats_within_one_export_are_kept() {
+    let mut older = ExportBuilder::new();
+    older
+        .record(HEART_RATE, "count/min", "72", days_ago(2))
+        .record(HEART_RATE, "count/min", "72", days_ago(2));
+    let mut newer = ExportBuilder::new();
+    newer
+        .record(HEART_RATE, "count/min", "72", days_ago(2))
+        .record(STEP_COUNT, "count", "1200", days_ago(1))
+        .record(STEP_COUNT, "count", "1200", days_ago(1));
+
+    let merged = merge_exports(vec![
+        parsed(&older, "", "older"),
+        parsed(&newer, "", "newer"),
+    ]);
+
+    assert_eq!(merged.duplicates, 1);
+    let ids: Vec<&str> = merged
+        .export
+        .records
+        .iter()
+        .map(|record| record.export_id.as_deref().unwrap())
+        .collect();
+    assert_eq!(ids, ["older", "older", "newer", "newer"]);
+}
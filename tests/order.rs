@@ -0,0 +1,199 @@
+mod common;
+
+use apple_health_export_parser_rs::HealthRecord;
+use apple_health_export_parser_rs::health_type::RecordType;
+use apple_health_export_parser_rs::order::{RecordOrder, sort_records};
+use apple_health_export_parser_rs::progress::Progress;
+use chrono::Duration;
+use common::{ExportBuilder, HEART_RATE, STEP_COUNT, apple_date, days_ago, parse, type_filter};
+use smallstr::SmallString;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::HashMap;
+
+/// Counts the bytes the current thread has allocated, and the most it has
+/// held at once, so a test can measure what a call needs on top of its
+/// input.
+struct CountingAlloc;
+
+thread_local! {
+    static HELD: Cell<usize> = const { Cell::new(0) };
+    static PEAK: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = HELD.try_with(|held| {
+            held.set(held.get() + layout.size());
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(held.get())));
+        });
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = HELD.try_with(|held| held.set(held.get().saturating_sub(layout.size())));
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    // Counted as resizing in place, as the system allocator does when it
+    // shrinks, rather than as a new block next to the old one.
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = HELD.try_with(|held| {
+            held.set(held.get().saturating_sub(layout.size()) + new_size);
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(held.get())));
+        });
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+/// Bytes `f` holds at its peak beyond what was held when it was called.
+fn peak_extra(f: impl FnOnce()) -> usize {
+    let before = HELD.with(Cell::get);
+    PEAK.with(|peak| peak.set(before));
+    f();
+    PEAK.with(Cell::get) - before
+}
+
+/// Heart rate and steps interleaved, written newest first, with pairs of
+/// records that share a type and start date to show that ties keep their
+/// document order.
+fn records() -> Vec<HealthRecord> {
+    let mut builder = ExportBuilder::new();
+    for i in 0..20 {
+        let start = days_ago(1) - Duration::hours(i);
+        let record_type = if i % 3 == 0 { STEP_COUNT } else { HEART_RATE };
+        builder.record(record_type, "count", &format!("{}a", i), start);
+        if i % 4 == 0 {
+            builder.record(record_type, "count", &format!("{}b", i), start);
+        }
+    }
+    parse(&builder.xml(), &type_filter(&["*"], &[])).records
+}
+
+fn values(records: &[HealthRecord]) -> Vec<&str> {
+    records
+        .iter()
+        .map(|record| record.value.as_deref().unwrap())
+        .collect()
+}
+
+fn sorted(order: RecordOrder, memory_limit: u64) -> Vec<HealthRecord> {
+    let mut records = records();
+    sort_records(
+        &mut records,
+        order,
+        memory_limit,
+        &Progress::new(true, false),
+    )
+    .unwrap();
+    records
+}
+
+#[test]
+fn document_order_is_left_alone() {
+    assert_eq!(
+        values(&sorted(RecordOrder::Document, 0)),
+        values(&records())
+    );
+}
+
+#[test]
+fn start_order_is_stable() {
+    let records = sorted(RecordOrder::Start, u64::MAX);
+
+    assert_eq!(
+        values(&records),
+        [
+            "19a", "18a", "17a", "16a", "16b", "15a", "14a", "13a", "12a", "12b", "11a", "10a",
+            "9a", "8a", "8b", "7a", "6a", "5a", "4a", "4b", "3a", "2a", "1a", "0a", "0b",
+        ]
+    );
+}
+
+#[test]
+fn type_order_groups_types_then_sorts_by_start() {
+    let records = sorted(RecordOrder::Type, u64::MAX);
+
+    assert_eq!(
+        values(&records),
+        [
+            "19a", "17a", "16a", "16b", "14a", "13a", "11a", "10a", "8a", "8b", "7a", "5a", "4a",
+            "4b", "2a", "1a", "18a", "15a", "12a", "12b", "9a", "6a", "3a", "0a", "0b",
+        ]
+    );
+}
+
+#[test]
+fn sorting_in_spilled_runs_matches_sorting_in_memory() {
+    for order in [RecordOrder::Start, RecordOrder::Type] {
+        let in_memory = sorted(order, u64::MAX);
+        for memory_limit in [1, 2000] {
+            assert_eq!(
+                values(&sorted(order, memory_limit)),
+                values(&in_memory),
+                "{} order with {} bytes",
+                order,
+                memory_limit
+            );
+        }
+    }
+}
+
+/// `count` heart rate records, newest first.
+fn many_records(count: usize) -> Vec<HealthRecord> {
+    (0..count)
+        .map(|i| {
+            let start = apple_date(days_ago(1) - Duration::minutes(i as i64));
+            HealthRecord {
+                record_type: Some(RecordType::from_identifier(HEART_RATE)),
+                unit: Some(SmallString::from("count/min")),
+                value: Some(SmallString::from(i.to_string().as_str())),
+                start_date: Some(SmallString::from(start.as_str())),
+                end_date: Some(SmallString::from(start.as_str())),
+                metadata: HashMap::new(),
+                source_name: Some(SmallString::from("Watch")),
+                export_id: None,
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn spilling_bounds_the_memory_the_sort_needs_on_top_of_the_records() {
+    const COUNT: usize = 50_000;
+    const LIMIT: u64 = 1 << 20;
+    let progress = Progress::new(true, false);
+
+    let mut in_memory = many_records(COUNT);
+    let in_memory_extra = peak_extra(|| {
+        sort_records(&mut in_memory, RecordOrder::Start, u64::MAX, &progress).unwrap()
+    });
+    let mut spilled = many_records(COUNT);
+    let spilled_extra =
+        peak_extra(|| sort_records(&mut spilled, RecordOrder::Start, LIMIT, &progress).unwrap());
+
+    assert_eq!(values(&spilled), values(&in_memory));
+    assert_eq!(values(&spilled)[0], (COUNT - 1).to_string());
+    // About one run, plus the merge's read buffers and heads.
+    assert!(
+        spilled_extra < 2 * LIMIT as usize,
+        "spilling needed {} bytes on top of the records",
+        spilled_extra
+    );
+    assert!(
+        spilled_extra < in_memory_extra,
+        "spilling needed {} bytes, sorting in memory {}",
+        spilled_extra,
+        in_memory_extra
+    );
+}
+
+#[test]
+fn orders_parse_from_their_names() {
+    assert_eq!("Type".parse(), Ok(RecordOrder::Type));
+    assert_eq!("start".parse(), Ok(RecordOrder::Start));
+    assert!("date".parse::<RecordOrder>().is_err());
+}
@@ -0,0 +1,363 @@
+mod common;
+
+use apple_health_export_parser_rs::diagnostics::SkipReason;
+use apple_health_export_parser_rs::health_type::RecordType;
+use apple_health_export_parser_rs::progress::{Progress, Unit};
+use apple_health_export_parser_rs::{HealthRecord, parse_record_refs, parse_records};
+use common::{
+    BLOOD_PRESSURE, DIASTOLIC, ExportBuilder, HEART_RATE, STEP_COUNT, SYSTOLIC, days_ago, parse,
+    type_filter,
+};
+use std::borrow::Cow;
+
+fn types_and_values(
+    parsed: &apple_health_export_parser_rs::ParsedRecords,
+) -> Vec<(String, String)> {
+    parsed
+        .records
+        .iter()
+        .map(|record| {
+            (
+                record.record_type.as_ref().unwrap().to_string(),
+                record.value.as_deref().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn default_filter_keeps_recent_default_types_in_document_order() {
+    let xml = ExportBuilder::new()
+        .record(HEART_RATE, "count/min", "61", days_ago(3))
+        .record(STEP_COUNT, "count", "1200", days_ago(2))
+        .record(SYSTOLIC, "mmHg", "120", days_ago(2))
+        .record(HEART_RATE, "count/min", "62", days_ago(1))
+        .xml();
+
+    let parsed = parse(&xml, &type_filter(&[], &[]));
+
+    assert_eq!(
+        types_and_values(&parsed),
+        [
+            (HEART_RATE.to_string(), "61".to_string()),
+            (STEP_COUNT.to_string(), "1200".to_string()),
+            (HEART_RATE.to_string(), "62".to_string()),
+        ]
+    );
+    assert_eq!(parsed.records[0].record_type, Some(RecordType::HeartRate));
+    assert_eq!(parsed.records[0].unit.as_deref(), Some("count/min"));
+    assert_eq!(
+        parsed.records[0].source_name.as_deref(),
+        Some("Apple Watch")
+    );
+
+    let by_type = &parsed.diagnostics.skipped[&SkipReason::FilteredByType];
+    assert_eq!(by_type.count, 1);
+    assert_eq!(by_type.by_type.get(SYSTOLIC), Some(&1));
+    assert_eq!(parsed.diagnostics.scanned, 4);
+    assert_eq!(parsed.diagnostics.parsed, 3);
+}
+
+#[test]
+fn records_older_than_a_year_are_skipped() {
+    let xml = ExportBuilder::new()
+        .record(STEP_COUNT, "count", "10", days_ago(400))
+        .record(STEP_COUNT, "count", "20", days_ago(30))
+        .xml();
+
+    let parsed = parse(&xml, &type_filter(&[], &[]));
+
+    assert_eq!(
+        types_and_values(&parsed),
+        [(STEP_COUNT.to_string(), "20".to_string())]
+    );
+    let by_date = &parsed.diagnostics.skipped[&SkipReason::FilteredByDate];
+    assert_eq!(by_date.count, 1);
+    assert_eq!(by_date.by_type.get(STEP_COUNT), Some(&1));
+}
+
+#[test]
+fn include_groups_and_exclude_globs() {
+    let xml = ExportBuilder::new()
+        .record(HEART_RATE, "count/min", "70", days_ago(1))
+        .record(
+            "HKQuantityTypeIdentifierRestingHeartRate",
+            "count/min",
+            "50",
+            days_ago(1),
+        )
+        .record(STEP_COUNT, "count", "900", days_ago(1))
+        .xml();
+
+    let parsed = parse(&xml, &type_filter(&["@heart"], &["*Resting*"]));
+
+    assert_eq!(
+        types_and_values(&parsed),
+        [(HEART_RATE.to_string(), "70".to_string())]
+    );
+}
+
+#[test]
+fn records_inside_correlations_are_parsed() {
+    let xml = ExportBuilder::new()
+        .correlation(
+            BLOOD_PRESSURE,
+            days_ago(5),
+            &[(SYSTOLIC, "mmHg", "121"), (DIASTOLIC, "mmHg", "79")],
+        )
+        .record(HEART_RATE, "count/min", "64", days_ago(5))
+        .xml();
+
+    let parsed = parse(&xml, &type_filter(&["*BloodPressure*", HEART_RATE], &[]));
+
+    assert_eq!(
+        types_and_values(&parsed),
+        [
+            (SYSTOLIC.to_string(), "121".to_string()),
+            (DIASTOLIC.to_string(), "79".to_string()),
+            (HEART_RATE.to_string(), "64".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn tag_text_in_attribute_values_and_comments_is_not_a_record() {
+    let lookalike = "<Record type='HKQuantityTypeIdentifierHeartRate' value='999'/>";
+    let xml = ExportBuilder::new()
+        .record_with_metadata(
+            HEART_RATE,
+            "count/min",
+            "65",
+            days_ago(4),
+            &[("HKPhysicalEffortEstimationType", lookalike)],
+        )
+        .record(HEART_RATE, "count/min", "66", days_ago(4))
+        .xml()
+        .replace(
+            "</HealthData>",
+            &format!(" <!-- {} -->\n</HealthData>", lookalike),
+        );
+
+    let parsed = parse(&xml, &type_filter(&[HEART_RATE], &[]));
+
+    assert_eq!(
+        types_and_values(&parsed),
+        [
+            (HEART_RATE.to_string(), "65".to_string()),
+            (HEART_RATE.to_string(), "66".to_string()),
+        ]
+    );
+    assert_eq!(parsed.diagnostics.scanned, 2);
+    assert_eq!(
+        parsed.records[0].metadata["HKPhysicalEffortEstimationType"],
+        lookalike
+    );
+}
+
+#[test]
+fn activity_type_metadata_is_mapped_to_workout_names() {
+    let xml = ExportBuilder::new()
+        .record_with_metadata(
+            HEART_RATE,
+            "count/min",
+            "140",
+            days_ago(1),
+            &[
+                ("HKActivityType", "37"),
+                ("HKPhysicalEffortEstimationType", "1"),
+                ("HKMetadataKeyHeartRateMotionContext", "2"),
+            ],
+        )
+        .record_with_metadata(
+            HEART_RATE,
+            "count/min",
+            "120",
+            days_ago(1),
+            &[("HKActivityType", "81")],
+        )
+        .record_with_metadata(
+            HEART_RATE,
+            "count/min",
+            "100",
+            days_ago(1),
+            &[("HKActivityType", "HKWorkoutActivityTypeYoga")],
+        )
+        .xml();
+
+    let parsed = parse(&xml, &type_filter(&[], &[]));
+    let metadata = |i: usize, key: &str| {
+        parsed.records[i]
+            .metadata
+            .get(key)
+            .map(|value| value.to_string())
+    };
+
+    assert_eq!(metadata(0, "HKActivityType").as_deref(), Some("Running"));
+    assert_eq!(
+        metadata(0, "HKPhysicalEffortEstimationType").as_deref(),
+        Some("1")
+    );
+    assert_eq!(metadata(0, "HKMetadataKeyHeartRateMotionContext"), None);
+    assert_eq!(
+        metadata(1, "HKActivityType").as_deref(),
+        Some("Unknown(81)")
+    );
+    assert_eq!(metadata(2, "HKActivityType").as_deref(), Some("Yoga"));
+}
+
+#[test]
+fn record_refs_borrow_from_the_document_and_match_owned_records() {
+    let xml = ExportBuilder::new()
+        .record_with_metadata(
+            HEART_RATE,
+            "count/min",
+            "88",
+            days_ago(1),
+            &[
+                ("HKActivityType", "37"),
+                ("HKPhysicalEffortEstimationType", "1"),
+            ],
+        )
+        .record(STEP_COUNT, "count", "300", days_ago(1))
+        .record(SYSTOLIC, "mmHg", "118", days_ago(1))
+        .xml();
+    let type_filter = type_filter(&[], &[]);
+    let progress = Progress::new(true, false);
+    let task = progress.task("parsing", None, Unit::Records);
+
+    let refs = parse_record_refs(&xml, &type_filter, &task, false).unwrap();
+    let owned = parse(&xml, &type_filter);
+
+    assert_eq!(refs.records.len(), 2);
+    let first = &refs.records[0];
+    assert!(matches!(first.value, Some(Cow::Borrowed("88"))));
+    assert!(matches!(
+        first.source_name,
+        Some(Cow::Borrowed("Apple Watch"))
+    ));
+    assert_eq!(
+        first.metadata,
+        [
+            (
+                Cow::Borrowed("HKActivityType"),
+                Cow::Owned("Running".to_string())
+            ),
+            (
+                Cow::Borrowed("HKPhysicalEffortEstimationType"),
+                Cow::Borrowed("1")
+            ),
+        ]
+    );
+    assert_eq!(
+        refs.diagnostics.skipped_count(),
+        owned.diagnostics.skipped_count()
+    );
+
+    for (borrowed, owned) in refs.records.into_iter().zip(&owned.records) {
+        let converted = HealthRecord::from(borrowed);
+        assert_eq!(
+            serde_json::to_value(&converted).unwrap(),
+            serde_json::to_value(owned).unwrap()
+        );
+    }
+}
+
+#[test]
+fn entities_in_attributes_are_unescaped() {
+    let xml = ExportBuilder::new()
+        .record(HEART_RATE, "count/min", "88", days_ago(1))
+        .record(STEP_COUNT, "count", "300", days_ago(1))
+        .xml()
+        .replacen(
+            r#"sourceName="Apple Watch""#,
+            r#"sourceName="W &lt;x&gt; &amp; co""#,
+            1,
+        );
+    let type_filter = type_filter(&[], &[]);
+    let progress = Progress::new(true, false);
+    let task = progress.task("parsing", None, Unit::Records);
+
+    let refs = parse_record_refs(&xml, &type_filter, &task, false).unwrap();
+    let owned = parse(&xml, &type_filter);
+
+    assert_eq!(
+        refs.records[0].source_name,
+        Some(Cow::Owned("W <x> & co".to_string()))
+    );
+    // Values without entities still borrow from the document.
+    assert!(matches!(
+        refs.records[1].source_name,
+        Some(Cow::Borrowed("Apple Watch"))
+    ));
+    assert_eq!(owned.records[0].source_name.as_deref(), Some("W <x> & co"));
+}
+
+#[test]
+fn lenient_errors_carry_the_line_of_each_malformed_record() {
+    let mut builder = ExportBuilder::new();
+    for value in 0..6 {
+        builder.record(HEART_RATE, "count/min", &value.to_string(), days_ago(2));
+    }
+    let xml = builder
+        .xml()
+        .replace("value=\"2\"", "value=\"2\" broken")
+        .replace("value=\"5\"", "value=\"5\" broken");
+    let line_of = |text: &str| xml[..xml.find(text).unwrap()].matches('\n').count() as u64 + 1;
+
+    let progress = Progress::new(true, false);
+    let task = progress.task("parsing", None, Unit::Records);
+    let parsed = parse_records(&xml, &type_filter(&[HEART_RATE], &[]), &task, true).unwrap();
+
+    assert_eq!(parsed.records.len(), 4);
+    let lines: Vec<u64> = parsed.errors.iter().map(|error| error.line).collect();
+    assert_eq!(
+        lines,
+        [line_of("value=\"2\" broken"), line_of("value=\"5\" broken")]
+    );
+    let samples = &parsed.diagnostics.skipped[&SkipReason::MalformedXml].samples;
+    let sample_lines: Vec<u64> = samples.iter().map(|sample| sample.line).collect();
+    assert_eq!(sample_lines, lines);
+}
+
+#[test]
+fn attributes_before_the_type_are_kept() {
+    // Moves each record's type from the front of its tag to the end.
+    let xml: String = ExportBuilder::new()
+        .record(HEART_RATE, "count/min", "72", days_ago(3))
+        .record(STEP_COUNT, "count", "800", days_ago(3))
+        .record(HEART_RATE, "count/min", "60", days_ago(400))
+        .xml()
+        .lines()
+        .map(|line| match line.split_once("<Record type=\"") {
+            Some((indent, rest)) => {
+                let (record_type, attributes) = rest.split_once("\" ").unwrap();
+                let attributes = attributes.trim_end_matches("/>");
+                format!(
+                    "{}<Record {} type=\"{}\"/>\n",
+                    indent, attributes, record_type
+                )
+            }
+            None => format!("{}\n", line),
+        })
+        .collect();
+    assert!(xml.contains(r#"endDate=""#) && !xml.contains(r#"<Record type="#));
+
+    let parsed = parse(&xml, &type_filter(&[HEART_RATE], &[]));
+
+    assert_eq!(
+        types_and_values(&parsed),
+        [(HEART_RATE.to_string(), "72".to_string())]
+    );
+    let record = &parsed.records[0];
+    assert_eq!(record.unit.as_deref(), Some("count/min"));
+    assert_eq!(record.source_name.as_deref(), Some("Apple Watch"));
+    assert!(record.start_date.is_some() && record.end_date.is_some());
+    assert_eq!(
+        parsed.diagnostics.skipped[&SkipReason::FilteredByType].count,
+        1
+    );
+    assert_eq!(
+        parsed.diagnostics.skipped[&SkipReason::FilteredByDate].count,
+        1
+    );
+}
@@ -0,0 +1,147 @@
+mod common;
+
+use apple_health_export_parser_rs::HealthRecord;
+use apple_health_export_parser_rs::query::Query;
+use chrono::{TimeZone, Utc};
+use common::{ExportBuilder, HEART_RATE, STEP_COUNT, days_ago, parse, type_filter};
+
+fn records() -> Vec<HealthRecord> {
+    let yesterday = days_ago(1).date_naive();
+    let at = |hour: u32| Utc.from_utc_datetime(&yesterday.and_hms_opt(hour, 15, 0).unwrap());
+    let xml = ExportBuilder::new()
+        .record(HEART_RATE, "count/min", "158", at(23))
+        .record(HEART_RATE, "count/min", "162", at(14))
+        .record(HEART_RATE, "count/min", "55", at(3))
+        .record_with_metadata(
+            HEART_RATE,
+            "count/min",
+            "171",
+            at(2),
+            &[("HKActivityType", "37")],
+        )
+        .record(STEP_COUNT, "count", "1500", at(23))
+        .xml()
+        .replacen("sourceName=\"Apple Watch\"", "sourceName=\"iPhone\"", 1);
+    parse(&xml, &type_filter(&["*"], &[])).records
+}
+
+/// Values of the records `query` selects, in order.
+fn selected(query: &str) -> Vec<String> {
+    let query = Query::parse(query).unwrap();
+    records()
+        .iter()
+        .filter(|record| query.matches(record))
+        .map(|record| record.value.as_deref().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn example_query_selects_fast_heart_rates_at_night_from_the_watch() {
+    // The first record, from the iPhone, is the only other night-time match.
+    assert_eq!(
+        selected(
+            r#"type = HeartRate and value > 150 and source ~ "Watch" and hour(start) between 22 and 6"#
+        ),
+        ["171"]
+    );
+}
+
+#[test]
+fn types_match_names_identifiers_globs_and_groups() {
+    assert_eq!(selected("type = heartrate").len(), 4);
+    assert_eq!(selected(&format!("type = {}", STEP_COUNT)), ["1500"]);
+    assert_eq!(selected("type = *Step*"), ["1500"]);
+    assert_eq!(selected("type = @activity"), ["1500"]);
+    assert_eq!(selected("type != @heart"), ["1500"]);
+}
+
+#[test]
+fn and_binds_tighter_than_or() {
+    assert_eq!(
+        selected("type = StepCount or value < 100 and hour(start) < 6"),
+        ["55", "1500"]
+    );
+    assert_eq!(
+        selected("(type = StepCount or value < 100) and hour(start) > 6"),
+        ["1500"]
+    );
+    assert_eq!(selected("not value > 100"), ["55"]);
+}
+
+#[test]
+fn dates_compare_on_the_literal_length() {
+    let day = days_ago(1).format("%Y-%m-%d").to_string();
+    assert_eq!(selected(&format!("start = {}", day)).len(), 5);
+    assert_eq!(selected(&format!("start > {}", day)).len(), 0);
+    assert_eq!(
+        selected(&format!(
+            "start >= '{} 14:00' and start < '{} 23'",
+            day, day
+        )),
+        ["162"]
+    );
+    let weekday = days_ago(1).format("%A").to_string();
+    assert_eq!(selected(&format!("weekday(start) = {}", weekday)).len(), 5);
+    assert_eq!(selected("duration = 0").len(), 5);
+}
+
+#[test]
+fn missing_fields_never_match() {
+    assert_eq!(selected("metadata.HKActivityType = Running"), ["171"]);
+    assert_eq!(selected("metadata.HKActivityType != Running").len(), 0);
+}
+
+#[test]
+fn queries_see_records_of_any_age_once_the_cutoff_is_lifted() {
+    let xml = ExportBuilder::new()
+        .record(HEART_RATE, "count/min", "80", days_ago(400))
+        .record(
+            "HKQuantityTypeIdentifierBloodGlucose",
+            "mg/dL",
+            "95",
+            days_ago(500),
+        )
+        .record(HEART_RATE, "count/min", "70", days_ago(1))
+        .xml();
+    let year_ago = days_ago(366).format("%Y-%m-%d").to_string();
+    let query = Query::parse(&format!("start < {}", year_ago)).unwrap();
+    let values = |filter| {
+        parse(&xml, &filter)
+            .records
+            .into_iter()
+            .filter(|record| query.matches(record))
+            .map(|record| record.value.unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert!(values(type_filter(&[], &[])).is_empty());
+    assert_eq!(
+        values(type_filter(&["*"], &[]).with_all_dates()),
+        ["80", "95"]
+    );
+    assert_eq!(
+        values(type_filter(&[HEART_RATE], &[]).with_all_dates()),
+        ["80"]
+    );
+}
+
+#[test]
+fn errors_point_at_the_problem() {
+    let error = |query: &str| Query::parse(query).unwrap_err();
+
+    assert_eq!(error("value >> 3"), "expected a value at column 8");
+    assert_eq!(
+        error("hour(start) = late"),
+        "expected a number, not 'late' at column 15"
+    );
+    assert!(error("colour = red").starts_with("unknown field 'colour'"));
+    assert_eq!(
+        error("type > HeartRate"),
+        "type only supports =, != and ~ at column 1"
+    );
+    assert_eq!(error("(value > 1"), "expected ')' at column 11");
+    assert_eq!(
+        error("source ~ \"Watch"),
+        "unterminated string at column 10"
+    );
+}
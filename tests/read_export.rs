@@ -0,0 +1,94 @@
+mod common;
+
+use apple_health_export_parser_rs::error::ParseError;
+use apple_health_export_parser_rs::input::{Document, ExportInput};
+use apple_health_export_parser_rs::progress::Progress;
+use apple_health_export_parser_rs::{parse_export, read_export_xml};
+use common::{ExportBuilder, HEART_RATE, days_ago, type_filter};
+use std::fs;
+
+fn sample_export() -> ExportBuilder {
+    let mut export = ExportBuilder::new();
+    export
+        .record(HEART_RATE, "count/min", "72", days_ago(2))
+        .workout("HKWorkoutActivityTypeRunning", days_ago(2), 30, true)
+        .workout("HKWorkoutActivityTypeYoga", days_ago(1), 20, false);
+    export
+}
+
+#[test]
+fn reads_export_xml_from_a_zip() {
+    let dir = tempfile::tempdir().unwrap();
+    let export = sample_export();
+    let path = export.write_zip(dir.path());
+
+    let input = ExportInput::detect(&path, Document::Export).unwrap();
+    match &input {
+        ExportInput::Zip { entry, .. } => assert_eq!(entry, "apple_health_export/export.xml"),
+        ExportInput::Xml(path) => panic!("detected {} as bare XML", path.display()),
+    }
+    let mapped = read_export_xml(&input, &Progress::new(true, false)).unwrap();
+    assert_eq!(&mapped[..], export.xml().as_bytes());
+}
+
+#[test]
+fn reads_export_xml_from_an_extracted_directory_and_a_bare_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let nested = dir.path().join("apple_health_export");
+    fs::create_dir(&nested).unwrap();
+    let export = sample_export();
+    let path = export.write_xml(&nested);
+
+    for candidate in [dir.path(), path.as_path()] {
+        let input = ExportInput::detect(candidate, Document::Export).unwrap();
+        assert_eq!(input.source_path(), path);
+        let mapped = read_export_xml(&input, &Progress::new(true, false)).unwrap();
+        assert_eq!(&mapped[..], export.xml().as_bytes());
+    }
+}
+
+#[test]
+fn zip_without_the_requested_document_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = sample_export().write_zip(dir.path());
+
+    match ExportInput::detect(&path, Document::Cda) {
+        Err(ParseError::MissingExport { document, .. }) => {
+            assert_eq!(document, "export_cda.xml")
+        }
+        other => panic!("expected MissingExport, got {:?}", other),
+    }
+}
+
+#[test]
+fn workouts_routes_and_new_elements_are_kept_as_unknown_elements() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("export.xml");
+    let xml = sample_export().xml().replace(
+        "</HealthData>",
+        " <FutureSample kind=\"new\">\n  <FutureDetail value=\"1\"/>\n </FutureSample>\n</HealthData>",
+    );
+    fs::write(&path, xml).unwrap();
+    let input = ExportInput::detect(&path, Document::Export).unwrap();
+
+    let parsed = parse_export(
+        &input,
+        Document::Export,
+        &type_filter(&[], &[]),
+        true,
+        false,
+        &Progress::new(true, false),
+    )
+    .unwrap();
+
+    assert_eq!(parsed.records.len(), 1);
+    assert_eq!(parsed.unknown.counts.get("Workout"), Some(&2));
+    assert_eq!(parsed.unknown.counts.get("ExportDate"), Some(&1));
+    assert_eq!(parsed.unknown.counts.get("Me"), Some(&1));
+    assert_eq!(parsed.unknown.counts.get("FutureSample"), Some(&1));
+
+    let elements = serde_json::to_string(&parsed.unknown.elements).unwrap();
+    assert!(elements.contains("\"FutureDetail\""));
+    assert_eq!(elements.matches("\"WorkoutRoute\"").count(), 1);
+    assert!(elements.contains("/workout-routes/route_"));
+}
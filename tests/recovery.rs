@@ -0,0 +1,197 @@
+use apple_health_export_parser_rs::diagnostics::DocumentIssue;
+use apple_health_export_parser_rs::error::ParseError;
+use apple_health_export_parser_rs::input::{Document, ExportInput};
+use apple_health_export_parser_rs::progress::Progress;
+use apple_health_export_parser_rs::recovery::{complete_len, root_start};
+use apple_health_export_parser_rs::type_filter::{FilterConfig, TypeFilter};
+use apple_health_export_parser_rs::{ParsedExport, parse_export};
+use chrono::Utc;
+use std::fs;
+
+const BROKEN_DTD: &str = r#"<!DOCTYPE HealthData [
+<!ELEMENT HealthData (ExportDate,Me,(Record|Correlation|Workout)*)>
+<!ATTLIST HealthData
+  locale CDATA #REQUIRED
+<!ELEMENT ExportDate EMPTY>
+<!ATTLIST Record
+  type CDATA #REQUIRED
+  unit CDATA "<Record "
+>
+"#;
+
+fn record(hour: u32, value: u32) -> String {
+    let date = format!("{} {:02}:00:00 +0000", Utc::now().format("%Y-%m-%d"), hour);
+    format!(
+        r#"<Record type="HKQuantityTypeIdentifierHeartRate" sourceName="Watch" unit="count/min" startDate="{date}" endDate="{date}" value="{value}""#
+    )
+}
+
+fn document(prolog: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}<HealthData locale=\"en_GB\">\n{}",
+        prolog, body
+    )
+}
+
+/// Parses `xml` as a bare export.xml in strict mode.
+fn parse(xml: &str) -> ParsedExport {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("export.xml");
+    fs::write(&path, xml).unwrap();
+    let type_filter = TypeFilter::from_config(&FilterConfig::default()).unwrap();
+    parse_export(
+        &ExportInput::Xml(path),
+        Document::Export,
+        &type_filter,
+        true,
+        false,
+        &Progress::new(true, false),
+    )
+    .unwrap()
+}
+
+fn values(parsed: &ParsedExport) -> Vec<&str> {
+    parsed
+        .records
+        .iter()
+        .map(|record| record.value.as_deref().unwrap())
+        .collect()
+}
+
+#[test]
+fn root_is_found_past_a_broken_dtd() {
+    let xml = document(BROKEN_DTD, "</HealthData>\n");
+    let start = root_start(&xml, "HealthData").unwrap();
+    assert!(xml[start..].starts_with("<HealthData locale"));
+    assert_eq!(complete_len(&xml, start, "HealthData"), None);
+}
+
+#[test]
+fn broken_dtd_does_not_hide_top_level_elements() {
+    let body = format!(
+        " <ExportDate value=\"2026-01-01\"/>\n {}/>\n <Me sex=\"\"/>\n <Foo a=\"1\"/>\n</HealthData>\n",
+        record(10, 70)
+    );
+    let parsed = parse(&document(BROKEN_DTD, &body));
+
+    assert_eq!(values(&parsed), ["70"]);
+    assert_eq!(parsed.unknown.counts.len(), 3);
+    assert_eq!(parsed.unknown.counts.get("ExportDate"), Some(&1));
+    assert_eq!(parsed.unknown.counts.get("Me"), Some(&1));
+    assert_eq!(parsed.unknown.counts.get("Foo"), Some(&1));
+    assert_eq!(parsed.unknown.elements.len(), 3);
+}
+
+#[test]
+fn malformed_xml_outside_records_is_reported() {
+    let body = format!(
+        " {}/>\n <Me sex=\"\"></Mx>\n <Foo a=\"1\"/>\n</HealthData>\n",
+        record(10, 70)
+    );
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("export.xml");
+    fs::write(&path, document("", &body)).unwrap();
+    let type_filter = TypeFilter::from_config(&FilterConfig::default()).unwrap();
+    let parse = |lenient| {
+        parse_export(
+            &ExportInput::Xml(path.clone()),
+            Document::Export,
+            &type_filter,
+            true,
+            lenient,
+            &Progress::new(true, false),
+        )
+    };
+
+    match parse(false) {
+        Err(ParseError::Xml { line, .. }) => assert_eq!(line, 4),
+        other => panic!("expected an XML error, got {:?}", other.map(|p| p.errors)),
+    }
+    let parsed = parse(true).unwrap();
+    assert_eq!(values(&parsed), ["70"]);
+    assert!(parsed.errors.is_empty());
+    assert!(parsed.diagnostics.skipped.is_empty());
+    let malformed = &parsed.diagnostics.document[&DocumentIssue::MalformedXml];
+    assert_eq!(malformed.count, 1);
+    assert_eq!(malformed.samples[0].line, 4);
+    assert!(malformed.samples[0].detail.starts_with("in <Me>"));
+}
+
+#[test]
+fn unclosed_element_outside_records_is_reported() {
+    let body = format!(
+        " {}/>\n <Me sex=\"\">\n <Foo a=\"1\"/>\n</HealthData>\n",
+        record(10, 70)
+    );
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("export.xml");
+    fs::write(&path, document("", &body)).unwrap();
+    let type_filter = TypeFilter::from_config(&FilterConfig::default()).unwrap();
+    let parsed = parse_export(
+        &ExportInput::Xml(path),
+        Document::Export,
+        &type_filter,
+        true,
+        false,
+        &Progress::new(true, false),
+    );
+
+    match parsed {
+        Err(ParseError::Xml { message, .. }) => assert!(message.starts_with("in <Me>")),
+        other => panic!("expected an XML error, got {:?}", other.map(|p| p.errors)),
+    }
+}
+
+#[test]
+fn duplicated_attributes_are_accepted() {
+    let body = format!(
+        " {} value=\"71\" sourceName=\"Watch\">\n  <MetadataEntry key=\"HKActivityType\" key=\"HKActivityType\" value=\"1\"/>\n </Record>\n <Foo a=\"1\" a=\"2\"/>\n</HealthData>\n",
+        record(10, 70)
+    );
+    let parsed = parse(&document("", &body));
+
+    assert!(parsed.errors.is_empty());
+    assert_eq!(values(&parsed), ["71"]);
+    assert!(parsed.records[0].metadata.contains_key("HKActivityType"));
+    assert_eq!(parsed.unknown.counts.get("Foo"), Some(&1));
+}
+
+#[test]
+fn truncated_file_keeps_every_complete_element() {
+    let complete = format!(
+        " {}/>\n {}>\n  <MetadataEntry key=\"HKActivityType\" value=\"1\"/>\n </Record>\n",
+        record(10, 70),
+        record(11, 71)
+    );
+    // Cut inside an attribute, inside a child, and between children.
+    let tails = [
+        format!(" {}", &record(12, 72)[..60]),
+        format!(" {}>\n  <MetadataEntry key=\"HKAct", record(12, 72)),
+        format!(
+            " {}>\n  <MetadataEntry key=\"HKActivityType\" value=\"1\"/>\n",
+            record(12, 72)
+        ),
+    ];
+
+    for tail in tails {
+        let xml = document(BROKEN_DTD, &format!("{}{}", complete, tail));
+        let parsed = parse(&xml);
+
+        assert_eq!(values(&parsed), ["70", "71"], "tail {:?}", tail);
+        let truncated = &parsed.diagnostics.document[&DocumentIssue::Truncated];
+        assert_eq!(truncated.count, 1);
+        assert!(truncated.samples[0].excerpt.starts_with("<Record"));
+    }
+}
+
+#[test]
+fn truncated_file_cut_inside_the_root_tag_has_no_records() {
+    let xml = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<HealthData loc";
+    let parsed = parse(xml);
+
+    assert!(parsed.records.is_empty());
+    assert_eq!(
+        parsed.diagnostics.document[&DocumentIssue::Truncated].count,
+        1
+    );
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use apple_health_export_parser_rs::diagnostics::SkipReason;
+use apple_health_export_parser_rs::progress::{Progress, Unit};
+use apple_health_export_parser_rs::state_of_mind::{
+    StateOfMindAssociation, StateOfMindKind, StateOfMindLabel, ValenceClassification,
+    parse_state_of_mind,
+};
+use common::{ExportBuilder, apple_date, days_ago, type_filter};
+
+fn entry(days: i64, valence: &str) -> String {
+    let date = apple_date(days_ago(days));
+    format!(
+        r#" <StateOfMind kind="HKStateOfMindKindDailyMood" valence="{valence}" valenceClassification="HKStateOfMindValenceClassificationPleasant" sourceName="iPhone" startDate="{date}" endDate="{date}">
+  <Label value="HKStateOfMindLabelHappy"/>
+  <Label value="HKStateOfMindLabelCalm"/>
+  <Association value="HKStateOfMindAssociationFamily"/>
+ </StateOfMind>
+"#
+    )
+}
+
+fn export(entries: &str) -> String {
+    ExportBuilder::new()
+        .xml()
+        .replace("</HealthData>", &format!("{}</HealthData>", entries))
+}
+
+#[test]
+fn entries_in_the_window_are_parsed_with_labels_and_associations() {
+    let xml = export(&format!("{}{}", entry(3, "0.6"), entry(400, "-0.2")));
+    let progress = Progress::new(true, false);
+    let task = progress.task("parsing", None, Unit::Records);
+
+    let parsed = parse_state_of_mind(&xml, &type_filter(&[], &[]), &task, false).unwrap();
+
+    assert_eq!(parsed.records.len(), 1);
+    let mood = &parsed.records[0];
+    assert_eq!(mood.kind, StateOfMindKind::DailyMood);
+    assert_eq!(mood.valence, Some(0.6));
+    assert_eq!(
+        mood.valence_classification,
+        Some(ValenceClassification::Pleasant)
+    );
+    assert_eq!(
+        mood.labels,
+        [StateOfMindLabel::Happy, StateOfMindLabel::Calm]
+    );
+    assert_eq!(mood.associations, [StateOfMindAssociation::Family]);
+    assert_eq!(mood.source_name.as_deref(), Some("iPhone"));
+    assert_eq!(
+        parsed.diagnostics.skipped[&SkipReason::FilteredByDate].count,
+        1
+    );
+
+    let all_dates = type_filter(&[], &[]).with_all_dates();
+    let parsed = parse_state_of_mind(&xml, &all_dates, &task, false).unwrap();
+    assert_eq!(parsed.records.len(), 2);
+}
+
+#[test]
+fn malformed_entries_are_reported_and_skipped_in_lenient_mode() {
+    let broken = entry(2, "0.1").replace("<Label value", "<Label broken value");
+    let xml = export(&format!("{}{}", broken, entry(1, "0.3")));
+    let progress = Progress::new(true, false);
+    let task = progress.task("parsing", None, Unit::Records);
+    let filter = type_filter(&[], &[]);
+
+    assert!(parse_state_of_mind(&xml, &filter, &task, false).is_err());
+
+    let parsed = parse_state_of_mind(&xml, &filter, &task, true).unwrap();
+    assert_eq!(parsed.records.len(), 1);
+    assert_eq!(parsed.records[0].valence, Some(0.3));
+    assert_eq!(parsed.errors.len(), 1);
+    assert_eq!(parsed.errors[0].element, "StateOfMind");
+    let line = xml[..xml.find("<Label broken").unwrap()]
+        .matches('\n')
+        .count()
+        + 1;
+    assert_eq!(parsed.errors[0].line, line as u64);
+}
+
+#[test]
+fn entities_in_attributes_are_unescaped() {
+    let xml = export(&entry(3, "0.6").replace(
+        r#"sourceName="iPhone""#,
+        r#"sourceName="Ann&apos;s iPhone""#,
+    ));
+    let progress = Progress::new(true, false);
+    let task = progress.task("parsing", None, Unit::Records);
+
+    let parsed = parse_state_of_mind(&xml, &type_filter(&[], &[]), &task, false).unwrap();
+
+    assert_eq!(
+        parsed.records[0].source_name.as_deref(),
+        Some("Ann's iPhone")
+    );
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use apple_health_export_parser_rs::health_type::{RecordType, TypeGroup};
+use apple_health_export_parser_rs::summary::{TypeSummary, summarize};
+use common::{ExportBuilder, HEART_RATE, STEP_COUNT, days_ago, parse, type_filter};
+
+#[test]
+fn values_combine_by_aggregation_under_each_group() {
+    let xml = ExportBuilder::new()
+        .record(HEART_RATE, "count/min", "60", days_ago(2))
+        .record(STEP_COUNT, "count", "1200", days_ago(2))
+        .record(HEART_RATE, "count/min", "90", days_ago(1))
+        .record(STEP_COUNT, "count", "800", days_ago(1))
+        .record(
+            "HKQuantityTypeIdentifierRespiratoryRate",
+            "count/min",
+            "14",
+            days_ago(1),
+        )
+        .record(
+            "HKCategoryTypeIdentifierSleepAnalysis",
+            "",
+            "HKCategoryValueSleepAnalysisAsleepCore",
+            days_ago(1),
+        )
+        .xml();
+    let records = parse(&xml, &type_filter(&["*"], &[])).records;
+
+    let summary = summarize(&records);
+
+    let of = |group, name| &summary[&group][&RecordType::from_identifier(name)];
+    assert_eq!(
+        of(TypeGroup::Activity, STEP_COUNT),
+        &TypeSummary {
+            records: 2,
+            values: 2,
+            total: Some(2000.0),
+            ..TypeSummary::default()
+        }
+    );
+    assert_eq!(
+        of(TypeGroup::Heart, HEART_RATE),
+        &TypeSummary {
+            records: 2,
+            values: 2,
+            mean: Some(75.0),
+            min: Some(60.0),
+            max: Some(90.0),
+            ..TypeSummary::default()
+        }
+    );
+    assert_eq!(
+        of(TypeGroup::Respiratory, "RespiratoryRate").mean,
+        Some(14.0)
+    );
+    assert_eq!(
+        of(TypeGroup::Sleep, "SleepAnalysis"),
+        &TypeSummary {
+            records: 1,
+            ..TypeSummary::default()
+        }
+    );
+    assert_eq!(summary.len(), 4);
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use apple_health_export_parser_rs::health_type::{TypeGroup, in_group};
+use apple_health_export_parser_rs::type_filter::{DEFAULT_TYPES, FilterConfig, TypeFilter};
+use common::{HEART_RATE, STEP_COUNT, type_filter};
+use std::fs;
+
+#[test]
+fn globs_match_any_run_or_any_one_character() {
+    let cases = [
+        ("*", "", true),
+        ("*", HEART_RATE, true),
+        ("HK*", HEART_RATE, true),
+        ("*HeartRate", HEART_RATE, true),
+        (
+            "*HeartRate",
+            "HKQuantityTypeIdentifierRestingHeartRate",
+            true,
+        ),
+        (
+            "*HeartRate",
+            "HKQuantityTypeIdentifierHeartRateVariabilitySDNN",
+            false,
+        ),
+        (
+            "*Heart*",
+            "HKQuantityTypeIdentifierHeartRateVariabilitySDNN",
+            true,
+        ),
+        (
+            "HKQuantityTypeIdentifier*Rate",
+            "HKQuantityTypeIdentifierRate",
+            true,
+        ),
+        ("*a*a*a", "HKaXaX", false),
+        ("*a*a*a", "HKaXaXa", true),
+        ("**Count", STEP_COUNT, true),
+        ("HKQuantityTypeIdentifierStepCoun?", STEP_COUNT, true),
+        ("HKQuantityTypeIdentifierStepCount?", STEP_COUNT, false),
+        ("?", "", false),
+        ("hk*", HEART_RATE, false),
+    ];
+
+    for (pattern, record_type, expected) in cases {
+        assert_eq!(
+            type_filter(&[pattern], &[]).allows(record_type),
+            expected,
+            "{} against {:?}",
+            pattern,
+            record_type
+        );
+    }
+}
+
+#[test]
+fn groups_expand_to_their_types() {
+    let filter = type_filter(&["@Respiratory"], &[]);
+
+    for health_type in in_group(TypeGroup::Respiratory) {
+        assert!(filter.allows(health_type.identifier));
+    }
+    assert!(filter.allows("HKQuantityTypeIdentifierRespiratoryRate"));
+    assert!(!filter.allows(HEART_RATE));
+
+    let error = TypeFilter::from_config(&FilterConfig {
+        include: vec!["@cardio".to_string()],
+        exclude: Vec::new(),
+    })
+    .unwrap_err();
+    assert!(error.contains("unknown type group 'cardio'"), "{}", error);
+}
+
+#[test]
+fn exclusions_win_over_inclusions() {
+    let filter = type_filter(&["@heart", "*Count"], &[HEART_RATE, "*Flights*"]);
+
+    assert!(!filter.allows(HEART_RATE));
+    assert!(filter.allows("HKQuantityTypeIdentifierRestingHeartRate"));
+    assert!(filter.allows(STEP_COUNT));
+    assert!(!filter.allows("HKQuantityTypeIdentifierFlightsClimbed"));
+}
+
+#[test]
+fn default_types_apply_without_inclusions() {
+    let filter = type_filter(&[], &[STEP_COUNT]);
+
+    for record_type in DEFAULT_TYPES.iter().filter(|t| **t != STEP_COUNT) {
+        assert!(filter.allows(record_type), "{}", record_type);
+    }
+    assert!(!filter.allows(STEP_COUNT));
+    assert!(!filter.allows("HKQuantityTypeIdentifierBodyTemperature"));
+}
+
+#[test]
+fn profiles_load_from_toml_and_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let toml = dir.path().join("heart.toml");
+    fs::write(
+        &toml,
+        "include = [\"@heart\"]\nexclude = [\"*Variability*\"]\n",
+    )
+    .unwrap();
+    let json = dir.path().join("heart.json");
+    fs::write(
+        &json,
+        r#"{"include": ["@heart"], "exclude": ["*Variability*"]}"#,
+    )
+    .unwrap();
+
+    for path in [&toml, &json] {
+        let filter = TypeFilter::from_config(&FilterConfig::load(path).unwrap()).unwrap();
+        assert!(filter.allows(HEART_RATE));
+        assert!(!filter.allows("HKQuantityTypeIdentifierHeartRateVariabilitySDNN"));
+    }
+
+    fs::write(&toml, "includes = [\"@heart\"]\n").unwrap();
+    assert!(FilterConfig::load(&toml).is_err());
+}
+
+#[test]
+fn descriptions_are_stable_and_name_the_cutoff() {
+    let a = type_filter(&[STEP_COUNT, HEART_RATE], &["*Flights*"]);
+    let b = type_filter(&[HEART_RATE, STEP_COUNT], &["*Flights*"]);
+
+    assert_eq!(a.describe(), b.describe());
+    assert!(a.describe()[0].starts_with("include="));
+    assert!(a.describe().last().unwrap().starts_with("cutoff=20"));
+    assert_eq!(a.with_all_dates().describe().last().unwrap(), "cutoff=none");
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use apple_health_export_parser_rs::progress::{Progress, Unit};
+use apple_health_export_parser_rs::vision_prescription::parse_vision_prescriptions;
+use common::ExportBuilder;
+
+const PRESCRIPTION: &str = r#" <VisionPrescription type="HKVisionPrescriptionTypeGlasses" dateIssued="2026-03-01 09:00:00 +0000" expirationDate="2028-03-01 09:00:00 +0000" brand="Acme">
+  <RightEye sphere="-1.25" sphereUnit="D" cylinder="-0.5" axis="90" axisUnit="deg"/>
+  <LeftEye sphere="-1.5" sphereUnit="D"/>
+  <MetadataEntry key="HKMetadataKeyOptometrist" value="Dr. Lens"/>
+ </VisionPrescription>
+"#;
+
+#[test]
+fn prescriptions_are_parsed_with_both_eyes() {
+    let xml = ExportBuilder::new()
+        .xml()
+        .replace("</HealthData>", &format!("{}</HealthData>", PRESCRIPTION));
+    let progress = Progress::new(true, false);
+    let task = progress.task("parsing", None, Unit::Records);
+
+    let parsed = parse_vision_prescriptions(&xml, &task, false).unwrap();
+
+    assert_eq!(parsed.records.len(), 1);
+    let prescription = &parsed.records[0];
+    assert_eq!(prescription.brand.as_deref(), Some("Acme"));
+    let right = prescription.right_eye.as_ref().unwrap();
+    let sphere = right.sphere.as_ref().unwrap();
+    assert_eq!(sphere.value, -1.25);
+    assert_eq!(sphere.unit.as_deref(), Some("D"));
+    assert_eq!(right.axis.as_ref().unwrap().value, 90.0);
+    assert!(right.cylinder.as_ref().unwrap().unit.is_none());
+    let left = prescription.left_eye.as_ref().unwrap();
+    assert!(left.cylinder.is_none());
+    assert_eq!(
+        prescription.metadata["HKMetadataKeyOptometrist"].as_str(),
+        "Dr. Lens"
+    );
+}
+
+#[test]
+fn malformed_prescriptions_fail_unless_lenient() {
+    let broken = PRESCRIPTION.replace("<LeftEye sphere", "<LeftEye broken sphere");
+    let xml = ExportBuilder::new().xml().replace(
+        "</HealthData>",
+        &format!("{}{}</HealthData>", broken, PRESCRIPTION),
+    );
+    let progress = Progress::new(true, false);
+    let task = progress.task("parsing", None, Unit::Records);
+
+    assert!(parse_vision_prescriptions(&xml, &task, false).is_err());
+
+    let parsed = parse_vision_prescriptions(&xml, &task, true).unwrap();
+    assert_eq!(parsed.records.len(), 1);
+    assert_eq!(parsed.errors.len(), 1);
+    assert_eq!(parsed.errors[0].element, "VisionPrescription");
+}
@@ -0,0 +1,93 @@
+use apple_health_export_parser_rs::workout_activity::{WorkoutActivityType, WorkoutCategory};
+
+#[test]
+fn every_code_round_trips_through_u32() {
+    for code in 0..=4000 {
+        assert_eq!(WorkoutActivityType::from_u32(code).as_u32(), code);
+    }
+}
+
+#[test]
+fn every_named_type_round_trips_through_strings_and_serde() {
+    let mut count = 0;
+    for activity in WorkoutActivityType::all() {
+        count += 1;
+        let identifier = activity
+            .identifier()
+            .expect("named types have an identifier");
+        assert!(identifier.starts_with("HKWorkoutActivityType"));
+        assert_eq!(identifier.parse::<WorkoutActivityType>(), Ok(activity));
+        assert_eq!(
+            activity.to_string().parse::<WorkoutActivityType>(),
+            Ok(activity)
+        );
+        assert_eq!(
+            activity
+                .to_string()
+                .to_uppercase()
+                .parse::<WorkoutActivityType>(),
+            Ok(activity)
+        );
+        assert_eq!(
+            activity.as_u32().to_string().parse::<WorkoutActivityType>(),
+            Ok(activity)
+        );
+
+        let json = serde_json::to_string(&activity).unwrap();
+        assert_eq!(json, format!("\"{}\"", identifier));
+        assert_eq!(
+            serde_json::from_str::<WorkoutActivityType>(&json).unwrap(),
+            activity
+        );
+    }
+    // Codes 1 to 84 without the unnamed 81, plus Other (3000).
+    assert_eq!(count, 84);
+}
+
+#[test]
+fn unnamed_codes_stay_unknown() {
+    let unknown = WorkoutActivityType::from_u32(81);
+    assert_eq!(unknown, WorkoutActivityType::Unknown(81));
+    assert_eq!(unknown.identifier(), None);
+    assert_eq!(unknown.category(), WorkoutCategory::Other);
+    assert_eq!(unknown.to_string(), "Unknown(81)");
+    assert_eq!("Unknown(81)".parse::<WorkoutActivityType>(), Ok(unknown));
+
+    let json = serde_json::to_string(&unknown).unwrap();
+    assert_eq!(
+        serde_json::from_str::<WorkoutActivityType>(&json).unwrap(),
+        unknown
+    );
+
+    assert!(
+        "HKWorkoutActivityTypeMoonWalking"
+            .parse::<WorkoutActivityType>()
+            .is_err()
+    );
+}
+
+#[test]
+fn categories_group_related_activities() {
+    let category = |name: &str| name.parse::<WorkoutActivityType>().unwrap().category();
+    assert_eq!(
+        category("HKWorkoutActivityTypeRunning"),
+        WorkoutCategory::Cardio
+    );
+    assert_eq!(
+        category("HKWorkoutActivityTypeTraditionalStrengthTraining"),
+        WorkoutCategory::Strength
+    );
+    assert_eq!(
+        category("HKWorkoutActivityTypeSwimming"),
+        WorkoutCategory::Water
+    );
+    assert_eq!(
+        category("HKWorkoutActivityTypeSnowboarding"),
+        WorkoutCategory::Winter
+    );
+    assert_eq!(
+        category("HKWorkoutActivityTypeSoccer"),
+        WorkoutCategory::TeamSports
+    );
+    assert_eq!(category("Mind and Body"), WorkoutCategory::MindAndBody);
+}
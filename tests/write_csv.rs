@@ -0,0 +1,92 @@
+mod common;
+
+use apple_health_export_parser_rs::{write_csv, write_csv_per_type};
+use common::{ExportBuilder, HEART_RATE, STEP_COUNT, apple_date, days_ago, parse, type_filter};
+use std::fs;
+
+#[test]
+fn writes_one_row_per_record_with_metadata_as_json() {
+    let start = days_ago(1);
+    let xml = ExportBuilder::new()
+        .record_with_metadata(
+            HEART_RATE,
+            "count/min",
+            "150",
+            start,
+            &[("HKActivityType", "13")],
+        )
+        .record(STEP_COUNT, "count", "42", start)
+        .xml();
+    let parsed = parse(&xml, &type_filter(&[], &[]));
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("output.csv");
+    write_csv(&parsed.records, &path).unwrap();
+
+    let mut reader = csv::Reader::from_path(&path).unwrap();
+    assert_eq!(
+        reader.headers().unwrap(),
+        vec![
+            "record_type",
+            "value",
+            "unit",
+            "start_date",
+            "end_date",
+            "metadata",
+            "source_name",
+            "export_id",
+        ]
+    );
+    let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+    let date = apple_date(start);
+    assert_eq!(
+        rows[0],
+        vec![
+            HEART_RATE,
+            "150",
+            "count/min",
+            &date,
+            &date,
+            r#"{"HKActivityType":"Cycling"}"#,
+            "Apple Watch",
+            "",
+        ]
+    );
+    assert_eq!(
+        rows[1],
+        vec![
+            STEP_COUNT,
+            "42",
+            "count",
+            &date,
+            &date,
+            "{}",
+            "Apple Watch",
+            ""
+        ]
+    );
+    assert_eq!(rows.len(), 2);
+}
+
+#[test]
+fn writes_one_file_per_type_named_after_the_catalogue() {
+    let xml = ExportBuilder::new()
+        .record(HEART_RATE, "count/min", "60", days_ago(1))
+        .record(STEP_COUNT, "count", "10", days_ago(1))
+        .record(HEART_RATE, "count/min", "61", days_ago(1))
+        .xml();
+    let parsed = parse(&xml, &type_filter(&[], &[]));
+
+    let dir = tempfile::tempdir().unwrap();
+    write_csv_per_type(&parsed.records, dir.path()).unwrap();
+
+    let mut names: Vec<String> = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["heart_rate.csv", "steps.csv"]);
+
+    let heart_rate = fs::read_to_string(dir.path().join("heart_rate.csv")).unwrap();
+    assert_eq!(heart_rate.lines().count(), 3);
+}